@@ -0,0 +1,895 @@
+//! This module provides ARC (Authenticated Received Chain, rfc8617) sealing and validation.
+//!
+//! Intermediaries like mailing lists often modify mails, which breaks
+//! DKIM signatures. With ARC each intermediary adds a ARC set to the
+//! (encoded) mail, consisting of:
+//!
+//! - a `ARC-Authentication-Results` header with the results of the
+//!   authentication checks done by the intermediary
+//! - a `ARC-Message-Signature` header, a DKIM like signature over the
+//!   mail as it was forwarded by the intermediary
+//! - a `ARC-Seal` header, a signature over all ARC sets of the mail
+//!
+//! All signatures use `rsa-sha256` and the `relaxed` canonicalization.
+//!
+//! The hashing and signing is done by a `ArcCrypto` implementation, the
+//! public keys needed for validation are looked up using a `ArcKeyLookup`
+//! implementation, which normally does a DNS lookup but can also be a
+//! static `HashMap` (e.g. for testing). If the `arc-openssl` feature is
+//! enabled a OpenSSL based `ArcCrypto` implementation is provided by the
+//! `openssl` sub-module.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use chrono::Utc;
+use soft_ascii_string::SoftAsciiStr;
+
+use internals::{
+    MailType,
+    bind::base64,
+    encoder::{EncodableInHeader, EncodingBuffer}
+};
+use headers::header_components::{ArcAuthenticationResults, AuthenticationResults};
+
+use crate::{
+    error::{ArcError, CryptoError},
+    raw_mail::{parse_fields, split_header_section},
+    crypto::PrivateKey
+};
+
+#[cfg(feature="arc-openssl")]
+pub mod openssl;
+
+const AUTHENTICATION_RESULTS: &str = "ARC-Authentication-Results";
+const MESSAGE_SIGNATURE: &str = "ARC-Message-Signature";
+const SEAL: &str = "ARC-Seal";
+
+/// The highest allowed instance.
+const MAX_INSTANCE: u32 = 50;
+
+/// Lines of generated headers are folded to be at most this long.
+const MAX_LINE_LEN: usize = 76;
+
+/// The headers signed by the `ARC-Message-Signature` by default (if present).
+const DEFAULT_SIGNED_HEADERS: &[&str] = &[
+    "From", "Sender", "Reply-To", "Subject", "Date", "Message-Id",
+    "To", "Cc", "In-Reply-To", "References", "List-Id",
+    "Mime-Version", "Content-Type", "Content-Transfer-Encoding"
+];
+
+/// Trait for providing the crypto operations used for ARC.
+pub trait ArcCrypto: Send + Sync + 'static {
+
+    /// Returns the SHA-256 digest of `data`.
+    fn sha256(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    /// Signs `data` using RSA with SHA-256 (PKCS#1 v1.5).
+    fn sign_rsa_sha256(&self, key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+
+    /// Verifies a RSA SHA-256 signature of `data`.
+    ///
+    /// The public key is the DER encoded `SubjectPublicKeyInfo`
+    /// from the `p=` tag of the DKIM key record.
+    fn verify_rsa_sha256(&self, public_key: &[u8], data: &[u8], signature: &[u8])
+        -> Result<(), CryptoError>;
+}
+
+/// Trait for looking up the public keys used to validate ARC sets.
+pub trait ArcKeyLookup {
+
+    /// Looks up the DKIM key record for the selector and domain.
+    ///
+    /// Normally this is the TXT record of `<selector>._domainkey.<domain>`,
+    /// e.g. `v=DKIM1; k=rsa; p=MIIBIjANBgkqh...`.
+    fn lookup_key(&self, selector: &str, domain: &str) -> Result<String, CryptoError>;
+}
+
+/// Uses the map as static key lookup, keys are in the form `<selector>._domainkey.<domain>`.
+impl ArcKeyLookup for HashMap<String, String> {
+    fn lookup_key(&self, selector: &str, domain: &str) -> Result<String, CryptoError> {
+        let name = format!("{}._domainkey.{}", selector, domain);
+        self.get(&name.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| CryptoError::MissingKey(name))
+    }
+}
+
+/// The chain validation status (the `cv=` tag of a `ARC-Seal`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChainValidationStatus {
+    /// There is no ARC chain (yet).
+    None,
+    /// The ARC chain is valid.
+    Pass,
+    /// The ARC chain is invalid.
+    Fail
+}
+
+impl ChainValidationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ChainValidationStatus::None => "none",
+            ChainValidationStatus::Pass => "pass",
+            ChainValidationStatus::Fail => "fail"
+        }
+    }
+}
+
+/// The result of validating the ARC chain of a mail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArcValidation {
+    /// The validation status, which is used as `cv=` when sealing the mail.
+    pub status: ChainValidationStatus,
+
+    /// The number of ARC sets in the mail.
+    pub instances: u32,
+
+    /// Why the validation failed, if it failed.
+    pub reason: Option<String>
+}
+
+impl ArcValidation {
+
+    /// The instance to use when adding a ARC set to the mail.
+    pub fn next_instance(&self) -> u32 {
+        self.instances + 1
+    }
+
+    fn fail(instances: u32, reason: impl Into<String>) -> Self {
+        ArcValidation {
+            status: ChainValidationStatus::Fail,
+            instances,
+            reason: Some(reason.into())
+        }
+    }
+}
+
+/// A ARC set, i.e. the three headers added by one intermediary.
+///
+/// Each header is a complete (folded) header line, without the
+/// trailing CRLF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArcSet {
+    pub instance: u32,
+    pub authentication_results: String,
+    pub message_signature: String,
+    pub seal: String
+}
+
+impl ArcSet {
+
+    /// Returns a new encoded mail with the headers of this set placed at the top.
+    pub fn prepend_to(&self, raw_mail: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(raw_mail.len() + 1024);
+        for line in &[&self.seal, &self.message_signature, &self.authentication_results] {
+            out.extend_from_slice(line.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(raw_mail);
+        out
+    }
+}
+
+/// Creates ARC sets for a given signing domain and selector.
+///
+/// # Example
+///
+/// Forwarding a mail while keeping the ARC chain:
+///
+/// ```no_run
+/// # extern crate mail_core;
+/// # extern crate mail_headers;
+/// # use std::collections::HashMap;
+/// # use mail_core::arc::{self, ArcSealer};
+/// # use mail_headers::header_components::{AuthenticationResults, MethodResult};
+/// # fn main() {}
+/// # fn forward(sealer: &ArcSealer, keys: &HashMap<String, String>, raw_mail: &[u8])
+/// #   -> Result<Vec<u8>, mail_core::error::ArcError>
+/// # {
+/// let validation = arc::validate_chain(raw_mail, sealer.crypto(), keys);
+/// let results = AuthenticationResults::new("lists.example.com")
+///     .with_result(MethodResult::new("arc", validation.status.as_str()));
+/// let set = sealer.seal(raw_mail, validation.next_instance(), results, validation.status)?;
+/// Ok(set.prepend_to(raw_mail))
+/// # }
+/// ```
+pub struct ArcSealer {
+    domain: String,
+    selector: String,
+    key: PrivateKey,
+    signed_headers: Vec<String>,
+    crypto: Arc<dyn ArcCrypto>
+}
+
+impl ArcSealer {
+
+    pub fn new<D, S>(domain: D, selector: S, key: PrivateKey, crypto: Arc<dyn ArcCrypto>) -> Self
+        where D: Into<String>, S: Into<String>
+    {
+        ArcSealer {
+            domain: domain.into(),
+            selector: selector.into(),
+            key,
+            signed_headers: DEFAULT_SIGNED_HEADERS.iter().map(|name| name.to_string()).collect(),
+            crypto
+        }
+    }
+
+    /// Sets the headers signed by the `ARC-Message-Signature` (if present in the mail).
+    pub fn with_signed_headers(mut self, signed_headers: Vec<String>) -> Self {
+        self.signed_headers = signed_headers;
+        self
+    }
+
+    pub fn crypto(&self) -> &dyn ArcCrypto {
+        &*self.crypto
+    }
+
+    /// Creates the ARC set with given instance for the encoded mail.
+    ///
+    /// The `chain_status` should be the result of validating the
+    /// chain of the mail (see `validate_chain`), i.e. `None` for
+    /// the first instance and `Pass` or `Fail` for later instances.
+    ///
+    /// The `ARC-Seal` signs all ARC sets of the mail, so all previous
+    /// instances have to be complete, except if the chain status is
+    /// `Fail`.
+    pub fn seal(
+        &self,
+        raw_mail: &[u8],
+        instance: u32,
+        results: AuthenticationResults,
+        chain_status: ChainValidationStatus
+    ) -> Result<ArcSet, ArcError> {
+        if instance == 0 || instance > MAX_INSTANCE {
+            return Err(ArcError::InvalidInstance(instance));
+        }
+
+        let (header_section, body) = split_header_section(raw_mail);
+        let fields = parse_fields(&String::from_utf8_lossy(header_section));
+        if !fields.iter().any(|(name, _)| name.eq_ignore_ascii_case("From")) {
+            return Err(ArcError::Malformed("missing From header"));
+        }
+        let sets = collect_arc_sets(&fields).map_err(ArcError::Malformed)?;
+
+        let mut seal_data = Vec::new();
+        for previous in 1..instance {
+            match sets.get(&previous).and_then(RawArcSet::complete) {
+                Some(set) => set.canonicalize_into(&mut seal_data),
+                None if chain_status == ChainValidationStatus::Fail => {},
+                None => return Err(ArcError::Malformed("incomplete ARC chain"))
+            }
+        }
+
+        let instance_str = instance.to_string();
+        let timestamp = Utc::now().timestamp().to_string();
+
+        let authentication_results = encode_authentication_results(instance, results)?;
+
+        let body_hash = encode_base64(&self.crypto.sha256(&relaxed_body(body))?);
+        let signed_headers = self.signed_headers.iter()
+            .filter(|name| fields.iter().any(|(field_name, _)| field_name.eq_ignore_ascii_case(name)))
+            .map(|name| name.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let signed_headers_str = signed_headers.join(":");
+
+        let mut ams_tags = vec![
+            ("i", instance_str.as_str()),
+            ("a", "rsa-sha256"),
+            ("c", "relaxed/relaxed"),
+            ("d", self.domain.as_str()),
+            ("s", self.selector.as_str()),
+            ("t", timestamp.as_str()),
+            ("h", signed_headers_str.as_str()),
+            ("bh", body_hash.as_str()),
+            ("b", "")
+        ];
+        let unsigned_ams = fold_tag_list(MESSAGE_SIGNATURE, &ams_tags);
+        let mut ams_data = Vec::new();
+        for (name, value) in select_headers(&fields, &signed_headers) {
+            ams_data.extend_from_slice(relaxed_header(name, value).as_bytes());
+        }
+        ams_data.extend_from_slice(signature_input_line(&unsigned_ams).as_bytes());
+        let ams_signature = encode_base64(&self.crypto.sign_rsa_sha256(&self.key, &ams_data)?);
+        ams_tags.pop();
+        ams_tags.push(("b", ams_signature.as_str()));
+        let message_signature = fold_tag_list(MESSAGE_SIGNATURE, &ams_tags);
+
+        let mut seal_tags = vec![
+            ("i", instance_str.as_str()),
+            ("a", "rsa-sha256"),
+            ("t", timestamp.as_str()),
+            ("cv", chain_status.as_str()),
+            ("d", self.domain.as_str()),
+            ("s", self.selector.as_str()),
+            ("b", "")
+        ];
+        let unsigned_seal = fold_tag_list(SEAL, &seal_tags);
+        seal_data.extend_from_slice(relaxed_header_line(&authentication_results).as_bytes());
+        seal_data.extend_from_slice(relaxed_header_line(&message_signature).as_bytes());
+        seal_data.extend_from_slice(signature_input_line(&unsigned_seal).as_bytes());
+        let seal_signature = encode_base64(&self.crypto.sign_rsa_sha256(&self.key, &seal_data)?);
+        seal_tags.pop();
+        seal_tags.push(("b", seal_signature.as_str()));
+        let seal = fold_tag_list(SEAL, &seal_tags);
+
+        Ok(ArcSet { instance, authentication_results, message_signature, seal })
+    }
+}
+
+/// Validates the ARC chain of a encoded mail (rfc8617 section 5.2).
+///
+/// Only the `ARC-Message-Signature` of the latest instance is
+/// validated, as earlier intermediaries might have changed the
+/// mail, but all `ARC-Seal`s are validated.
+pub fn validate_chain(raw_mail: &[u8], crypto: &dyn ArcCrypto, lookup: &dyn ArcKeyLookup)
+    -> ArcValidation
+{
+    let (header_section, body) = split_header_section(raw_mail);
+    let fields = parse_fields(&String::from_utf8_lossy(header_section));
+
+    let sets = match collect_arc_sets(&fields) {
+        Ok(sets) => sets,
+        Err(reason) => return ArcValidation::fail(0, reason)
+    };
+    let instances = match sets.keys().next_back() {
+        Some(&instances) => instances,
+        None => return ArcValidation { status: ChainValidationStatus::None, instances: 0, reason: None }
+    };
+    if instances > MAX_INSTANCE {
+        return ArcValidation::fail(instances, "too many ARC sets");
+    }
+
+    let mut complete_sets = Vec::new();
+    for instance in 1..=instances {
+        match sets.get(&instance).and_then(RawArcSet::complete) {
+            Some(set) => complete_sets.push(set),
+            None => return ArcValidation::fail(instances, format!("incomplete ARC set {}", instance))
+        }
+    }
+
+    let seal_tags = complete_sets.iter()
+        .map(|set| parse_tags(set.seal))
+        .collect::<Vec<_>>();
+    for (idx, tags) in seal_tags.iter().enumerate() {
+        let expected = if idx == 0 { "none" } else { "pass" };
+        match tag(tags, "cv") {
+            Some(cv) if cv.eq_ignore_ascii_case(expected) => {},
+            Some(cv) if cv.eq_ignore_ascii_case("fail") =>
+                return ArcValidation::fail(instances, format!("ARC set {} has cv=fail", idx + 1)),
+            _ => return ArcValidation::fail(instances, format!("ARC set {} has unexpected cv", idx + 1))
+        }
+    }
+
+    //UNWRAP_SAFE: there is at least one instance
+    let latest = complete_sets.last().unwrap();
+    if let Err(reason) = validate_message_signature(latest.message_signature, &fields, body, crypto, lookup) {
+        return ArcValidation::fail(instances, reason);
+    }
+
+    let mut seal_data = Vec::new();
+    for (idx, set) in complete_sets.iter().enumerate() {
+        seal_data.extend_from_slice(relaxed_header(AUTHENTICATION_RESULTS, set.authentication_results).as_bytes());
+        seal_data.extend_from_slice(relaxed_header(MESSAGE_SIGNATURE, set.message_signature).as_bytes());
+        let mut data = seal_data.clone();
+        data.extend_from_slice(signature_input(SEAL, set.seal).as_bytes());
+        if let Err(reason) = verify_signature(&seal_tags[idx], &data, crypto, lookup) {
+            return ArcValidation::fail(instances, format!("invalid ARC-Seal {}: {}", idx + 1, reason));
+        }
+        seal_data.extend_from_slice(relaxed_header(SEAL, set.seal).as_bytes());
+    }
+
+    ArcValidation { status: ChainValidationStatus::Pass, instances, reason: None }
+}
+
+fn validate_message_signature(
+    value: &str,
+    fields: &[(String, String)],
+    body: &[u8],
+    crypto: &dyn ArcCrypto,
+    lookup: &dyn ArcKeyLookup
+) -> Result<(), String> {
+    let tags = parse_tags(value);
+    match tag(&tags, "c") {
+        Some(canonicalization) if canonicalization.eq_ignore_ascii_case("relaxed/relaxed") => {},
+        _ => return Err("unsupported ARC-Message-Signature canonicalization".to_owned())
+    }
+
+    let body_hash = tag(&tags, "bh")
+        .and_then(|body_hash| base64::normal_decode(body_hash).ok())
+        .ok_or_else(|| "malformed ARC-Message-Signature body hash".to_owned())?;
+    let actual_body_hash = crypto.sha256(&relaxed_body(body)).map_err(|err| err.to_string())?;
+    if body_hash != actual_body_hash {
+        return Err("ARC-Message-Signature body hash mismatch".to_owned());
+    }
+
+    let signed_headers = tag(&tags, "h")
+        .ok_or_else(|| "ARC-Message-Signature without signed headers".to_owned())?
+        .split(':')
+        .map(|name| name.trim().to_owned())
+        .collect::<Vec<_>>();
+    if signed_headers.iter().any(|name| name.to_ascii_lowercase().starts_with("arc-")) {
+        return Err("ARC-Message-Signature signs ARC headers".to_owned());
+    }
+
+    let mut data = Vec::new();
+    for (name, value) in select_headers(fields, &signed_headers) {
+        data.extend_from_slice(relaxed_header(name, value).as_bytes());
+    }
+    data.extend_from_slice(signature_input(MESSAGE_SIGNATURE, value).as_bytes());
+    verify_signature(&tags, &data, crypto, lookup)
+        .map_err(|reason| format!("invalid ARC-Message-Signature: {}", reason))
+}
+
+fn verify_signature(
+    tags: &[(String, String)],
+    data: &[u8],
+    crypto: &dyn ArcCrypto,
+    lookup: &dyn ArcKeyLookup
+) -> Result<(), String> {
+    if !tag(tags, "a").map(|algorithm| algorithm.eq_ignore_ascii_case("rsa-sha256")).unwrap_or(false) {
+        return Err("unsupported signature algorithm".to_owned());
+    }
+    let domain = tag(tags, "d").ok_or_else(|| "missing d= tag".to_owned())?;
+    let selector = tag(tags, "s").ok_or_else(|| "missing s= tag".to_owned())?;
+    let signature = tag(tags, "b")
+        .and_then(|signature| base64::normal_decode(signature).ok())
+        .ok_or_else(|| "malformed b= tag".to_owned())?;
+
+    let record = lookup.lookup_key(selector, domain).map_err(|err| err.to_string())?;
+    let public_key = parse_key_record(&record)?;
+    crypto.verify_rsa_sha256(&public_key, data, &signature).map_err(|err| err.to_string())
+}
+
+/// Returns the public key from a DKIM key record.
+fn parse_key_record(record: &str) -> Result<Vec<u8>, String> {
+    let tags = parse_tags(record);
+    if let Some(key_type) = tag(&tags, "k") {
+        if !key_type.eq_ignore_ascii_case("rsa") {
+            return Err(format!("unsupported key type {}", key_type));
+        }
+    }
+    match tag(&tags, "p") {
+        Some("") => Err("key was revoked".to_owned()),
+        Some(key) => base64::normal_decode(key).map_err(|_| "malformed key record".to_owned()),
+        None => Err("key record without key".to_owned())
+    }
+}
+
+/// The headers of one instance of a ARC set, as found in a mail.
+#[derive(Default)]
+struct RawArcSet<'a> {
+    authentication_results: Vec<&'a str>,
+    message_signatures: Vec<&'a str>,
+    seals: Vec<&'a str>
+}
+
+/// A ARC set with exactly one header of each kind.
+struct CompleteArcSet<'a> {
+    authentication_results: &'a str,
+    message_signature: &'a str,
+    seal: &'a str
+}
+
+impl<'a> RawArcSet<'a> {
+    fn complete(&self) -> Option<CompleteArcSet<'a>> {
+        match (&*self.authentication_results, &*self.message_signatures, &*self.seals) {
+            (&[authentication_results], &[message_signature], &[seal]) =>
+                Some(CompleteArcSet { authentication_results, message_signature, seal }),
+            _ => None
+        }
+    }
+}
+
+impl<'a> CompleteArcSet<'a> {
+    fn canonicalize_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(relaxed_header(AUTHENTICATION_RESULTS, self.authentication_results).as_bytes());
+        out.extend_from_slice(relaxed_header(MESSAGE_SIGNATURE, self.message_signature).as_bytes());
+        out.extend_from_slice(relaxed_header(SEAL, self.seal).as_bytes());
+    }
+}
+
+/// Groups all ARC headers by their instance.
+fn collect_arc_sets<'a>(fields: &'a [(String, String)]) -> Result<BTreeMap<u32, RawArcSet<'a>>, &'static str> {
+    let mut sets = BTreeMap::<u32, RawArcSet>::new();
+    for (name, value) in fields {
+        let is_authentication_results = name.eq_ignore_ascii_case(AUTHENTICATION_RESULTS);
+        let is_message_signature = name.eq_ignore_ascii_case(MESSAGE_SIGNATURE);
+        let is_seal = name.eq_ignore_ascii_case(SEAL);
+        if !(is_authentication_results || is_message_signature || is_seal) {
+            continue;
+        }
+
+        let instance = if is_authentication_results {
+            // the results are no tag list, but they start with `i=<instance>;`
+            parse_tags(value.split(';').next().unwrap_or(""))
+                .into_iter()
+                .next()
+                .and_then(|(name, value)| if name == "i" { value.parse().ok() } else { None })
+        } else {
+            tag(&parse_tags(value), "i").and_then(|instance| instance.parse().ok())
+        };
+        let instance: u32 = instance.ok_or("malformed ARC instance")?;
+        if instance == 0 {
+            return Err("malformed ARC instance");
+        }
+
+        let set = sets.entry(instance).or_default();
+        if is_authentication_results {
+            set.authentication_results.push(value);
+        } else if is_message_signature {
+            set.message_signatures.push(value);
+        } else {
+            set.seals.push(value);
+        }
+    }
+    Ok(sets)
+}
+
+/// Selects the headers to sign/verify in the order given by `names`.
+///
+/// For headers appearing multiple times the last not yet
+/// selected one is used (rfc6376 section 5.4.2).
+fn select_headers<'a, N>(fields: &'a [(String, String)], names: &[N]) -> Vec<&'a (String, String)>
+    where N: AsRef<str>
+{
+    let mut used = vec![false; fields.len()];
+    let mut selected = Vec::new();
+    for name in names {
+        let found = fields.iter()
+            .enumerate()
+            .rev()
+            .find(|&(idx, (field_name, _))| !used[idx] && field_name.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(idx, _)| idx);
+        if let Some(idx) = found {
+            used[idx] = true;
+            selected.push(&fields[idx]);
+        }
+    }
+    selected
+}
+
+/// Parses a tag list (rfc6376 section 3.2), whitespace is removed from all values.
+fn parse_tags(value: &str) -> Vec<(String, String)> {
+    value.split(';')
+        .filter_map(|tag| {
+            let eq_idx = tag.find('=')?;
+            let name = tag[..eq_idx].trim().to_owned();
+            let value = tag[eq_idx+1..].chars().filter(|ch| !ch.is_whitespace()).collect();
+            Some((name, value))
+        })
+        .collect()
+}
+
+fn tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag_name, _)| tag_name == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Canonicalizes a header using the `relaxed` algorithm (rfc6376 section 3.4.2).
+fn relaxed_header(name: &str, value: &str) -> String {
+    let mut out = name.trim().to_ascii_lowercase();
+    out.push(':');
+    let mut started = false;
+    let mut pending_space = false;
+    for ch in value.chars() {
+        match ch {
+            '\r' | '\n' => {},
+            ' ' | '\t' => pending_space = true,
+            ch => {
+                if pending_space && started {
+                    out.push(' ');
+                }
+                pending_space = false;
+                started = true;
+                out.push(ch);
+            }
+        }
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Canonicalizes a complete (generated) header line.
+fn relaxed_header_line(line: &str) -> String {
+    //UNWRAP_SAFE: generated header lines always contain a colon
+    let colon_idx = line.find(':').unwrap();
+    relaxed_header(&line[..colon_idx], &line[colon_idx+1..])
+}
+
+/// Returns the canonicalized signature header as included in the signed data.
+///
+/// That is the header with an empty `b=` tag and without the trailing CRLF.
+fn signature_input(name: &str, value: &str) -> String {
+    let mut stripped = String::with_capacity(value.len());
+    for (idx, tag) in value.split(';').enumerate() {
+        if idx > 0 {
+            stripped.push(';');
+        }
+        match tag.find('=') {
+            Some(eq_idx) if tag[..eq_idx].trim() == "b" => stripped.push_str(&tag[..eq_idx+1]),
+            _ => stripped.push_str(tag)
+        }
+    }
+    let mut canonical = relaxed_header(name, &stripped);
+    let new_len = canonical.len() - 2;
+    canonical.truncate(new_len);
+    canonical
+}
+
+/// Like `signature_input` but for a complete (generated) header line.
+fn signature_input_line(line: &str) -> String {
+    //UNWRAP_SAFE: generated header lines always contain a colon
+    let colon_idx = line.find(':').unwrap();
+    signature_input(&line[..colon_idx], &line[colon_idx+1..])
+}
+
+/// Canonicalizes a body using the `relaxed` algorithm (rfc6376 section 3.4.4).
+fn relaxed_body(body: &[u8]) -> Vec<u8> {
+    let mut lines = body.split(|&bch| bch == b'\n')
+        .map(|line| {
+            let line = if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line };
+            let mut out = Vec::with_capacity(line.len());
+            let mut pending_space = false;
+            for &bch in line {
+                if bch == b' ' || bch == b'\t' {
+                    pending_space = true;
+                } else {
+                    if pending_space {
+                        out.push(b' ');
+                    }
+                    pending_space = false;
+                    out.push(bch);
+                }
+            }
+            out
+        })
+        .collect::<Vec<_>>();
+
+    while lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    let mut out = Vec::with_capacity(body.len());
+    for line in lines {
+        out.extend_from_slice(&line);
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Encodes the `ARC-Authentication-Results` header line.
+fn encode_authentication_results(instance: u32, results: AuthenticationResults)
+    -> Result<String, ArcError>
+{
+    let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    buffer.write_header_line(|handle| {
+        handle.write_str(SoftAsciiStr::from_unchecked(AUTHENTICATION_RESULTS))?;
+        handle.write_str(SoftAsciiStr::from_unchecked(":"))?;
+        handle.write_fws();
+        ArcAuthenticationResults::new(instance, results).encode(handle)
+    })?;
+    let mut line = buffer.to_string()?;
+    let new_len = line.trim_end().len();
+    line.truncate(new_len);
+    Ok(line)
+}
+
+/// Encodes data as base64 without any line breaks.
+fn encode_base64(data: &[u8]) -> String {
+    // chunks of 48 bytes are encoded without line breaks or padding
+    data.chunks(48)
+        .map(|chunk| base64::normal_encode(chunk).to_string())
+        .collect()
+}
+
+/// Creates a header line from a tag list, folding it at places where FWS is allowed.
+///
+/// Values of the `b` and `bh` tags are folded at any position
+/// and values of the `h` tag are folded after a `:`.
+fn fold_tag_list(name: &str, tags: &[(&str, &str)]) -> String {
+    let mut out = format!("{}:", name);
+    let mut line_len = out.len();
+    for (idx, &(tag, value)) in tags.iter().enumerate() {
+        let separator = if idx + 1 < tags.len() { ";" } else { "" };
+        let tag_len = tag.len() + 1 + value.len() + separator.len();
+        if line_len + 1 + tag_len <= MAX_LINE_LEN {
+            out.push(' ');
+            line_len += 1;
+        } else {
+            out.push_str("\r\n ");
+            line_len = 1;
+        }
+        out.push_str(tag);
+        out.push('=');
+        line_len += tag.len() + 1;
+
+        let pieces: Vec<&str> = match tag {
+            "h" => value.split_inclusive(':').collect(),
+            "b" | "bh" => (0..value.len()).map(|idx| &value[idx..idx+1]).collect(),
+            _ => vec![value]
+        };
+        for piece in pieces {
+            if line_len + piece.len() > MAX_LINE_LEN && line_len > 1 {
+                out.push_str("\r\n ");
+                line_len = 1;
+            }
+            out.push_str(piece);
+            line_len += piece.len();
+        }
+        out.push_str(separator);
+        line_len += separator.len();
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use std::str;
+
+    use headers::header_components::MethodResult;
+    use super::*;
+
+    /// A fake crypto implementation, public and private keys are the same.
+    struct FakeCrypto;
+
+    fn fake_hash(data: &[u8]) -> Vec<u8> {
+        let mut state = [0u8; 32];
+        for (idx, &bch) in data.iter().enumerate() {
+            let slot = idx % 32;
+            state[slot] = state[slot].wrapping_mul(31).wrapping_add(bch);
+        }
+        state.to_vec()
+    }
+
+    impl ArcCrypto for FakeCrypto {
+        fn sha256(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+            Ok(fake_hash(data))
+        }
+
+        fn sign_rsa_sha256(&self, key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+            let mut input = key.as_der().to_vec();
+            input.extend_from_slice(data);
+            Ok(fake_hash(&input))
+        }
+
+        fn verify_rsa_sha256(&self, public_key: &[u8], data: &[u8], signature: &[u8])
+            -> Result<(), CryptoError>
+        {
+            let expected = self.sign_rsa_sha256(&PrivateKey::from_der(public_key.to_vec()), data)?;
+            if expected == signature {
+                Ok(())
+            } else {
+                Err(CryptoError::InvalidSignature(format_err!("signature mismatch")))
+            }
+        }
+    }
+
+    const MAIL: &[u8] = b"From: <me@example.test>\r\n\
+        To: <list@lists.example.test>\r\n\
+        Subject: Hy there\r\n\
+        \r\n\
+        Hy,\r\n\
+        how  are you?\r\n";
+
+    fn keys() -> HashMap<String, String> {
+        let mut keys = HashMap::new();
+        keys.insert("arc._domainkey.lists.example.test".to_owned(), "v=DKIM1; k=rsa; p=a2V5".to_owned());
+        keys.insert("arc._domainkey.forwarder.example.test".to_owned(), "v=DKIM1; p=b3RoZXI=".to_owned());
+        keys
+    }
+
+    fn sealer(domain: &str, key: &[u8]) -> ArcSealer {
+        ArcSealer::new(domain, "arc", PrivateKey::from_der(key.to_vec()), Arc::new(FakeCrypto))
+    }
+
+    fn results(authserv_id: &str) -> AuthenticationResults {
+        AuthenticationResults::new(authserv_id)
+            .with_result(MethodResult::new("spf", "pass").with_property("smtp", "mailfrom", "example.test"))
+    }
+
+    #[test]
+    fn relaxed_canonicalization() {
+        // example from rfc6376 section 3.4.5
+        assert_eq!(relaxed_header("A", " X\r\n"), "a:X\r\n");
+        assert_eq!(relaxed_header("B ", " Y\t\r\n\tZ  "), "b:Y Z\r\n");
+        assert_eq!(relaxed_body(b" C \r\nD \t E\r\n\r\n\r\n"), b" C\r\nD E\r\n".to_vec());
+        assert_eq!(relaxed_body(b"\r\n\r\n"), b"".to_vec());
+    }
+
+    #[test]
+    fn signature_input_strips_signature() {
+        assert_eq!(
+            signature_input("ARC-Seal", " i=1; bh=YWJj;\r\n b=YWJj\r\n ZGVm"),
+            "arc-seal:i=1; bh=YWJj; b="
+        );
+    }
+
+    #[test]
+    fn seal_and_validate() {
+        let crypto = FakeCrypto;
+        let keys = keys();
+        assert_eq!(validate_chain(MAIL, &crypto, &keys).status, ChainValidationStatus::None);
+
+        let set = sealer("lists.example.test", b"key")
+            .seal(MAIL, 1, results("lists.example.test"), ChainValidationStatus::None)
+            .unwrap();
+        assert!(set.authentication_results.starts_with("ARC-Authentication-Results: i=1; lists.example.test;"));
+        assert!(set.message_signature.contains("h=from:subject:to;"));
+        for line in set.seal.lines().chain(set.message_signature.lines()) {
+            assert!(line.len() <= 78, "line too long: {:?}", line);
+        }
+
+        let sealed = set.prepend_to(MAIL);
+        let validation = validate_chain(&sealed, &crypto, &keys);
+        assert_eq!(validation, ArcValidation {
+            status: ChainValidationStatus::Pass,
+            instances: 1,
+            reason: None
+        });
+
+        // the next intermediary modifies the mail and adds a second set
+        let mut modified = sealed.clone();
+        modified.extend_from_slice(b"--\r\nlist footer\r\n");
+        let set = sealer("forwarder.example.test", b"other")
+            .seal(&modified, validation.next_instance(), results("forwarder.example.test"), validation.status)
+            .unwrap();
+        assert!(set.seal.contains("cv=pass;"));
+
+        let validation = validate_chain(&set.prepend_to(&modified), &crypto, &keys);
+        assert_eq!(validation.status, ChainValidationStatus::Pass);
+        assert_eq!(validation.instances, 2);
+    }
+
+    #[test]
+    fn modifications_after_sealing_fail_validation() {
+        let crypto = FakeCrypto;
+        let sealed = sealer("lists.example.test", b"key")
+            .seal(MAIL, 1, results("lists.example.test"), ChainValidationStatus::None)
+            .unwrap()
+            .prepend_to(MAIL);
+
+        let text = str::from_utf8(&sealed).unwrap();
+        let validation = validate_chain(text.replace("Hy there", "Buy now").as_bytes(), &crypto, &keys());
+        assert_eq!(validation.status, ChainValidationStatus::Fail);
+
+        let validation = validate_chain(text.replace("are you", "are we").as_bytes(), &crypto, &keys());
+        assert_eq!(validation.status, ChainValidationStatus::Fail);
+        assert_eq!(validation.reason.unwrap(), "ARC-Message-Signature body hash mismatch");
+
+        let validation = validate_chain(&sealed, &crypto, &HashMap::new());
+        assert_eq!(validation.status, ChainValidationStatus::Fail);
+    }
+
+    #[test]
+    fn broken_chains_fail_validation() {
+        let crypto = FakeCrypto;
+        let sealed = sealer("lists.example.test", b"key")
+            .seal(MAIL, 2, results("lists.example.test"), ChainValidationStatus::Fail)
+            .unwrap()
+            .prepend_to(MAIL);
+
+        let validation = validate_chain(&sealed, &crypto, &keys());
+        assert_eq!(validation.status, ChainValidationStatus::Fail);
+        assert_eq!(validation.instances, 2);
+    }
+
+    #[test]
+    fn invalid_seal_arguments() {
+        let sealer = sealer("lists.example.test", b"key");
+        let results = results("lists.example.test");
+        match sealer.seal(MAIL, 0, results.clone(), ChainValidationStatus::None) {
+            Err(ArcError::InvalidInstance(0)) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+        match sealer.seal(MAIL, 2, results.clone(), ChainValidationStatus::Pass) {
+            Err(ArcError::Malformed(_)) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+        match sealer.seal(b"Subject: Hy\r\n\r\nHy", 1, results, ChainValidationStatus::None) {
+            Err(ArcError::Malformed(_)) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+}
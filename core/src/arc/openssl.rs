@@ -0,0 +1,100 @@
+//! A `ArcCrypto` implementation using OpenSSL (feature `arc-openssl`).
+use openssl_crate::{
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::PKey,
+    sha::sha256,
+    sign::{Signer, Verifier}
+};
+
+use crate::{
+    error::CryptoError,
+    crypto::PrivateKey
+};
+use super::ArcCrypto;
+
+/// A `ArcCrypto` implementation using OpenSSL.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpensslArcCrypto;
+
+impl ArcCrypto for OpensslArcCrypto {
+
+    fn sha256(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        Ok(sha256(data).to_vec())
+    }
+
+    fn sign_rsa_sha256(&self, key: &PrivateKey, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let key = PKey::private_key_from_der(key.as_der())
+            .map_err(|err| CryptoError::MalformedKeyMaterial(err.to_string()))?;
+        Signer::new(MessageDigest::sha256(), &key)
+            .and_then(|mut signer| {
+                signer.update(data)?;
+                signer.sign_to_vec()
+            })
+            .map_err(backend_error)
+    }
+
+    fn verify_rsa_sha256(&self, public_key: &[u8], data: &[u8], signature: &[u8])
+        -> Result<(), CryptoError>
+    {
+        let key = PKey::public_key_from_der(public_key)
+            .map_err(|err| CryptoError::MalformedKeyMaterial(err.to_string()))?;
+        let valid = Verifier::new(MessageDigest::sha256(), &key)
+            .and_then(|mut verifier| {
+                verifier.update(data)?;
+                verifier.verify(signature)
+            })
+            .map_err(|err| CryptoError::InvalidSignature(err.into()))?;
+
+        if valid {
+            Ok(())
+        } else {
+            Err(CryptoError::InvalidSignature(format_err!("signature does not match")))
+        }
+    }
+}
+
+fn backend_error(err: ErrorStack) -> CryptoError {
+    CryptoError::Backend(err.into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use openssl_crate::rsa::Rsa;
+
+    use internals::bind::base64;
+    use headers::header_components::{AuthenticationResults, MethodResult};
+
+    use arc::{self, ArcSealer, ChainValidationStatus};
+    use super::*;
+
+    #[test]
+    fn seal_and_validate() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let public_key = base64::normal_encode(key.public_key_to_der().unwrap())
+            .as_str()
+            .replace("\r\n", "");
+        let mut keys = HashMap::new();
+        keys.insert("arc._domainkey.lists.example.test".to_owned(), format!("v=DKIM1; k=rsa; p={}", public_key));
+
+        let private_key = PrivateKey::from_der(key.private_key_to_der().unwrap());
+        let sealer = ArcSealer::new("lists.example.test", "arc", private_key, Arc::new(OpensslArcCrypto));
+
+        let mail = b"From: <me@example.test>\r\nSubject: Hy\r\n\r\nHy there\r\n";
+        let results = AuthenticationResults::new("lists.example.test")
+            .with_result(MethodResult::new("dkim", "none"));
+        let sealed = sealer.seal(mail, 1, results, ChainValidationStatus::None)
+            .unwrap()
+            .prepend_to(mail);
+
+        let validation = arc::validate_chain(&sealed, &OpensslArcCrypto, &keys);
+        assert_eq!(validation.status, ChainValidationStatus::Pass, "{:?}", validation.reason);
+
+        let tampered = String::from_utf8(sealed).unwrap().replace("Subject: Hy", "Subject: Buy");
+        let validation = arc::validate_chain(tampered.as_bytes(), &OpensslArcCrypto, &keys);
+        assert_eq!(validation.status, ChainValidationStatus::Fail);
+    }
+}
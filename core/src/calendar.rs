@@ -0,0 +1,516 @@
+//! This module provides a minimal iCalendar (rfc5545) builder for meeting invitations.
+//!
+//! A invitation is send as `text/calendar` body with a `method`
+//! parameter (iTIP, rfc5546) matching the `METHOD` of the calendar.
+//! Mail clients like Outlook or Gmail only show the buttons to
+//! accept/decline a invitation if the calendar body is one of the
+//! alternative bodies of the mail, not if it is a attachment, use
+//! `MailParts::compose_with_calendar` to create such a mail.
+//!
+//! Only `VEVENT` components with the most common properties are
+//! supported.
+use chrono::{DateTime, Utc};
+
+use headers::header_components::{Email, FileMeta, MediaType};
+
+use crate::{
+    context::Context,
+    resource::{Data, Metadata, Resource}
+};
+
+/// The maximal length of a content line in octets, excluding the line break.
+const MAX_LINE_LEN: usize = 75;
+
+/// The iTIP method of a calendar (the `METHOD` property).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CalendarMethod {
+    /// Invites the attendees to a event or updates a event.
+    Request,
+    /// Cancels a event (or the participation of some attendees).
+    Cancel,
+    /// Replies to a invitation, e.g. to accept it.
+    Reply
+}
+
+impl CalendarMethod {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            CalendarMethod::Request => "REQUEST",
+            CalendarMethod::Cancel => "CANCEL",
+            CalendarMethod::Reply => "REPLY"
+        }
+    }
+}
+
+/// The status of a event (the `STATUS` property).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    Cancelled
+}
+
+impl EventStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EventStatus::Tentative => "TENTATIVE",
+            EventStatus::Confirmed => "CONFIRMED",
+            EventStatus::Cancelled => "CANCELLED"
+        }
+    }
+}
+
+/// The role of a attendee (the `ROLE` parameter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttendeeRole {
+    Chair,
+    Required,
+    Optional,
+    NonParticipant
+}
+
+impl AttendeeRole {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AttendeeRole::Chair => "CHAIR",
+            AttendeeRole::Required => "REQ-PARTICIPANT",
+            AttendeeRole::Optional => "OPT-PARTICIPANT",
+            AttendeeRole::NonParticipant => "NON-PARTICIPANT"
+        }
+    }
+}
+
+/// The participation status of a attendee (the `PARTSTAT` parameter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParticipationStatus {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+    Delegated
+}
+
+impl ParticipationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ParticipationStatus::NeedsAction => "NEEDS-ACTION",
+            ParticipationStatus::Accepted => "ACCEPTED",
+            ParticipationStatus::Declined => "DECLINED",
+            ParticipationStatus::Tentative => "TENTATIVE",
+            ParticipationStatus::Delegated => "DELEGATED"
+        }
+    }
+}
+
+/// The organizer of a event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Organizer {
+    pub email: Email,
+    /// The display name (the `CN` parameter).
+    pub name: Option<String>
+}
+
+impl Organizer {
+
+    pub fn new(email: Email) -> Self {
+        Organizer { email, name: None }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// A attendee of a event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Attendee {
+    pub email: Email,
+    /// The display name (the `CN` parameter).
+    pub name: Option<String>,
+    pub role: AttendeeRole,
+    pub status: ParticipationStatus,
+    /// If a reply is expected from the attendee.
+    pub rsvp: bool
+}
+
+impl Attendee {
+
+    /// Creates a required attendee, from which a reply is expected.
+    pub fn new(email: Email) -> Self {
+        Attendee {
+            email,
+            name: None,
+            role: AttendeeRole::Required,
+            status: ParticipationStatus::NeedsAction,
+            rsvp: true
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_role(mut self, role: AttendeeRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    pub fn with_status(mut self, status: ParticipationStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_rsvp(mut self, rsvp: bool) -> Self {
+        self.rsvp = rsvp;
+        self
+    }
+}
+
+/// A event (a `VEVENT` component).
+///
+/// The `uid` identifies the event, updates and cancellations
+/// of a event have to use the same `uid` and a higher `sequence`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Event {
+    pub uid: String,
+    /// The time the calendar was created (the `DTSTAMP` property).
+    pub timestamp: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub sequence: u32,
+    /// The status, if not set `CANCELLED` is used for calendars with the `CANCEL` method.
+    pub status: Option<EventStatus>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub organizer: Option<Organizer>,
+    pub attendees: Vec<Attendee>
+}
+
+impl Event {
+
+    /// Creates a new event, using the current time as timestamp.
+    pub fn new(uid: impl Into<String>, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Event {
+            uid: uid.into(),
+            timestamp: Utc::now(),
+            start, end,
+            sequence: 0,
+            status: None,
+            summary: None,
+            description: None,
+            location: None,
+            organizer: None,
+            attendees: Vec::new()
+        }
+    }
+
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn with_status(mut self, status: EventStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    pub fn with_organizer(mut self, organizer: Organizer) -> Self {
+        self.organizer = Some(organizer);
+        self
+    }
+
+    pub fn with_attendee(mut self, attendee: Attendee) -> Self {
+        self.attendees.push(attendee);
+        self
+    }
+
+    fn write_lines(&self, method: CalendarMethod, out: &mut String) {
+        push_line(out, "BEGIN:VEVENT");
+        push_line(out, &format!("UID:{}", escape_text(&self.uid)));
+        push_line(out, &format!("DTSTAMP:{}", format_date_time(&self.timestamp)));
+        push_line(out, &format!("DTSTART:{}", format_date_time(&self.start)));
+        push_line(out, &format!("DTEND:{}", format_date_time(&self.end)));
+        push_line(out, &format!("SEQUENCE:{}", self.sequence));
+
+        let status = self.status.or_else(|| {
+            if method == CalendarMethod::Cancel { Some(EventStatus::Cancelled) } else { None }
+        });
+        if let Some(status) = status {
+            push_line(out, &format!("STATUS:{}", status.as_str()));
+        }
+
+        let texts = [
+            ("SUMMARY", &self.summary),
+            ("DESCRIPTION", &self.description),
+            ("LOCATION", &self.location)
+        ];
+        for &(name, value) in texts.iter() {
+            if let Some(ref value) = *value {
+                push_line(out, &format!("{}:{}", name, escape_text(value)));
+            }
+        }
+
+        if let Some(ref organizer) = self.organizer {
+            let mut line = "ORGANIZER".to_owned();
+            push_name_param(&mut line, &organizer.name);
+            push_mailto(&mut line, &organizer.email);
+            push_line(out, &line);
+        }
+
+        for attendee in &self.attendees {
+            let mut line = "ATTENDEE".to_owned();
+            push_name_param(&mut line, &attendee.name);
+            line.push_str(";ROLE=");
+            line.push_str(attendee.role.as_str());
+            line.push_str(";PARTSTAT=");
+            line.push_str(attendee.status.as_str());
+            if attendee.rsvp {
+                line.push_str(";RSVP=TRUE");
+            }
+            push_mailto(&mut line, &attendee.email);
+            push_line(out, &line);
+        }
+
+        push_line(out, "END:VEVENT");
+    }
+}
+
+/// A calendar (a `VCALENDAR` object) with a iTIP method.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Calendar {
+    pub method: CalendarMethod,
+    /// The product identifier (the `PRODID` property).
+    pub product_id: String,
+    pub events: Vec<Event>
+}
+
+impl Calendar {
+
+    pub fn new(method: CalendarMethod) -> Self {
+        Calendar {
+            method,
+            product_id: "-//mail-core//calendar//EN".to_owned(),
+            events: Vec::new()
+        }
+    }
+
+    pub fn with_product_id(mut self, product_id: impl Into<String>) -> Self {
+        self.product_id = product_id.into();
+        self
+    }
+
+    pub fn with_event(mut self, event: Event) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// Serializes the calendar as iCalendar text (with CRLF line endings).
+    pub fn to_ics(&self) -> String {
+        let mut out = String::new();
+        push_line(&mut out, "BEGIN:VCALENDAR");
+        push_line(&mut out, &format!("PRODID:{}", escape_text(&self.product_id)));
+        push_line(&mut out, "VERSION:2.0");
+        push_line(&mut out, "CALSCALE:GREGORIAN");
+        push_line(&mut out, &format!("METHOD:{}", self.method.as_str()));
+        for event in &self.events {
+            event.write_lines(self.method, &mut out);
+        }
+        push_line(&mut out, "END:VCALENDAR");
+        out
+    }
+
+    /// Creates a `text/calendar; method=...; charset=utf-8` resource, used as alternative body.
+    pub fn create_resource(&self, ctx: &impl Context) -> Resource {
+        let media_type = format!("text/calendar; method={}; charset=utf-8", self.method.as_str());
+        //UNWRAP_SAFE: it's a valid media type
+        let media_type = MediaType::parse(media_type.as_str()).unwrap();
+        create_resource(self.to_ics(), media_type, Default::default(), ctx)
+    }
+
+    /// Creates a `application/ics` resource with given file name, used as attachment.
+    ///
+    /// It's a copy of the calendar for clients not supporting calendar
+    /// alternative bodies. The `application/ics` media type is used so
+    /// that clients supporting them don't show the invitation twice.
+    pub fn create_ics_attachment(&self, file_name: impl Into<String>, ctx: &impl Context) -> Resource {
+        //UNWRAP_SAFE: it's a valid media type
+        let media_type = MediaType::parse("application/ics").unwrap();
+        let file_meta = FileMeta {
+            file_name: Some(file_name.into()),
+            ..Default::default()
+        };
+        create_resource(self.to_ics(), media_type, file_meta, ctx)
+    }
+}
+
+fn create_resource(ics: String, media_type: MediaType, file_meta: FileMeta, ctx: &impl Context)
+    -> Resource
+{
+    let meta = Metadata {
+        file_meta,
+        media_type,
+        content_id: ctx.generate_content_id()
+    };
+    Resource::Data(Data::new(ics.into_bytes(), meta))
+}
+
+fn format_date_time(date_time: &DateTime<Utc>) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a `TEXT` value (rfc5545 section 3.3.11).
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\r' | '\n' => out.push_str("\\n"),
+            ch => out.push(ch)
+        }
+    }
+    out
+}
+
+/// Pushes a `CN` parameter if there is a name.
+///
+/// The value is always quoted, `"` and control characters
+/// can not be represented and are removed.
+fn push_name_param(line: &mut String, name: &Option<String>) {
+    if let Some(ref name) = *name {
+        line.push_str(";CN=\"");
+        line.extend(name.chars().filter(|&ch| ch != '"' && !ch.is_control()));
+        line.push('"');
+    }
+}
+
+fn push_mailto(line: &mut String, email: &Email) {
+    line.push_str(":mailto:");
+    line.push_str(email.local_part.as_str());
+    line.push('@');
+    line.push_str(email.domain.as_str());
+}
+
+/// Pushes a content line, folding it if it's longer than 75 octets.
+fn push_line(out: &mut String, line: &str) {
+    let mut line_len = 0;
+    for ch in line.chars() {
+        if line_len + ch.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            line_len = 1;
+        }
+        out.push(ch);
+        line_len += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use headers::HeaderTryFrom;
+    use super::*;
+
+    fn email(email: &str) -> Email {
+        Email::try_from(email).unwrap()
+    }
+
+    fn event() -> Event {
+        Event::new(
+            "1234@example.test",
+            Utc.with_ymd_and_hms(2026, 11, 2, 9, 30, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 11, 2, 10, 0, 0).unwrap()
+        )
+            .with_timestamp(Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap())
+            .with_summary("Planning, Q4")
+            .with_location("Room 1")
+            .with_organizer(Organizer::new(email("me@example.test")).with_name("Me"))
+            .with_attendee(Attendee::new(email("you@example.test")).with_name("You; Other"))
+    }
+
+    #[test]
+    fn request_to_ics() {
+        let ics = Calendar::new(CalendarMethod::Request)
+            .with_event(event())
+            .to_ics();
+
+        assert_eq!(ics, "\
+            BEGIN:VCALENDAR\r\n\
+            PRODID:-//mail-core//calendar//EN\r\n\
+            VERSION:2.0\r\n\
+            CALSCALE:GREGORIAN\r\n\
+            METHOD:REQUEST\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:1234@example.test\r\n\
+            DTSTAMP:20261018T120000Z\r\n\
+            DTSTART:20261102T093000Z\r\n\
+            DTEND:20261102T100000Z\r\n\
+            SEQUENCE:0\r\n\
+            SUMMARY:Planning\\, Q4\r\n\
+            LOCATION:Room 1\r\n\
+            ORGANIZER;CN=\"Me\":mailto:me@example.test\r\n\
+            ATTENDEE;CN=\"You; Other\";ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TR\r\n \
+            UE:mailto:you@example.test\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n\
+        ");
+    }
+
+    #[test]
+    fn cancel_and_reply() {
+        let ics = Calendar::new(CalendarMethod::Cancel)
+            .with_event(event().with_sequence(1))
+            .to_ics();
+        assert!(ics.contains("\r\nMETHOD:CANCEL\r\n"));
+        assert!(ics.contains("\r\nSEQUENCE:1\r\nSTATUS:CANCELLED\r\n"));
+
+        let attendee = Attendee::new(email("you@example.test"))
+            .with_status(ParticipationStatus::Accepted)
+            .with_rsvp(false);
+        let mut reply = event();
+        reply.attendees = vec![attendee];
+        let ics = Calendar::new(CalendarMethod::Reply).with_event(reply).to_ics();
+        assert!(ics.contains("\r\nMETHOD:REPLY\r\n"));
+        assert!(!ics.contains("STATUS:"));
+        assert!(ics.contains("\r\nATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=ACCEPTED:mailto:you@example.test\r\n"));
+    }
+
+    #[test]
+    fn long_lines_are_folded_at_char_boundaries() {
+        let description = "Über die Planung\nund mehr ".repeat(10);
+        let ics = Calendar::new(CalendarMethod::Request)
+            .with_event(event().with_description(description))
+            .to_ics();
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_LEN));
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains("\r\nDESCRIPTION:Über die Planung\\nund mehr Über"));
+    }
+}
@@ -17,7 +17,9 @@
 // in the later part of the file for better readability.       ||
 //-------------------------------------------------------------//
 
-use media_type::{MULTIPART, ALTERNATIVE, RELATED, MIXED};
+use std::sync::Arc;
+
+use media_type::{MULTIPART, ALTERNATIVE, RELATED, MIXED, TEXT};
 use vec1::Vec1;
 
 use headers::{
@@ -26,13 +28,22 @@ use headers::{
     header_components::{
         Disposition,
         DispositionKind,
-        MediaType
+        MediaType,
+        LanguageList,
+        ContentLocation
     }
 };
 
 use crate::{
+    calendar::Calendar,
+    context::Context,
+    error::MailError,
+    html_text::html_to_plain_text,
     mail::Mail,
-    resource::Resource
+    pgp::{self, OpenPgpBackend, PgpProtection},
+    smime::{self, SmimeBackend, SmimeProtection},
+    resource::Resource,
+    utils::SendBoxFuture
 };
 
 /// Parts used to create a mail body (in a multipart mail).
@@ -68,8 +79,61 @@ pub struct BodyPart {
     /// Attachments of a `BodyPart` instance will be combined with
     /// the attachments of other instances and the ones in the
     /// `MailParts` instance.
-    pub attachments: Vec<Resource>
+    pub attachments: Vec<Resource>,
+
+    /// The language(s) of the body, used for the `Content-Language` header.
+    ///
+    /// This is normally used if there are alternative bodies in
+    /// different languages.
+    pub language: Option<LanguageList>,
+
+    /// A URI identifying the body, used for the `Content-Location` header.
+    ///
+    /// This is normally used with html bodies containing
+    /// relative URIs, so that they can be resolved.
+    pub location: Option<ContentLocation>
+
+}
+
+impl BodyPart {
+
+    /// Creates a new `BodyPart` for given resource.
+    ///
+    /// It has no embeddings, attachments, language or location,
+    /// which can be added with the `with_*` methods.
+    pub fn new(resource: Resource) -> Self {
+        BodyPart {
+            resource,
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new(),
+            language: None,
+            location: None
+        }
+    }
+
+    /// Sets the embeddings which should be displayed inline.
+    pub fn with_inline_embeddings(mut self, inline_embeddings: Vec<Resource>) -> Self {
+        self.inline_embeddings = inline_embeddings;
+        self
+    }
+
+    /// Sets the embeddings which should be treated as attachments.
+    pub fn with_attachments(mut self, attachments: Vec<Resource>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// Sets the language(s) of the body.
+    pub fn with_language(mut self, language: LanguageList) -> Self {
+        self.language = Some(language);
+        self
+    }
 
+    /// Sets the URI identifying the body.
+    pub fn with_location(mut self, location: ContentLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
 }
 
 /// Parts which can be used to compose a multipart mail.
@@ -198,6 +262,112 @@ impl MailParts {
 
         mail
     }
+
+    /// Adds a `text/plain` alternative body derived from the `text/html` alternative body.
+    ///
+    /// If there is no `text/plain` alternative body this converts the
+    /// (last) `text/html` alternative body to plain text, keeping links
+    /// as footnotes, and inserts it as first (i.e. least preferred)
+    /// alternative body. The language of the html body is used for the
+    /// text body, too.
+    ///
+    /// The html is decoded using it's `charset` parameter (see `Data::decode_text`).
+    /// Only html bodies which are already loaded (`Resource::Data`) and use a
+    /// supported charset can be converted, in all other cases `self` is
+    /// returned unchanged.
+    ///
+    /// See the `html_text` module for details about the conversion.
+    pub fn with_plain_text_alternative(mut self, ctx: &impl Context) -> Self {
+        let is_text_subtype = |body: &BodyPart, subtype: &str| match body.resource {
+            Resource::Data(ref data) => {
+                let media_type = data.media_type();
+                media_type.type_() == TEXT && media_type.subtype() == subtype
+            },
+            _ => false
+        };
+
+        if self.alternative_bodies.iter().any(|body| is_text_subtype(body, "plain")) {
+            return self;
+        }
+
+        let text_body = self.alternative_bodies.iter()
+            .rev()
+            .find(|body| is_text_subtype(body, "html"))
+            .and_then(|body| match body.resource {
+                Resource::Data(ref data) => {
+                    let html = data.decode_text().ok()?;
+                    let mut text_body = BodyPart::new(
+                        Resource::plain_text(html_to_plain_text(&html), ctx));
+                    text_body.language = body.language.clone();
+                    Some(text_body)
+                },
+                _ => None
+            });
+
+        if let Some(text_body) = text_body {
+            self.alternative_bodies.insert(0, text_body);
+        }
+        self
+    }
+
+    /// Composes the mail with a calendar (e.g. a meeting invitation) as alternative body.
+    ///
+    /// The calendar is added as last (i.e. preferred) alternative body with
+    /// a `text/calendar; method=...; charset=utf-8` media type. It must be a
+    /// alternative body and not a attachment, as mail clients like Outlook or
+    /// Gmail only show the buttons to reply to a invitation in that case.
+    ///
+    /// If `ics_attachment_name` is given a copy of the calendar is also added
+    /// as (`application/ics`) attachment with that file name.
+    ///
+    /// See the `calendar` module for more details.
+    pub fn compose_with_calendar(
+        mut self,
+        calendar: &Calendar,
+        ics_attachment_name: Option<&str>,
+        ctx: &impl Context
+    ) -> Mail {
+        if let Some(file_name) = ics_attachment_name {
+            self.attachments.push(calendar.create_ics_attachment(file_name, ctx));
+        }
+        self.alternative_bodies.push(BodyPart::new(calendar.create_resource(ctx)));
+        self.compose()
+    }
+
+    /// Composes the mail like `compose` and then applies the given PGP/MIME protection.
+    ///
+    /// This signs and/or encrypts the whole composed mail body, including
+    /// all attachments. Top level headers like `From` or `Subject` should
+    /// be added to the resulting mail (they are not protected).
+    ///
+    /// See the `pgp` module for more details.
+    pub fn compose_with_pgp<C>(
+        self,
+        protection: PgpProtection,
+        backend: Arc<dyn OpenPgpBackend>,
+        ctx: C
+    ) -> SendBoxFuture<Mail, MailError>
+        where C: Context
+    {
+        pgp::protect(self.compose(), protection, backend, ctx)
+    }
+
+    /// Composes the mail and protects it using S/MIME.
+    ///
+    /// Like `compose_with_pgp` top level headers should be added
+    /// to the resulting mail.
+    ///
+    /// See the `smime` module for more details.
+    pub fn compose_with_smime<C>(
+        self,
+        protection: SmimeProtection,
+        backend: Arc<dyn SmimeBackend>,
+        ctx: C
+    ) -> SendBoxFuture<Mail, MailError>
+        where C: Context
+    {
+        smime::protect(self.compose(), protection, backend, ctx)
+    }
 }
 
 impl BodyPart {
@@ -215,6 +385,10 @@ impl BodyPart {
     /// have a `Inline` disposition that body will be
     /// wrapped into a `multipart/related` body containing
     /// them.
+    ///
+    /// If a language or location is given the `Content-Language`
+    /// and `Content-Location` headers are set on the non-multipart
+    /// body.
     pub fn create_mail(
         self,
         attachments_out: &mut Vec<Mail>,
@@ -222,10 +396,18 @@ impl BodyPart {
         let BodyPart {
             resource,
             inline_embeddings,
-            attachments
+            attachments,
+            language,
+            location
         } = self;
 
-        let body = resource.create_mail();
+        let mut body = resource.create_mail();
+        if let Some(language) = language {
+            body.insert_header(headers::ContentLanguage::body(language));
+        }
+        if let Some(location) = location {
+            body.insert_header(headers::ContentLocation::body(location));
+        }
 
         for attachment in attachments.into_iter() {
             let mail = attachment.create_mail_with_disposition(DispositionKind::Attachment);
@@ -278,7 +460,8 @@ impl Mail {
         -> Mail
     {
         let mut bodies = other_bodies;
-        bodies.push(self);
+        //TODO[opt] accept iter and prepend instead of insert in vec
+        bodies.insert(0, self);
         new_multipart(&MIXED, bodies)
     }
 
@@ -295,8 +478,7 @@ impl Mail {
         -> Mail
     {
         let mut bodies = alternates;
-        //TODO[opt] accept iter and prepend instead of insert in vec
-        bodies.insert(0, self);
+        bodies.push(self);
         new_multipart(&ALTERNATIVE, bodies)
     }
 
@@ -324,4 +506,182 @@ fn new_multipart(sub_type: &'static str, bodies: Vec<Mail>)
     let content_type = MediaType::new(MULTIPART, sub_type)
         .unwrap();
     Mail::new_multipart_mail(content_type, bodies)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use headers::{
+        HeaderTryFrom,
+        headers::{
+            ContentDisposition, ContentType,
+            ContentLanguage, ContentLocation as ContentLocationHeader
+        },
+        header_components::{LanguageList, ContentLocation}
+    };
+
+    use calendar::CalendarMethod;
+    use context::Context;
+    use default_impl::test_context;
+    use mail::MailBody;
+    use resource::{Data, Metadata};
+    use super::*;
+
+    #[test]
+    fn body_part_sets_language_and_location() {
+        let ctx = test_context();
+        let body = BodyPart::new(Resource::plain_text("Hallo", &ctx))
+            .with_language(LanguageList::try_from("de-CH").unwrap())
+            .with_location(ContentLocation::try_from("https://example.test/mail/").unwrap());
+
+        let mut attachments = Vec::new();
+        let mail = body.create_mail(&mut attachments);
+
+        let language = mail.headers().get_single(ContentLanguage).unwrap().unwrap();
+        assert_eq!(language.first().as_str(), "de-CH");
+        let location = mail.headers().get_single(ContentLocationHeader).unwrap().unwrap();
+        assert_eq!(location.as_str(), "https://example.test/mail/");
+    }
+
+    #[test]
+    fn compose_puts_preferred_alternative_last_and_attachments_after_body() {
+        let ctx = test_context();
+        let body = |text: &str| BodyPart::new(Resource::plain_text(text, &ctx));
+        let parts = MailParts {
+            alternative_bodies: Vec1::try_from_vec(vec![body("fallback"), body("preferred")]).unwrap(),
+            inline_embeddings: Vec::new(),
+            attachments: vec![Resource::plain_text("attachment", &ctx)]
+        };
+
+        let mail = parts.compose();
+
+        let bodies = assert_multipart(&mail, "mixed");
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[1].headers().contains(ContentDisposition));
+        assert_eq!(text_of(&bodies[1]), "attachment");
+
+        let alternatives = assert_multipart(&bodies[0], "alternative");
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(text_of(&alternatives[0]), "fallback");
+        assert_eq!(text_of(&alternatives[1]), "preferred");
+    }
+
+    fn text_of(mail: &Mail) -> &str {
+        match *mail.body() {
+            MailBody::SingleBody { body: Resource::Data(ref data) } => {
+                ::std::str::from_utf8(data.buffer()).unwrap()
+            },
+            _ => panic!("expected a data body")
+        }
+    }
+
+    #[test]
+    fn calendar_is_a_alternative_body() {
+        let ctx = test_context();
+        let html = BodyPart::new(Resource::plain_text("Hy", &ctx))
+            .with_inline_embeddings(vec![Resource::plain_text("logo", &ctx)]);
+        let parts = MailParts {
+            alternative_bodies: Vec1::new(html),
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new()
+        };
+        let calendar = Calendar::new(CalendarMethod::Request);
+
+        let mail = parts.compose_with_calendar(&calendar, Some("invite.ics"), &ctx);
+
+        let bodies = assert_multipart(&mail, "mixed");
+        assert_eq!(bodies.len(), 2);
+        match *bodies[1].body() {
+            MailBody::SingleBody { body: Resource::Data(ref data) } => {
+                assert_eq!(data.media_type().full_type(), "application/ics");
+                assert_eq!(data.file_meta().file_name.as_ref().unwrap(), "invite.ics");
+            },
+            _ => panic!("expected ics attachment")
+        }
+
+        let alternatives = assert_multipart(&bodies[0], "alternative");
+        assert_eq!(alternatives.len(), 2);
+        assert_multipart(&alternatives[0], "related");
+        match *alternatives[1].body() {
+            MailBody::SingleBody { body: Resource::Data(ref data) } => {
+                let media_type = data.media_type();
+                assert_eq!(media_type.full_type(), "text/calendar");
+                assert_eq!(media_type.get_param("method").unwrap().to_content(), "REQUEST");
+                assert_eq!(media_type.get_param("charset").unwrap().to_content(), "utf-8");
+            },
+            _ => panic!("expected calendar body")
+        }
+        assert!(!alternatives[1].headers().contains(ContentDisposition));
+    }
+
+    #[test]
+    fn plain_text_alternative_is_derived_from_html() {
+        let ctx = test_context();
+        let html = Data::new(
+            &b"<p>Hy <a href=\"https://example.test\">there</a></p>"[..],
+            Metadata {
+                file_meta: Default::default(),
+                media_type: MediaType::parse("text/html; charset=utf-8").unwrap(),
+                content_id: ctx.generate_content_id()
+            }
+        );
+        let parts = MailParts {
+            alternative_bodies: Vec1::new(
+                BodyPart::new(Resource::Data(html))
+                    .with_language(LanguageList::try_from("de").unwrap())
+            ),
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new()
+        };
+
+        let parts = parts.with_plain_text_alternative(&ctx);
+        assert_eq!(parts.alternative_bodies.len(), 2);
+        let text_body = &parts.alternative_bodies[0];
+        assert_eq!(text_body.language, Some(LanguageList::try_from("de").unwrap()));
+        match text_body.resource {
+            Resource::Data(ref data) => {
+                assert_eq!(data.media_type().full_type(), "text/plain");
+                assert_eq!(&**data.buffer(), &b"Hy there[1]\r\n\r\n[1] https://example.test\r\n"[..]);
+            },
+            _ => panic!("expected text body")
+        }
+
+        // a existing text body is not replaced
+        let parts = parts.with_plain_text_alternative(&ctx);
+        assert_eq!(parts.alternative_bodies.len(), 2);
+    }
+
+    #[test]
+    fn plain_text_alternative_uses_html_charset() {
+        let ctx = test_context();
+        let html = Data::new(
+            &b"<p>Gr\xfc\xdfe</p>"[..],
+            Metadata {
+                file_meta: Default::default(),
+                media_type: MediaType::parse("text/html; charset=iso-8859-1").unwrap(),
+                content_id: ctx.generate_content_id()
+            }
+        );
+        let parts = MailParts {
+            alternative_bodies: Vec1::new(BodyPart::new(Resource::Data(html))),
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new()
+        };
+
+        let parts = parts.with_plain_text_alternative(&ctx);
+        match parts.alternative_bodies[0].resource {
+            Resource::Data(ref data) => {
+                assert_eq!(&**data.buffer(), "Grüße\r\n".as_bytes());
+            },
+            _ => panic!("expected text body")
+        }
+    }
+
+    fn assert_multipart<'a>(mail: &'a Mail, subtype: &str) -> &'a [Mail] {
+        let content_type = mail.headers().get_single(ContentType).unwrap().unwrap();
+        assert_eq!(content_type.subtype().as_ref(), subtype);
+        match *mail.body() {
+            MailBody::MultipleBodies { ref bodies, .. } => bodies,
+            _ => panic!("expected multipart body")
+        }
+    }
+}
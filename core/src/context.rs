@@ -6,7 +6,7 @@ use futures::{ future::{self, Either}, Future, IntoFuture };
 use utils::SendBoxFuture;
 
 use headers::header_components::{
-    MessageId, ContentId
+    MessageId, ContentId, AutoSubmitted
 };
 
 use crate::{
@@ -176,6 +176,19 @@ pub trait Context: Debug + Clone + Send + Sync + 'static {
     {
         self.offload( future::lazy( func ) )
     }
+
+    /// marks all mails encoded with this context as automatically submitted
+    ///
+    /// If this returns `Some` and a mail has no `Auto-Submitted` header one is
+    /// inserted with the returned value when turning the mail into a encodable
+    /// mail. Except for `AutoSubmitted::No` a `X-Auto-Response-Suppress: All`
+    /// header is inserted, too (if there is none), as some mail servers do
+    /// not respect the `Auto-Submitted` header.
+    ///
+    /// The default impl. returns `None`.
+    fn auto_submitted(&self) -> Option<AutoSubmitted> {
+        None
+    }
 }
 
 
@@ -286,6 +299,7 @@ pub struct CompositeContext<
     M: MailIdGenComponent
 >{
     inner: Arc<(R, O, M)>,
+    auto_submitted: Option<AutoSubmitted>
 }
 
 impl<R, O, M> Clone for CompositeContext<R, O, M>
@@ -296,6 +310,7 @@ impl<R, O, M> Clone for CompositeContext<R, O, M>
     fn clone(&self) -> Self {
         CompositeContext {
             inner: self.inner.clone(),
+            auto_submitted: self.auto_submitted
         }
     }
 }
@@ -309,9 +324,18 @@ impl<R, O, M> CompositeContext<R, O, M>
     pub fn new(resource_loader: R, offloader: O, message_id_gen: M) -> Self {
         CompositeContext {
             inner: Arc::new((resource_loader, offloader, message_id_gen)),
+            auto_submitted: None
         }
     }
 
+    /// Marks all mails encoded with this context as automatically submitted.
+    ///
+    /// See `Context::auto_submitted` for details.
+    pub fn with_auto_submitted(mut self, auto_submitted: AutoSubmitted) -> Self {
+        self.auto_submitted = Some(auto_submitted);
+        self
+    }
+
     /// Returns a reference to the resource loader component.
     pub fn resource_loader(&self) -> &R {
         &self.inner.0
@@ -362,6 +386,10 @@ impl<R, O, M> Context for CompositeContext<R, O, M>
         self.id_gen().generate_message_id()
     }
 
+    fn auto_submitted(&self) -> Option<AutoSubmitted> {
+        self.auto_submitted
+    }
+
 }
 
 /// Allows using a part of an context as an component.
@@ -0,0 +1,464 @@
+//! Provides a caching wrapper for resource loaders.
+use std::{
+    collections::{HashMap, BTreeMap},
+    sync::{Arc, Mutex},
+    time::SystemTime
+};
+
+use futures::{future::{self, Either}, Future};
+
+use crate::{
+    IRI,
+    utils::SendBoxFuture,
+    error::ResourceLoadingError,
+    resource::{Source, Resource, EncData, Metadata, UseMediaType},
+    context::{Context, ResourceLoaderComponent, MaybeEncData}
+};
+
+/// A version of a resource used to detect if a cached resource is outdated.
+///
+/// A version without modification time and size (i.e. the `Default`)
+/// represents a resource which never changes, e.g. a `data:` IRI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ResourceVersion {
+    /// The last modification time of the resource.
+    pub modified: Option<SystemTime>,
+
+    /// The size of the resource in bytes.
+    pub size: Option<u64>
+}
+
+/// Resource loaders which can cheaply determine the version of a resource.
+///
+/// This is needed to use a resource loader with the `CachingResourceLoader`.
+pub trait ResourceVersionLookup: ResourceLoaderComponent {
+
+    /// Returns the current version of the resource `source` refers to.
+    ///
+    /// If the version can not be determined `None` is returned, in which
+    /// case the resource will neither be taken from nor put into a cache.
+    ///
+    /// This method is allowed to block (e.g. to `stat` a file), it is
+    /// always called "offloaded".
+    fn resource_version(&self, source: &Source) -> Option<ResourceVersion>;
+}
+
+/// A `ResourceLoaderComponent` wrapper caching transfer encoded resources.
+///
+/// Resources are cached based on the IRI, file name and media type of
+/// their `Source` as well as the `ResourceVersion` the inner loader
+/// reports for them. If the version changed the cached entry is discarded
+/// and the resource is loaded again.
+///
+/// The cache is a LRU cache with a budget of transfer encoded bytes, if
+/// the budget is exceeded the least recently used entries are removed.
+/// Resources larger then the budget are not cached at all.
+///
+/// Each time a resource is returned from the cache it's given a new
+/// content id from the context, as such the same cached resource can
+/// be used multiple times in the same mail.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// use mail_core::{
+///     utils::Disabled,
+///     default_impl::{FsResourceLoader, CachingResourceLoader, Mux, InMemoryResourceLoader}
+/// };
+///
+/// # fn main() {
+/// let loader: FsResourceLoader = FsResourceLoader::new("./templates");
+/// // cache up to 16 MiB of transfer encoded resources
+/// let loader = CachingResourceLoader::new(loader, 16 * 1024 * 1024);
+/// # let _ = loader;
+///
+/// // a `Mux` can be cached, too (only resources of versioned loaders are cached)
+/// let templates: FsResourceLoader<Disabled> = FsResourceLoader::new("./templates");
+/// let mux = Mux::new()
+///     .with_versioned_loader("path", templates).unwrap()
+///     .with_loader("mem", InMemoryResourceLoader::new()).unwrap();
+/// let loader = CachingResourceLoader::new(mux, 16 * 1024 * 1024);
+/// # let _ = loader;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CachingResourceLoader<R>
+    where R: ResourceVersionLookup
+{
+    inner: Arc<R>,
+    cache: Arc<Mutex<LruCache>>
+}
+
+impl<R> CachingResourceLoader<R>
+    where R: ResourceVersionLookup
+{
+    /// Wraps `inner` caching up to `max_bytes` bytes of transfer encoded data.
+    pub fn new(inner: R, max_bytes: usize) -> Self {
+        CachingResourceLoader {
+            inner: Arc::new(inner),
+            cache: Arc::new(Mutex::new(LruCache::new(max_bytes)))
+        }
+    }
+
+    /// Returns a reference to the wrapped resource loader.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// The maximal number of transfer encoded bytes which will be cached.
+    pub fn max_bytes(&self) -> usize {
+        self.cache.lock().unwrap().max_bytes
+    }
+
+    /// The number of transfer encoded bytes which are currently cached.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.lock().unwrap().used_bytes
+    }
+
+    /// The number of currently cached resources.
+    pub fn cached_resources(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear()
+    }
+
+    /// Looks up the version and checks the cache, calling `load` on a cache miss.
+    fn cached<C, F, T>(&self, source: &Source, ctx: &C, load: F)
+        -> SendBoxFuture<T, ResourceLoadingError>
+        where C: Context,
+              T: CacheableResult + Send + 'static,
+              F: FnOnce(&R, &C) -> SendBoxFuture<T, ResourceLoadingError> + Send + 'static
+    {
+        let key = CacheKey::new(source);
+        let source = source.clone();
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let ctx2 = ctx.clone();
+
+        let fut = ctx
+            .offload_fn({
+                let inner = inner.clone();
+                move || Ok(inner.resource_version(&source))
+            })
+            .and_then(move |version| {
+                let version = match version {
+                    Some(version) => version,
+                    None => return Either::B(load(&inner, &ctx2))
+                };
+
+                let cached = cache.lock().unwrap().get(&key, version);
+                if let Some(enc_data) = cached {
+                    let enc_data = with_new_content_id(&enc_data, &ctx2);
+                    return Either::A(future::ok(T::from_enc_data(enc_data)));
+                }
+
+                let fut = load(&inner, &ctx2)
+                    .map(move |result| {
+                        if let Some(enc_data) = result.as_enc_data() {
+                            cache.lock().unwrap().insert(key, version, enc_data.clone());
+                        }
+                        result
+                    });
+
+                let fut: SendBoxFuture<T, ResourceLoadingError> = Box::new(fut);
+                Either::B(fut)
+            });
+
+        Box::new(fut)
+    }
+}
+
+impl<R> ResourceLoaderComponent for CachingResourceLoader<R>
+    where R: ResourceVersionLookup
+{
+    fn load_resource(&self, source: &Source, ctx: &impl Context)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        let source2 = source.clone();
+        self.cached(source, ctx, move |inner, ctx| inner.load_resource(&source2, ctx))
+    }
+
+    fn load_transfer_encoded_resource(&self, resource: &Resource, ctx: &impl Context)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
+    {
+        match resource {
+            Resource::Source(source) => {
+                let resource = resource.clone();
+                self.cached(source, ctx, move |inner, ctx| {
+                    inner.load_transfer_encoded_resource(&resource, ctx)
+                })
+            },
+            _ => self.inner.load_transfer_encoded_resource(resource, ctx)
+        }
+    }
+}
+
+/// Results of loading functions which can be cached.
+trait CacheableResult {
+    fn from_enc_data(enc_data: EncData) -> Self;
+    fn as_enc_data(&self) -> Option<&EncData>;
+}
+
+impl CacheableResult for EncData {
+    fn from_enc_data(enc_data: EncData) -> Self {
+        enc_data
+    }
+
+    fn as_enc_data(&self) -> Option<&EncData> {
+        Some(self)
+    }
+}
+
+impl CacheableResult for MaybeEncData {
+    fn from_enc_data(enc_data: EncData) -> Self {
+        MaybeEncData::EncData(enc_data)
+    }
+
+    fn as_enc_data(&self) -> Option<&EncData> {
+        match self {
+            MaybeEncData::EncData(enc_data) => Some(enc_data),
+            MaybeEncData::Data(_) => None
+        }
+    }
+}
+
+fn with_new_content_id(enc_data: &EncData, ctx: &impl Context) -> EncData {
+    let mut meta: Metadata = (**enc_data.metadata()).clone();
+    meta.content_id = ctx.generate_content_id();
+    EncData::new(enc_data.transfer_encoded_buffer().clone(), meta, enc_data.encoding())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    iri: IRI,
+    use_file_name: Option<String>,
+    default_media_type: Option<String>
+}
+
+impl CacheKey {
+    fn new(source: &Source) -> Self {
+        let default_media_type = match source.use_media_type {
+            UseMediaType::Auto => None,
+            UseMediaType::Default(ref media_type) => Some(media_type.as_str_repr().to_owned())
+        };
+
+        CacheKey {
+            iri: source.iri.clone(),
+            use_file_name: source.use_file_name.clone(),
+            default_media_type
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    version: ResourceVersion,
+    enc_data: EncData,
+    last_used: u64
+}
+
+/// A simple LRU cache with a budget of transfer encoded bytes.
+#[derive(Debug)]
+struct LruCache {
+    max_bytes: usize,
+    used_bytes: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+    usage: BTreeMap<u64, CacheKey>
+}
+
+impl LruCache {
+
+    fn new(max_bytes: usize) -> Self {
+        LruCache {
+            max_bytes,
+            used_bytes: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            usage: BTreeMap::new()
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, key: &CacheKey, version: ResourceVersion) -> Option<EncData> {
+        let is_outdated = match self.entries.get(key) {
+            Some(entry) => entry.version != version,
+            None => return None
+        };
+
+        if is_outdated {
+            self.remove(key);
+            return None;
+        }
+
+        let tick = self.next_tick();
+        //UNWRAP_SAFE: we just checked that the entry exists
+        let entry = self.entries.get_mut(key).unwrap();
+        self.usage.remove(&entry.last_used);
+        entry.last_used = tick;
+        self.usage.insert(tick, key.clone());
+        Some(entry.enc_data.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, version: ResourceVersion, enc_data: EncData) {
+        self.remove(&key);
+
+        let size = enc_data.transfer_encoded_buffer().len();
+        if size > self.max_bytes {
+            return;
+        }
+
+        while self.used_bytes + size > self.max_bytes {
+            let lru_key = match self.usage.values().next() {
+                Some(key) => key.clone(),
+                None => break
+            };
+            self.remove(&lru_key);
+        }
+
+        let tick = self.next_tick();
+        self.used_bytes += size;
+        self.usage.insert(tick, key.clone());
+        self.entries.insert(key, CacheEntry { version, enc_data, last_used: tick });
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.usage.remove(&entry.last_used);
+            self.used_bytes -= entry.enc_data.transfer_encoded_buffer().len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.usage.clear();
+        self.used_bytes = 0;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use soft_ascii_string::SoftAsciiString;
+    use headers::header_components::{MediaType, Domain};
+
+    use crate::{Data, TransferEncodingHint, context::CompositeContext};
+    use super::super::{HashedIdGen, simple_cpu_pool};
+    use super::*;
+
+    type TestContext = CompositeContext<
+        CachingResourceLoader<CountingLoader>,
+        ::futures_cpupool::CpuPool,
+        HashedIdGen
+    >;
+
+    fn ctx(max_bytes: usize) -> TestContext {
+        let domain = Domain::from_unchecked("cache.test".to_owned());
+        let unique_part = SoftAsciiString::from_unchecked("c4c8e");
+        let id_gen = HashedIdGen::new(domain, unique_part).unwrap();
+        let loader = CachingResourceLoader::new(CountingLoader::default(), max_bytes);
+        CompositeContext::new(loader, simple_cpu_pool(), id_gen)
+    }
+
+    fn loads(ctx: &TestContext) -> usize {
+        ctx.resource_loader().inner().loads.load(Ordering::SeqCst)
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingLoader {
+        loads: AtomicUsize,
+        version: AtomicUsize
+    }
+
+    impl ResourceLoaderComponent for CountingLoader {
+        fn load_resource(&self, source: &Source, ctx: &impl Context)
+            -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+        {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            let data = Data::plain_text(source.iri.tail(), ctx.generate_content_id());
+            let enc_data = data.transfer_encode(TransferEncodingHint::UseBase64);
+            Box::new(future::ok(MaybeEncData::EncData(enc_data)))
+        }
+    }
+
+    impl ResourceVersionLookup for CountingLoader {
+        fn resource_version(&self, _source: &Source) -> Option<ResourceVersion> {
+            Some(ResourceVersion {
+                modified: None,
+                size: Some(self.version.load(Ordering::SeqCst) as u64)
+            })
+        }
+    }
+
+    fn source(tail: &str) -> Source {
+        Source {
+            iri: IRI::from_parts("count", tail).unwrap(),
+            use_media_type: UseMediaType::Default(MediaType::parse("text/plain").unwrap()),
+            use_file_name: None
+        }
+    }
+
+    fn load(ctx: &TestContext, tail: &str) -> EncData {
+        let resource = Resource::Source(source(tail));
+        assert_ok!(Context::load_transfer_encoded_resource(ctx, &resource).wait())
+    }
+
+    #[test]
+    fn cached_resources_are_not_loaded_again() {
+        let ctx = ctx(1024);
+        let first = load(&ctx, "logo");
+        let second = load(&ctx, "logo");
+
+        assert_eq!(loads(&ctx), 1);
+        assert_eq!(first.transfer_encoded_buffer(), second.transfer_encoded_buffer());
+        assert_ne!(first.content_id(), second.content_id());
+        assert_eq!(ctx.resource_loader().cached_resources(), 1);
+    }
+
+    #[test]
+    fn changed_versions_are_reloaded() {
+        let ctx = ctx(1024);
+        load(&ctx, "logo");
+        ctx.resource_loader().inner().version.fetch_add(1, Ordering::SeqCst);
+        load(&ctx, "logo");
+
+        assert_eq!(loads(&ctx), 2);
+        assert_eq!(ctx.resource_loader().cached_resources(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        // "a" and "b" both are 4 bytes base64 encoded
+        let ctx = ctx(8);
+        load(&ctx, "a");
+        load(&ctx, "b");
+        load(&ctx, "a");
+        load(&ctx, "c");
+        assert_eq!(ctx.resource_loader().cached_bytes(), 8);
+        assert_eq!(loads(&ctx), 3);
+
+        // "b" was evicted, "a" was not
+        load(&ctx, "a");
+        assert_eq!(loads(&ctx), 3);
+        load(&ctx, "b");
+        assert_eq!(loads(&ctx), 4);
+    }
+
+    #[test]
+    fn resources_larger_then_the_budget_are_not_cached() {
+        let ctx = ctx(2);
+        load(&ctx, "abc");
+        assert_eq!(ctx.resource_loader().cached_resources(), 0);
+        assert_eq!(ctx.resource_loader().cached_bytes(), 0);
+    }
+}
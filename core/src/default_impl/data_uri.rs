@@ -0,0 +1,236 @@
+//! Provides a resource loader for RFC 2397 `data:` IRIs.
+use futures::IntoFuture;
+
+use internals::bind::base64;
+use headers::header_components::{MediaType, FileMeta};
+
+use crate::{
+    utils::SendBoxFuture,
+    error::{ResourceLoadingError, ResourceLoadingErrorKind},
+    resource::{Data, Metadata, Source, UseMediaType},
+    context::{Context, ResourceLoaderComponent, MaybeEncData}
+};
+
+use super::{ResourceVersion, ResourceVersionLookup};
+
+const DATA_SCHEME: &str = "data";
+const DEFAULT_MEDIA_TYPE: &str = "text/plain; charset=us-ascii";
+
+/// A `ResourceLoaderComponent` for `data:` IRIs as specified in RFC 2397.
+///
+/// The data is decoded from the IRI (either base64 or percent encoded),
+/// the media type is taken from the IRI. If the IRI doesn't specify a
+/// media type the `UseMediaType::Default` media type of the source is
+/// used, or if there is none `text/plain; charset=us-ascii`.
+///
+/// Sources with any other scheme then `data` are rejected with
+/// `ResourceLoadingErrorKind::UnknownScheme`, malformed `data:` IRIs
+/// are rejected with `ResourceLoadingErrorKind::InvalidIRI`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// use mail_core::{IRI, Source, UseMediaType};
+///
+/// # fn main() {
+/// // can be loaded with a `DataUriResourceLoader`
+/// let source = Source {
+///     iri: IRI::new("data:text/plain;charset=utf-8;base64,SGVsbG8gV29ybGQ=").unwrap(),
+///     use_media_type: UseMediaType::Auto,
+///     use_file_name: Some("hello.txt".to_owned())
+/// };
+/// # let _ = source;
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DataUriResourceLoader;
+
+impl DataUriResourceLoader {
+
+    /// Create a new `data:` IRI resource loader.
+    pub fn new() -> Self {
+        DataUriResourceLoader
+    }
+}
+
+impl ResourceLoaderComponent for DataUriResourceLoader {
+
+    fn load_resource(&self, source: &Source, ctx: &impl Context)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        let iri = source.iri.clone();
+        if iri.scheme() != DATA_SCHEME {
+            let err = ResourceLoadingError
+                ::from((iri, ResourceLoadingErrorKind::UnknownScheme));
+            return Box::new(Err(err).into_future());
+        }
+
+        let use_media_type = source.use_media_type.clone();
+        let use_file_name = source.use_file_name.clone();
+        let content_id = ctx.generate_content_id();
+
+        ctx.offload_fn(move || {
+            let (media_type, buffer) = decode_data_uri(iri.tail(), use_media_type)
+                .map_err(|kind| ResourceLoadingError::from((iri.clone(), kind)))?;
+
+            let file_meta = FileMeta {
+                file_name: use_file_name,
+                size: Some(buffer.len()),
+                ..Default::default()
+            };
+
+            let data = Data::new(buffer, Metadata {
+                file_meta,
+                media_type,
+                content_id
+            });
+
+            Ok(MaybeEncData::EncData(data.transfer_encode(Default::default())))
+        })
+    }
+}
+
+impl ResourceVersionLookup for DataUriResourceLoader {
+    /// The data is part of the IRI, so it never changes.
+    fn resource_version(&self, _source: &Source) -> Option<ResourceVersion> {
+        Some(ResourceVersion::default())
+    }
+}
+
+/// Decodes the scheme specific part of a `data:` IRI into its media type and data.
+fn decode_data_uri(tail: &str, use_media_type: UseMediaType)
+    -> Result<(MediaType, Vec<u8>), ResourceLoadingErrorKind>
+{
+    let comma_idx = tail.find(',')
+        .ok_or(ResourceLoadingErrorKind::InvalidIRI)?;
+
+    let (header, data) = (&tail[..comma_idx], &tail[comma_idx+1..]);
+
+    let (header, is_base64) =
+        if header.len() >= 7 && header[header.len()-7..].eq_ignore_ascii_case(";base64") {
+            (&header[..header.len()-7], true)
+        } else {
+            (header, false)
+        };
+
+    let header = String::from_utf8(percent_decode(header)?)
+        .map_err(|_| ResourceLoadingErrorKind::InvalidIRI)?;
+
+    let media_type =
+        if header.is_empty() {
+            match use_media_type {
+                UseMediaType::Default(media_type) => media_type,
+                UseMediaType::Auto => MediaType::parse(DEFAULT_MEDIA_TYPE).unwrap()
+            }
+        } else if header.starts_with(';') {
+            MediaType::parse(&format!("text/plain{}", header))
+                .map_err(|_| ResourceLoadingErrorKind::InvalidIRI)?
+        } else {
+            MediaType::parse(&header)
+                .map_err(|_| ResourceLoadingErrorKind::InvalidIRI)?
+        };
+
+    let data = percent_decode(data)?;
+    let buffer =
+        if is_base64 {
+            base64::normal_decode(data)
+                .map_err(|_| ResourceLoadingErrorKind::InvalidIRI)?
+        } else {
+            data
+        };
+
+    Ok((media_type, buffer))
+}
+
+fn percent_decode(input: &str) -> Result<Vec<u8>, ResourceLoadingErrorKind> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(bch) = iter.next() {
+        if bch == b'%' {
+            let high = iter.next().and_then(hex_value);
+            let low = iter.next().and_then(hex_value);
+            match (high, low) {
+                (Some(high), Some(low)) => out.push(high << 4 | low),
+                _ => return Err(ResourceLoadingErrorKind::InvalidIRI)
+            }
+        } else {
+            out.push(bch);
+        }
+    }
+    Ok(out)
+}
+
+fn hex_value(bch: u8) -> Option<u8> {
+    match bch {
+        b'0'..=b'9' => Some(bch - b'0'),
+        b'a'..=b'f' => Some(bch - b'a' + 10),
+        b'A'..=b'F' => Some(bch - b'A' + 10),
+        _ => None
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+
+    use crate::{IRI, Resource};
+    use super::super::test_context;
+    use super::*;
+
+    fn decode(tail: &str) -> Result<(String, Vec<u8>), ResourceLoadingErrorKind> {
+        decode_data_uri(tail, UseMediaType::Auto)
+            .map(|(media_type, data)| (media_type.as_str_repr().to_owned(), data))
+    }
+
+    #[test]
+    fn decodes_base64() {
+        let (media_type, data) = assert_ok!(decode("image/png;base64,iVBORw0KGgo="));
+        assert_eq!(media_type, "image/png");
+        assert_eq!(data, b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn decodes_percent_encoding() {
+        let (media_type, data) = assert_ok!(decode("text/plain;charset=utf-8,a%20b%E2%86%92"));
+        assert_eq!(media_type, "text/plain;charset=utf-8");
+        assert_eq!(data, "a b→".as_bytes());
+    }
+
+    #[test]
+    fn uses_defaults_for_media_type() {
+        let (media_type, data) = assert_ok!(decode(",abc"));
+        assert_eq!(media_type, DEFAULT_MEDIA_TYPE);
+        assert_eq!(data, b"abc");
+
+        let (media_type, _) = assert_ok!(decode(";charset=utf-8,abc"));
+        assert_eq!(media_type, "text/plain;charset=utf-8");
+
+        let default = MediaType::parse("text/html").unwrap();
+        let (media_type, _) = assert_ok!(decode_data_uri(",abc", UseMediaType::Default(default)));
+        assert_eq!(media_type.as_str_repr(), "text/html");
+    }
+
+    #[test]
+    fn rejects_malformed_uris() {
+        assert_eq!(assert_err!(decode("text/plain")), ResourceLoadingErrorKind::InvalidIRI);
+        assert_eq!(assert_err!(decode("text/plain,a%2")), ResourceLoadingErrorKind::InvalidIRI);
+        assert_eq!(assert_err!(decode(";base64,a$b")), ResourceLoadingErrorKind::InvalidIRI);
+    }
+
+    #[test]
+    fn loads_through_context() {
+        let source = Source {
+            iri: IRI::new("data:image/png;base64,iVBORw0KGgo=").unwrap(),
+            use_media_type: UseMediaType::Auto,
+            use_file_name: Some("logo.png".to_owned())
+        };
+
+        let ctx = test_context();
+        let enc_data = assert_ok!(Context::load_transfer_encoded_resource(&ctx, &Resource::Source(source)).wait());
+        assert_eq!(enc_data.media_type().as_str_repr(), "image/png");
+        assert_eq!(enc_data.file_meta().file_name, Some("logo.png".to_owned()));
+        assert_eq!(enc_data.file_meta().size, Some(8));
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    path::{Path, PathBuf},
+    path::{Path, PathBuf, Component},
     fs::{self, File},
     io::{self, Read},
     env,
@@ -8,7 +8,7 @@ use std::{
 
 use checked_command::CheckedCommand;
 use failure::Fail;
-use futures::IntoFuture;
+use futures::{Future, IntoFuture};
 
 use headers::header_components::{
     MediaType,
@@ -38,21 +38,56 @@ use crate::{
     }
 };
 
+use super::{PostProcess, ResourceVersion, ResourceVersionLookup};
+
 // have a scheme ignoring variant for Mux as the scheme is preset
 // allow a setup with different scheme path/file etc. the behavior stays the same!
-// do not handle sandboxing/security as such do not handle "file" only "path" ~use open_at if available?~
+// sandboxing is opt-in (`confined`) and path based, i.e. ~use open_at if available?~
+
+/// Specifies how a confined `FsResourceLoader` handles symlinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymlinkPolicy {
+    /// Reject any path which contains a symlink below the root.
+    Deny,
+
+    /// Follow symlinks as long as the resolved path is still inside of the root.
+    WithinRoot,
+
+    /// Follow all symlinks, even if they point outside of the root.
+    ///
+    /// Paths are still checked for `..` escapes and absolute paths.
+    Follow
+}
 
 //TODO more doc
 /// By setting SchemeValidation to Disabled the FsResourceLoader can be used to simple
 /// load a resource from a file based on a scheme tail as path independent of the rest,
 /// so e.g. it it is used in a `Mux` which selects a `ResourceLoader` impl based on a scheme
 /// the scheme would not be double validated.
+///
+/// All loaded data is passed through the loaders `PostProcess` pipeline
+/// before it is transfer encoded, by default the pipeline is empty.
+///
+/// # Confinement
+///
+/// By default the IRI tail is just joined with the root, i.e. `path:../x`
+/// or absolute paths can access files outside of the root. If the IRI
+/// can be influenced by a user the loader should be `confined`, in which
+/// case paths with `..` escaping the root and absolute paths are rejected
+/// and symlinks are handled according to a `SymlinkPolicy`. Violations
+/// are reported as `ResourceLoadingErrorKind::SandboxViolation`.
+///
+/// Note that the checks are path based, i.e. if an attacker can modify
+/// the file system under the root concurrently they can still race
+/// the checks.
 #[derive( Debug, Clone, PartialEq, Default )]
 pub struct FsResourceLoader<
     SchemeValidation: ConstSwitch = Enabled,
 > {
     root: PathBuf,
     scheme: &'static str,
+    post_process: PostProcess,
+    confinement: Option<SymlinkPolicy>,
     _marker: PhantomData<SchemeValidation>
 }
 
@@ -69,7 +104,13 @@ impl<SVSw> FsResourceLoader<SVSw>
     }
 
     pub fn new_with_scheme<P: Into<PathBuf>>( root: P, scheme: &'static str ) -> Self {
-        FsResourceLoader { root: root.into(), scheme, _marker: PhantomData}
+        FsResourceLoader {
+            root: root.into(),
+            scheme,
+            post_process: PostProcess::new(),
+            confinement: None,
+            _marker: PhantomData
+        }
     }
 
     pub fn with_cwd_root() -> Result<Self, io::Error> {
@@ -85,6 +126,38 @@ impl<SVSw> FsResourceLoader<SVSw>
         self.scheme
     }
 
+    /// Sets the post processing pipeline used for all loaded resources.
+    pub fn with_post_process(mut self, post_process: PostProcess) -> Self {
+        self.post_process = post_process;
+        self
+    }
+
+    /// Returns a reference to the post processing pipeline.
+    pub fn post_process(&self) -> &PostProcess {
+        &self.post_process
+    }
+
+    /// Returns a mutable reference to the post processing pipeline.
+    pub fn post_process_mut(&mut self) -> &mut PostProcess {
+        &mut self.post_process
+    }
+
+    /// Confines all loaded files to the root using given symlink policy.
+    pub fn confined(mut self, symlink_policy: SymlinkPolicy) -> Self {
+        self.confinement = Some(symlink_policy);
+        self
+    }
+
+    /// Returns true if loaded files are confined to the root.
+    pub fn is_confined(&self) -> bool {
+        self.confinement.is_some()
+    }
+
+    /// Returns the symlink policy if loaded files are confined to the root.
+    pub fn symlink_policy(&self) -> Option<SymlinkPolicy> {
+        self.confinement
+    }
+
     pub fn does_validate_scheme(&self) -> bool {
         SVSw::ENABLED
     }
@@ -110,26 +183,87 @@ impl<ValidateScheme> ResourceLoaderComponent for FsResourceLoader<ValidateScheme
             return Box::new(Err(err).into_future());
         }
 
-        let path = self.root().join(path_from_tail(&source.iri));
         let use_media_type = source.use_media_type.clone();
         let use_file_name = source.use_file_name.clone();
-
-        load_data(
-            path,
-            use_media_type,
-            use_file_name,
-            ctx,
-            |data| Ok(MaybeEncData::EncData(data.transfer_encode(Default::default())))
-        )
+        let max_size = self.post_process.size_limit();
+        let post_process = self.post_process.clone();
+        let post_process = move |data| {
+            let data = post_process.apply(data)?;
+            Ok(MaybeEncData::EncData(data.transfer_encode(Default::default())))
+        };
+
+        let symlink_policy = match self.confinement {
+            Some(symlink_policy) => symlink_policy,
+            None => {
+                let path = self.root().join(path_from_tail(&source.iri));
+                return load_data_with_limit(
+                    path, use_media_type, use_file_name, max_size, ctx, post_process);
+            }
+        };
+
+        let iri = source.iri.clone();
+        let relative_path = match normalize_confined(path_from_tail(&iri)) {
+            Ok(path) => path,
+            Err(kind) => {
+                let err = ResourceLoadingError::from((iri, kind));
+                return Box::new(Err(err).into_future());
+            }
+        };
+
+        let root = self.root().to_owned();
+        let ctx2 = ctx.clone();
+        let fut = ctx
+            .offload_fn(move || {
+                resolve_symlinks(&root, &relative_path, symlink_policy)
+                    .map_err(|err| err.with_source_iri_or_else(|| Some(iri)))
+            })
+            .and_then(move |path| {
+                load_data_with_limit(
+                    path, use_media_type, use_file_name, max_size, &ctx2, post_process)
+            });
+
+        Box::new(fut)
     }
 }
 
 
-//TODO add a PostProcess hook which can be any combination of
-// FixNewline, SniffMediaType and custom postprocessing
-// now this has new responsibilities
-// 2. get and create File Meta
-// 3. if source.media_type.is_none() do cautious mime sniffing
+impl<ValidateScheme> ResourceVersionLookup for FsResourceLoader<ValidateScheme>
+    where ValidateScheme: ConstSwitch
+{
+    /// Uses the modification time and size of the file as version.
+    ///
+    /// If the loader is confined the same checks as for loading the file
+    /// are done, i.e. there is no version for paths outside of the root.
+    fn resource_version(&self, source: &Source) -> Option<ResourceVersion> {
+        if ValidateScheme::ENABLED && !self.iri_has_compatible_scheme(&source.iri) {
+            return None;
+        }
+
+        let relative_path = path_from_tail(&source.iri);
+        let path = match self.confinement {
+            Some(symlink_policy) => {
+                let relative_path = normalize_confined(relative_path).ok()?;
+                resolve_symlinks(self.root(), &relative_path, symlink_policy).ok()?
+            },
+            None => self.root().join(relative_path)
+        };
+
+        let meta = fs::metadata(path).ok()?;
+        Some(ResourceVersion {
+            modified: meta.modified().ok(),
+            size: Some(meta.len())
+        })
+    }
+}
+
+/// Loads the file at `path` (offloaded) and passes the resulting `Data` to `post_process`.
+///
+/// This creates the `FileMeta` for the file and, if `use_media_type` is
+/// `UseMediaType::Auto`, sniffs the media type of the file.
+///
+/// The `post_process` function can be used to turn the data into any form
+/// needed, e.g. the `FsResourceLoader` applies its `PostProcess` pipeline
+/// and then transfer encodes the data.
 pub fn load_data<R, F>(
     path: PathBuf,
     use_media_type: UseMediaType,
@@ -139,6 +273,25 @@ pub fn load_data<R, F>(
 ) -> SendBoxFuture<R, ResourceLoadingError>
     where R: Send + 'static,
           F: FnOnce(Data) -> Result<R, ResourceLoadingError> + Send + 'static
+{
+    load_data_with_limit(path, use_media_type, use_file_name, None, ctx, post_process)
+}
+
+/// Like `load_data` but fails with `SizeLimitExceeded` before reading the
+/// file if it is larger then `max_size`.
+///
+/// At most `max_size + 1` bytes are read, so a file growing after the check
+/// is still caught by the size check of the post processing.
+fn load_data_with_limit<R, F>(
+    path: PathBuf,
+    use_media_type: UseMediaType,
+    use_file_name: Option<String>,
+    max_size: Option<usize>,
+    ctx: &impl Context,
+    post_process: F,
+) -> SendBoxFuture<R, ResourceLoadingError>
+    where R: Send + 'static,
+          F: FnOnce(Data) -> Result<R, ResourceLoadingError> + Send + 'static
 {
     let content_id = ctx.generate_content_id();
     ctx.offload_fn(move || {
@@ -151,7 +304,13 @@ pub fn load_data<R, F>(
                 }
             })?;
 
-        let mut file_meta = file_meta_from_metadata(fd.metadata()?);
+        let meta = fd.metadata()?;
+        if let Some(max_size) = max_size {
+            if meta.len() > max_size as u64 {
+                return Err(ResourceLoadingErrorKind::SizeLimitExceeded.into());
+            }
+        }
+        let mut file_meta = file_meta_from_metadata(meta);
 
         if let Some(name) = use_file_name {
             file_meta.file_name = Some(name)
@@ -161,7 +320,10 @@ pub fn load_data<R, F>(
         }
 
         let mut buffer = Vec::new();
-        fd.read_to_end(&mut buffer)?;
+        match max_size {
+            Some(max_size) => fd.take(max_size as u64 + 1).read_to_end(&mut buffer)?,
+            None => fd.read_to_end(&mut buffer)?
+        };
 
         let media_type =
             match use_media_type {
@@ -231,6 +393,66 @@ fn get_file_size(meta: &fs::Metadata) -> Option<u64> {
     None
 }
 
+/// Lexically normalizes a path so that it stays inside of the directory it's relative to.
+///
+/// Absolute paths (incl. windows prefixes) and `..` components leaving the directory
+/// are rejected.
+fn normalize_confined(path: &Path) -> Result<PathBuf, ResourceLoadingErrorKind> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                return Err(ResourceLoadingErrorKind::SandboxViolation);
+            },
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(ResourceLoadingErrorKind::SandboxViolation);
+                }
+            },
+            Component::Normal(part) => normalized.push(part)
+        }
+    }
+    Ok(normalized)
+}
+
+/// Checks the symlinks of `root.join(relative_path)` based on the symlink policy.
+///
+/// The relative path is expected to be normalized with `normalize_confined`.
+fn resolve_symlinks(root: &Path, relative_path: &Path, symlink_policy: SymlinkPolicy)
+    -> Result<PathBuf, ResourceLoadingError>
+{
+    let path = root.join(relative_path);
+    match symlink_policy {
+        SymlinkPolicy::Follow => {},
+        SymlinkPolicy::Deny => {
+            let mut current = root.to_owned();
+            for component in relative_path.components() {
+                current.push(component);
+                match fs::symlink_metadata(&current) {
+                    Ok(meta) => {
+                        if meta.file_type().is_symlink() {
+                            return Err(ResourceLoadingErrorKind::SandboxViolation.into());
+                        }
+                    },
+                    // opening the file will fail with the appropriate error
+                    Err(_) => break
+                }
+            }
+        },
+        SymlinkPolicy::WithinRoot => {
+            let canonical_root = root.canonicalize()?;
+            // if it fails opening the file will fail with the appropriate error
+            if let Ok(canonical_path) = path.canonicalize() {
+                if !canonical_path.starts_with(&canonical_root) {
+                    return Err(ResourceLoadingErrorKind::SandboxViolation.into());
+                }
+            }
+        }
+    }
+    Ok(path)
+}
+
 fn path_from_tail(path_iri: &IRI) -> &Path {
     let tail = path_iri.tail();
     let path = if tail.starts_with("///") {
@@ -259,4 +481,171 @@ mod tests {
             assert_eq!(res.as_str_repr(), "text/plain; charset=us-ascii");
         }
     }
-}
\ No newline at end of file
+
+    mod size_limit {
+        use std::process;
+        use futures::Future;
+        use crate::{context::CompositeContext, Resource};
+        use super::super::*;
+        use super::super::super::{HashedIdGen, simple_cpu_pool, PostProcess, SizeLimit};
+        use headers::header_components::Domain;
+        use soft_ascii_string::SoftAsciiString;
+
+        #[test]
+        fn size_limit_is_checked_before_reading() {
+            let dir = env::temp_dir()
+                .join(format!("mail-core-fs-{}-size-limit", process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("sub/file.txt"), b"inside").unwrap();
+
+            let loader: FsResourceLoader = FsResourceLoader::new(&dir)
+                .with_post_process(PostProcess::new().with_step(SizeLimit::new(3)));
+
+            let domain = Domain::from_unchecked("fs.test".to_owned());
+            let unique_part = SoftAsciiString::from_unchecked("s1z3");
+            let id_gen = HashedIdGen::new(domain, unique_part).unwrap();
+            let ctx = CompositeContext::new(loader, simple_cpu_pool(), id_gen);
+            let media_type = || UseMediaType::Default(MediaType::parse("text/plain").unwrap());
+
+            let err = assert_err!(load_data_with_limit(
+                dir.join("sub/file.txt"), media_type(), None, Some(3), &ctx,
+                |_data| -> Result<(), ResourceLoadingError> { panic!("file was read") }
+            ).wait());
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::SizeLimitExceeded);
+
+            let source = Source {
+                iri: IRI::new("path:sub/file.txt").unwrap(),
+                use_media_type: media_type(),
+                use_file_name: None
+            };
+            let resource = Resource::Source(source);
+            let err = assert_err!(Context::load_transfer_encoded_resource(&ctx, &resource).wait());
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::SizeLimitExceeded);
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    mod confinement {
+        use std::process;
+        use super::super::*;
+
+        fn test_dir(name: &str) -> PathBuf {
+            let dir = env::temp_dir()
+                .join(format!("mail-core-fs-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("root/sub")).unwrap();
+            fs::write(dir.join("root/sub/file.txt"), b"inside").unwrap();
+            fs::write(dir.join("secret.txt"), b"outside").unwrap();
+            dir
+        }
+
+        #[test]
+        fn normalizes_paths_inside_of_the_root() {
+            let path = assert_ok!(normalize_confined(Path::new("./sub/../sub/file.txt")));
+            assert_eq!(path, Path::new("sub/file.txt"));
+        }
+
+        #[test]
+        fn rejects_escaping_paths() {
+            for path in &["../secret.txt", "sub/../../secret.txt", "/etc/passwd"] {
+                let kind = assert_err!(normalize_confined(Path::new(path)), path);
+                assert_eq!(kind, ResourceLoadingErrorKind::SandboxViolation);
+            }
+        }
+
+        #[test]
+        fn absolute_tails_are_rejected() {
+            let iri = IRI::new("path:///etc/passwd").unwrap();
+            let kind = assert_err!(normalize_confined(path_from_tail(&iri)));
+            assert_eq!(kind, ResourceLoadingErrorKind::SandboxViolation);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn symlink_policies() {
+            use std::os::unix::fs::symlink;
+
+            let dir = test_dir("symlinks");
+            let root = dir.join("root");
+            symlink(dir.join("secret.txt"), root.join("escape.txt")).unwrap();
+            symlink(root.join("sub/file.txt"), root.join("inside.txt")).unwrap();
+
+            let escape = Path::new("escape.txt");
+            let inside = Path::new("inside.txt");
+            let plain = Path::new("sub/file.txt");
+
+            let err = assert_err!(resolve_symlinks(&root, inside, SymlinkPolicy::Deny));
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::SandboxViolation);
+            assert_ok!(resolve_symlinks(&root, plain, SymlinkPolicy::Deny));
+
+            let err = assert_err!(resolve_symlinks(&root, escape, SymlinkPolicy::WithinRoot));
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::SandboxViolation);
+            assert_ok!(resolve_symlinks(&root, inside, SymlinkPolicy::WithinRoot));
+
+            assert_ok!(resolve_symlinks(&root, escape, SymlinkPolicy::Follow));
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn version_lookup_is_confined() {
+            use std::os::unix::fs::symlink;
+
+            let dir = test_dir("version");
+            let root = dir.join("root");
+            symlink(dir.join("secret.txt"), root.join("escape.txt")).unwrap();
+            let loader: FsResourceLoader = FsResourceLoader::new(&root)
+                .confined(SymlinkPolicy::WithinRoot);
+
+            let version = |iri: &str| loader.resource_version(&Source {
+                iri: IRI::new(iri).unwrap(),
+                use_media_type: UseMediaType::Auto,
+                use_file_name: None
+            });
+
+            assert!(version("path:sub/file.txt").is_some());
+            assert!(version("path:../secret.txt").is_none());
+            assert!(version("path:escape.txt").is_none());
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+
+        #[test]
+        fn confined_loader_rejects_escapes() {
+            use crate::{context::CompositeContext, Resource};
+            use super::super::super::{HashedIdGen, simple_cpu_pool};
+            use headers::header_components::Domain;
+            use soft_ascii_string::SoftAsciiString;
+
+            let dir = test_dir("loader");
+            let loader: FsResourceLoader = FsResourceLoader::new(dir.join("root"))
+                .confined(SymlinkPolicy::Deny);
+
+            let domain = Domain::from_unchecked("fs.test".to_owned());
+            let unique_part = SoftAsciiString::from_unchecked("f5t3");
+            let id_gen = HashedIdGen::new(domain, unique_part).unwrap();
+            let ctx = CompositeContext::new(loader, simple_cpu_pool(), id_gen);
+
+            let load = |iri: &str| {
+                let source = Source {
+                    iri: IRI::new(iri).unwrap(),
+                    use_media_type: UseMediaType::Default(MediaType::parse("text/plain").unwrap()),
+                    use_file_name: None
+                };
+                Context::load_transfer_encoded_resource(&ctx, &Resource::Source(source)).wait()
+            };
+
+            let enc_data = assert_ok!(load("path:sub/file.txt"));
+            assert_eq!(enc_data.file_meta().file_name, Some("file.txt".to_owned()));
+
+            let err = assert_err!(load("path:../secret.txt"));
+            assert_eq!(err.kind(), ResourceLoadingErrorKind::SandboxViolation);
+            assert_eq!(err.source_iri().map(|iri| iri.as_str()), Some("path:../secret.txt"));
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Provides a resource loader for resources stored in memory, e.g. `mem:invoice-123`.
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock}
+};
+
+use futures::{future, IntoFuture};
+
+use headers::header_components::{MediaType, FileMeta};
+
+use crate::{
+    utils::SendBoxFuture,
+    error::{ResourceLoadingError, ResourceLoadingErrorKind},
+    resource::{Data, Metadata, Source},
+    context::{Context, ResourceLoaderComponent, MaybeEncData}
+};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    buffer: Arc<[u8]>,
+    media_type: MediaType,
+    file_meta: FileMeta
+}
+
+/// A `ResourceLoaderComponent` which loads resources from a keyed in-memory store.
+///
+/// The tail of the IRI is used as key, e.g. `mem:invoice-123` will load
+/// the resource stored under the key `invoice-123`. The scheme of the IRI
+/// is _not_ validated, so that the loader can be registered under any
+/// scheme in a `Mux`.
+///
+/// Resources have to be inserted into the store ahead of time, e.g. before
+/// a template referring to them is rendered. The loader is a cheaply
+/// cloneable handle to a shared store, so a clone can be kept to fill
+/// the store after the loader was moved into a `Mux` or `Context`.
+///
+/// Each time a resource is loaded it gets a new content id from the context,
+/// i.e. the content id of `Data` inserted with `insert_data` is not reused.
+///
+/// If a resource is not in the store the loading fails with
+/// `ResourceLoadingErrorKind::NotFound`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// # extern crate mail_headers;
+/// use mail_headers::header_components::MediaType;
+/// use mail_core::default_impl::{InMemoryResourceLoader, Mux};
+///
+/// # fn main() {
+/// let loader = InMemoryResourceLoader::new();
+/// let store = loader.clone();
+/// let mux = Mux::new().with_loader("mem", loader).unwrap();
+///
+/// let pdf = vec![b'%', b'P', b'D', b'F', b'-'];
+/// store.insert("invoice-123", pdf, MediaType::parse("application/pdf").unwrap());
+/// assert!(store.contains("invoice-123"));
+/// # let _ = mux;
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryResourceLoader {
+    store: Arc<RwLock<HashMap<String, Entry>>>
+}
+
+impl InMemoryResourceLoader {
+
+    /// Create a new loader with a empty store.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Inserts a resource under given key.
+    ///
+    /// Returns true if a resource with the same key was replaced.
+    pub fn insert(
+        &self,
+        key: impl Into<String>,
+        buffer: impl Into<Arc<[u8]>>,
+        media_type: MediaType
+    ) -> bool {
+        self.insert_with_file_meta(key, buffer, media_type, Default::default())
+    }
+
+    /// Inserts a resource with given file meta under given key.
+    ///
+    /// Returns true if a resource with the same key was replaced.
+    pub fn insert_with_file_meta(
+        &self,
+        key: impl Into<String>,
+        buffer: impl Into<Arc<[u8]>>,
+        media_type: MediaType,
+        file_meta: FileMeta
+    ) -> bool {
+        let entry = Entry {
+            buffer: buffer.into(),
+            media_type,
+            file_meta
+        };
+        self.store.write().unwrap()
+            .insert(key.into(), entry)
+            .is_some()
+    }
+
+    /// Inserts the buffer and metadata (except the content id) of `data`.
+    ///
+    /// Returns true if a resource with the same key was replaced.
+    pub fn insert_data(&self, key: impl Into<String>, data: &Data) -> bool {
+        self.insert_with_file_meta(
+            key,
+            data.buffer().clone(),
+            data.media_type().clone(),
+            data.file_meta().clone()
+        )
+    }
+
+    /// Removes the resource with given key, returns true if there was one.
+    pub fn remove(&self, key: &str) -> bool {
+        self.store.write().unwrap()
+            .remove(key)
+            .is_some()
+    }
+
+    /// Returns true if there is a resource with given key.
+    pub fn contains(&self, key: &str) -> bool {
+        self.store.read().unwrap()
+            .contains_key(key)
+    }
+
+    /// Removes all resources from the store.
+    pub fn clear(&self) {
+        self.store.write().unwrap().clear()
+    }
+}
+
+impl ResourceLoaderComponent for InMemoryResourceLoader {
+
+    fn load_resource(&self, source: &Source, ctx: &impl Context)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        let entry = self.store.read().unwrap()
+            .get(source.iri.tail())
+            .cloned();
+
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                let err = ResourceLoadingError
+                    ::from((source.iri.clone(), ResourceLoadingErrorKind::NotFound));
+                return Box::new(Err(err).into_future());
+            }
+        };
+
+        let Entry { buffer, media_type, mut file_meta } = entry;
+        if let Some(name) = source.use_file_name.clone() {
+            file_meta.file_name = Some(name);
+        }
+        if file_meta.size.is_none() {
+            file_meta.size = Some(buffer.len());
+        }
+
+        let data = Data::new(buffer, Metadata {
+            file_meta,
+            media_type,
+            content_id: ctx.generate_content_id()
+        });
+
+        Box::new(future::ok(MaybeEncData::Data(data)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+
+    use crate::{IRI, UseMediaType};
+    use super::super::{test_context, test_context_with_mem_store};
+    use super::*;
+
+    fn source(iri: &str) -> Source {
+        Source {
+            iri: IRI::new(iri).unwrap(),
+            use_media_type: UseMediaType::Auto,
+            use_file_name: None
+        }
+    }
+
+    fn load(loader: &InMemoryResourceLoader, iri: &str) -> Result<Data, ResourceLoadingError> {
+        match loader.load_resource(&source(iri), &test_context()).wait()? {
+            MaybeEncData::Data(data) => Ok(data),
+            MaybeEncData::EncData(_) => panic!("unexpected transfer encoded data")
+        }
+    }
+
+    #[test]
+    fn loads_inserted_resources() {
+        let loader = InMemoryResourceLoader::new();
+        assert_not!(loader.insert("invoice-123", b"%PDF-".to_vec(),
+            MediaType::parse("application/pdf").unwrap()));
+
+        let data = assert_ok!(load(&loader, "mem:invoice-123"));
+        assert_eq!(&**data.buffer(), b"%PDF-");
+        assert_eq!(data.media_type().as_str_repr(), "application/pdf");
+        assert_eq!(data.file_meta().size, Some(5));
+    }
+
+    #[test]
+    fn each_load_has_a_new_content_id() {
+        let loader = InMemoryResourceLoader::new();
+        loader.insert("logo", b"abc".to_vec(), MediaType::parse("image/png").unwrap());
+
+        let first = assert_ok!(load(&loader, "mem:logo"));
+        let second = assert_ok!(load(&loader, "mem:logo"));
+        assert_ne!(first.content_id(), second.content_id());
+    }
+
+    #[test]
+    fn clones_share_the_store() {
+        let (ctx, store) = test_context_with_mem_store();
+        store.insert("logo", b"abc".to_vec(), MediaType::parse("image/png").unwrap());
+
+        let data = match assert_ok!(Context::load_resource(&ctx, &source("mem:logo")).wait()) {
+            MaybeEncData::Data(data) => data,
+            MaybeEncData::EncData(_) => panic!("unexpected transfer encoded data")
+        };
+        assert_eq!(&**data.buffer(), b"abc");
+    }
+
+    #[test]
+    fn missing_resources_are_not_found() {
+        let loader = InMemoryResourceLoader::new();
+        let err = assert_err!(load(&loader, "mem:nope"));
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::NotFound);
+
+        loader.insert("nope", b"abc".to_vec(), MediaType::parse("text/plain").unwrap());
+        assert!(loader.remove("nope"));
+        assert_err!(load(&loader, "mem:nope"));
+    }
+}
@@ -10,6 +10,21 @@ pub use self::cpupool::*;
 mod fs;
 pub use self::fs::*;
 
+mod post_process;
+pub use self::post_process::*;
+
+mod mux;
+pub use self::mux::*;
+
+mod data_uri;
+pub use self::data_uri::*;
+
+mod in_memory;
+pub use self::in_memory::*;
+
+mod cache;
+pub use self::cache::*;
+
 mod message_id_gen;
 pub use self::message_id_gen::*;
 
@@ -24,17 +39,30 @@ compile_error!("test need following (default) features: default_impl_cpupool, de
 use soft_ascii_string::SoftAsciiString;
 #[cfg(test)]
 use headers::header_components::Domain;
+#[cfg(test)]
+use context::CompositeContext;
 
+/// Context used for unit tests, it can only load `data:` and `mem:` resources.
 #[cfg(test)]
-pub type TestContext = simple_context::Context;
+pub type TestContext = CompositeContext<Mux, futures_cpupool::CpuPool, HashedIdGen>;
 
 //same crate so we can do this ;=)
 #[cfg(test)]
 pub fn test_context() -> TestContext {
-    //TODO crate a test context which does not access the file system
+    test_context_with_mem_store().0
+}
+
+/// Like `test_context` but also returns the store used for `mem:` resources.
+#[cfg(test)]
+pub fn test_context_with_mem_store() -> (TestContext, InMemoryResourceLoader) {
     let domain = Domain::from_unchecked("fooblabar.test".to_owned());
     let unique_part = SoftAsciiString::from_unchecked("CM0U3c412");
-    simple_context::new(domain, unique_part).unwrap()
+    let store = InMemoryResourceLoader::new();
+    let resource_loader = Mux::new()
+        .with_loader("data", DataUriResourceLoader::new()).unwrap()
+        .with_loader("mem", store.clone()).unwrap();
+    let id_gen = HashedIdGen::new(domain, unique_part).unwrap();
+    (CompositeContext::new(resource_loader, simple_cpu_pool(), id_gen), store)
 }
 
 
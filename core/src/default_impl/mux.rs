@@ -1,64 +1,519 @@
-use context::{OffloaderComponent, ResourceLoaderComponent};
-use utils::SendBoxFuture;
-use error::Error;
-use file_buffer::FileBuffer;
+//! Provides a `ResourceLoaderComponent` which dispatches based on the IRI scheme.
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc
+};
 
-type BufferFuture = SendBoxFuture<FileBuffer, Error>;
-type DynResourceLoader =
-    ResourceLoaderComponent<BufferFuture=BufferFuture>;
+use futures::{Future, IntoFuture};
 
+use headers::header_components::{MessageId, ContentId, AutoSubmitted};
+
+use crate::{
+    utils::SendBoxFuture,
+    error::{ResourceLoadingError, ResourceLoadingErrorKind},
+    resource::{Source, Resource, EncData},
+    context::{
+        Context,
+        MaybeEncData,
+        ResourceLoaderComponent,
+        default_impl_for_load_transfer_encoded_resource
+    }
+};
+
+use super::{ResourceVersion, ResourceVersionLookup};
+
+/// Error returned when registering a loader for a scheme which already has one.
+#[derive(Clone, Debug, Fail)]
+#[fail(display = "a resource loader for the scheme {:?} is already registered", scheme)]
+pub struct SchemeAlreadyRegistered {
+    /// The (lower case) scheme which was registered twice.
+    pub scheme: String
+}
+
+/// A `ResourceLoaderComponent` dispatching to other loaders based on the IRI scheme.
+///
+/// For each `Source` the scheme of it's IRI is used to look up a loader, if
+/// no loader is registered for the scheme the loading fails with
+/// `ResourceLoadingErrorKind::UnknownScheme`.
+///
+/// Schemes are matched case insensitive (IRI schemes are always lower case).
+///
+/// # Registering a scheme twice
+///
+/// `register` will _not_ override an existing loader but return an error
+/// instead, to override a loader `replace` (or `replace_versioned`) has to
+/// be used explicitly.
+///
+/// # Scheme validation
+///
+/// Loaders which validate the scheme themselves (e.g. the default
+/// `FsResourceLoader`) will still do so when used through a `Mux`.
+/// As the scheme is already selected by the `Mux` it's recommended to
+/// use a `FsResourceLoader<Disabled>` which doesn't validate the scheme.
+/// This also allows registering the same file system loader for
+/// multiple schemes.
+///
+/// # Caching
+///
+/// The `Mux` implements `ResourceVersionLookup` by delegating to the loader
+/// registered for the scheme, so that it can be wrapped in a
+/// `CachingResourceLoader`. For this the loader has to be registered with
+/// `register_versioned`/`with_versioned_loader`/`replace_versioned`, resources
+/// of loaders registered with `register`/`with_loader`/`replace` are not cached.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// use mail_core::{
+///     utils::Disabled,
+///     default_impl::{Mux, FsResourceLoader}
+/// };
+///
+/// # fn main() {
+/// let mut mux = Mux::new();
+/// let templates: FsResourceLoader<Disabled> = FsResourceLoader::new("./templates");
+/// mux.register("path", templates).unwrap();
+/// assert!(mux.register("PATH", FsResourceLoader::<Disabled>::new("./other")).is_err());
+/// # }
+/// ```
+#[derive(Debug, Default)]
 pub struct Mux {
-    lookup: HashMap<&'static str, Box<DynResourceLoader>>
+    loaders: HashMap<String, Box<dyn DynResourceLoader>>
 }
 
 impl Mux {
 
-    pub fn set_handle<R>(&mut self, scheme: &'static str, handle: R)
+    /// Create a new `Mux` without any loaders.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a loader for given scheme.
+    ///
+    /// # Error
+    ///
+    /// If there is already a loader registered for given scheme a error is
+    /// returned and the existing loader is kept.
+    pub fn register<R>(&mut self, scheme: &str, loader: R) -> Result<(), SchemeAlreadyRegistered>
+        where R: ResourceLoaderComponent
+    {
+        let scheme = scheme.to_ascii_lowercase();
+        if self.loaders.contains_key(&scheme) {
+            return Err(SchemeAlreadyRegistered { scheme });
+        }
+        self.loaders.insert(scheme, Box::new(Unversioned(loader)));
+        Ok(())
+    }
+
+    /// Registers a loader which supports version lookups for given scheme.
+    ///
+    /// Like `register` but the `Mux` will delegate `resource_version`
+    /// calls for the scheme to the loader.
+    pub fn register_versioned<R>(&mut self, scheme: &str, loader: R) -> Result<(), SchemeAlreadyRegistered>
+        where R: ResourceVersionLookup
+    {
+        let scheme = scheme.to_ascii_lowercase();
+        if self.loaders.contains_key(&scheme) {
+            return Err(SchemeAlreadyRegistered { scheme });
+        }
+        self.loaders.insert(scheme, Box::new(Versioned(loader)));
+        Ok(())
+    }
+
+    /// Registers a loader for given scheme and returns self.
+    ///
+    /// This is a builder style version of `register`.
+    pub fn with_loader<R>(mut self, scheme: &str, loader: R) -> Result<Self, SchemeAlreadyRegistered>
+        where R: ResourceLoaderComponent
+    {
+        self.register(scheme, loader)?;
+        Ok(self)
+    }
+
+    /// Registers a loader which supports version lookups for given scheme and returns self.
+    ///
+    /// This is a builder style version of `register_versioned`.
+    pub fn with_versioned_loader<R>(mut self, scheme: &str, loader: R) -> Result<Self, SchemeAlreadyRegistered>
+        where R: ResourceVersionLookup
+    {
+        self.register_versioned(scheme, loader)?;
+        Ok(self)
+    }
+
+    /// Registers a loader for given scheme replacing any existing loader.
+    ///
+    /// Like with `register` the `Mux` has no version lookup for the scheme
+    /// afterwards, even if the replaced loader was registered with
+    /// `register_versioned`, use `replace_versioned` to keep it.
+    ///
+    /// Returns true if a existing loader was replaced.
+    pub fn replace<R>(&mut self, scheme: &str, loader: R) -> bool
         where R: ResourceLoaderComponent
     {
-        //it would be awesome if we could specialize this for
-        // R: ResourceLoaderComponent<BufferFuture=BufferFuture>
-        // we actually should be able to using Monomorphization + if with branch elemination etc.
-        // let any: Box<Any> = Box::new(Option<R::BufferFutur>::None)
-        // any.downcast_ref::<Option<BufferFuture>>()
-        //    Ok() => self.lookup.insert(scheme, handle)
-        //    Err() => wrap => self.lookup.insert(scheme, wrap)
-        let wrapped = Wrapper { inner: handle };
-        let boxed: DynResourceLoader = Box::new(wrapped);
-        //TODO what happens with previous
-        self.lookup.insert(scheme, boxed);
+        let scheme = scheme.to_ascii_lowercase();
+        self.loaders.insert(scheme, Box::new(Unversioned(loader))).is_some()
+    }
+
+    /// Registers a loader which supports version lookups for given scheme replacing any existing loader.
+    ///
+    /// Like `replace` but the `Mux` will delegate `resource_version`
+    /// calls for the scheme to the loader.
+    ///
+    /// Returns true if a existing loader was replaced.
+    pub fn replace_versioned<R>(&mut self, scheme: &str, loader: R) -> bool
+        where R: ResourceVersionLookup
+    {
+        let scheme = scheme.to_ascii_lowercase();
+        self.loaders.insert(scheme, Box::new(Versioned(loader))).is_some()
+    }
+
+    /// Removes the loader for given scheme, returns true if there was one.
+    pub fn unregister(&mut self, scheme: &str) -> bool {
+        self.loaders.remove(&scheme.to_ascii_lowercase()).is_some()
+    }
+
+    /// Returns true if a loader is registered for given scheme.
+    pub fn has_scheme(&self, scheme: &str) -> bool {
+        self.loaders.contains_key(&scheme.to_ascii_lowercase())
+    }
+
+    /// Returns a iterator over all registered schemes.
+    pub fn schemes(&self) -> impl Iterator<Item=&str> {
+        self.loaders.keys().map(|scheme| scheme.as_str())
+    }
+
+    fn lookup(&self, source: &Source) -> Result<&dyn DynResourceLoader, ResourceLoadingError> {
+        self.loaders.get(source.iri.scheme())
+            .map(|loader| &**loader)
+            .ok_or_else(|| {
+                ResourceLoadingError
+                    ::from(ResourceLoadingErrorKind::UnknownScheme)
+                    .with_source_iri_or_else(|| Some(source.iri.clone()))
+            })
     }
 }
 
 impl ResourceLoaderComponent for Mux {
-    type BufferFuture = BufferFuture;
 
-    fn load_resource<O>( &self, source: &Source, offload: &O) -> Self::BufferFuture
-        where O: OffloaderComponent
+    fn load_resource(&self, source: &Source, ctx: &impl Context)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        match self.lookup(source) {
+            Ok(loader) => loader.dyn_load_resource(source, &DynContext::new(ctx)),
+            Err(err) => Box::new(Err(err).into_future())
+        }
+    }
+
+    fn load_transfer_encoded_resource(&self, resource: &Resource, ctx: &impl Context)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
     {
-        if let Some(handle) = self.lookup.get(source.source.scheme()) {
-            handle.load_resource(source, offload)
-        } else {
-            let err: Error = "unknow iri scheme".into();
-            Err(err).into_future().into_boxed()
+        match resource {
+            Resource::Source(source) => match self.lookup(source) {
+                Ok(loader) => loader
+                    .dyn_load_transfer_encoded_resource(resource, &DynContext::new(ctx)),
+                Err(err) => Box::new(Err(err).into_future())
+            },
+            _ => default_impl_for_load_transfer_encoded_resource(ctx, resource)
         }
     }
 }
 
+impl ResourceVersionLookup for Mux {
+
+    /// Delegates to the loader registered for the scheme of the IRI.
+    ///
+    /// Returns `None` if there is no loader for the scheme or it was not
+    /// registered with `register_versioned`.
+    fn resource_version(&self, source: &Source) -> Option<ResourceVersion> {
+        self.lookup(source).ok()?.dyn_resource_version(source)
+    }
+}
+
+/// Object safe version of `ResourceLoaderComponent` (and `ResourceVersionLookup`).
+trait DynResourceLoader: Debug + Send + Sync + 'static {
+
+    fn dyn_load_resource(&self, source: &Source, ctx: &DynContext)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>;
+
+    fn dyn_load_transfer_encoded_resource(&self, resource: &Resource, ctx: &DynContext)
+        -> SendBoxFuture<EncData, ResourceLoadingError>;
+
+    fn dyn_resource_version(&self, source: &Source) -> Option<ResourceVersion>;
+}
+
+/// A loader registered with `register`, it has no version lookup.
+#[derive(Debug)]
+struct Unversioned<R>(R);
+
+/// A loader registered with `register_versioned`.
+#[derive(Debug)]
+struct Versioned<R>(R);
+
+impl<R> DynResourceLoader for Unversioned<R>
+    where R: ResourceLoaderComponent
+{
+    fn dyn_load_resource(&self, source: &Source, ctx: &DynContext)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        self.0.load_resource(source, ctx)
+    }
+
+    fn dyn_load_transfer_encoded_resource(&self, resource: &Resource, ctx: &DynContext)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
+    {
+        self.0.load_transfer_encoded_resource(resource, ctx)
+    }
 
-struct Wrapper<I: ResourceLoaderComponent> {
-    inner: I
+    fn dyn_resource_version(&self, _source: &Source) -> Option<ResourceVersion> {
+        None
+    }
 }
 
-impl<I> ResourceLoaderComponent for Wrapper<I>
-    where I: ResourceLoaderComponent
+impl<R> DynResourceLoader for Versioned<R>
+    where R: ResourceVersionLookup
 {
-    type BufferFuture = BufferFuture;
+    fn dyn_load_resource(&self, source: &Source, ctx: &DynContext)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        self.0.load_resource(source, ctx)
+    }
 
-    fn load_resource<O>( &self, source: &Source, offload: &O) -> Self::BufferFuture
-        where O: OffloaderComponent
+    fn dyn_load_transfer_encoded_resource(&self, resource: &Resource, ctx: &DynContext)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
     {
-        let original = self.inner.load_resource(source, offload);
-        original.into_boxed()
+        self.0.load_transfer_encoded_resource(resource, ctx)
     }
-}
\ No newline at end of file
+
+    fn dyn_resource_version(&self, source: &Source) -> Option<ResourceVersion> {
+        self.0.resource_version(source)
+    }
+}
+
+/// A type erased item or error of a offloaded future.
+type AnyBox = Box<dyn Any + Send>;
+
+/// Object safe subset of `Context`.
+trait ObjectSafeContext: Debug + Send + Sync + 'static {
+
+    fn dyn_load_resource(&self, source: &Source)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>;
+
+    fn dyn_load_transfer_encoded_resource(&self, resource: &Resource)
+        -> SendBoxFuture<EncData, ResourceLoadingError>;
+
+    fn dyn_generate_message_id(&self) -> MessageId;
+
+    fn dyn_generate_content_id(&self) -> ContentId;
+
+    fn dyn_offload(&self, fut: SendBoxFuture<AnyBox, AnyBox>) -> SendBoxFuture<AnyBox, AnyBox>;
+
+    fn dyn_auto_submitted(&self) -> Option<AutoSubmitted>;
+}
+
+impl<C> ObjectSafeContext for C
+    where C: Context
+{
+    fn dyn_load_resource(&self, source: &Source)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        <Self as Context>::load_resource(self, source)
+    }
+
+    fn dyn_load_transfer_encoded_resource(&self, resource: &Resource)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
+    {
+        <Self as Context>::load_transfer_encoded_resource(self, resource)
+    }
+
+    fn dyn_generate_message_id(&self) -> MessageId {
+        <Self as Context>::generate_message_id(self)
+    }
+
+    fn dyn_generate_content_id(&self) -> ContentId {
+        <Self as Context>::generate_content_id(self)
+    }
+
+    fn dyn_offload(&self, fut: SendBoxFuture<AnyBox, AnyBox>) -> SendBoxFuture<AnyBox, AnyBox> {
+        <Self as Context>::offload(self, fut)
+    }
+
+    fn dyn_auto_submitted(&self) -> Option<AutoSubmitted> {
+        <Self as Context>::auto_submitted(self)
+    }
+}
+
+/// A type erased `Context` wrapping any other `Context` implementation.
+///
+/// This is used by the `Mux` to pass the context to the loaders it
+/// dispatches to, as `ResourceLoaderComponent` is generic over the
+/// context and as such can not be boxed directly.
+///
+/// Offloading is implemented by offloading a future with a type erased
+/// item and error, which are downcast to their original types afterwards.
+#[derive(Debug, Clone)]
+pub struct DynContext {
+    inner: Arc<dyn ObjectSafeContext>
+}
+
+impl DynContext {
+
+    /// Wraps a clone of the given context.
+    pub fn new(ctx: &impl Context) -> Self {
+        DynContext {
+            inner: Arc::new(ctx.clone())
+        }
+    }
+}
+
+impl Context for DynContext {
+
+    fn load_resource(&self, source: &Source)
+        -> SendBoxFuture<MaybeEncData, ResourceLoadingError>
+    {
+        self.inner.dyn_load_resource(source)
+    }
+
+    fn load_transfer_encoded_resource(&self, resource: &Resource)
+        -> SendBoxFuture<EncData, ResourceLoadingError>
+    {
+        self.inner.dyn_load_transfer_encoded_resource(resource)
+    }
+
+    fn generate_message_id(&self) -> MessageId {
+        self.inner.dyn_generate_message_id()
+    }
+
+    fn generate_content_id(&self) -> ContentId {
+        self.inner.dyn_generate_content_id()
+    }
+
+    fn offload<F>(&self, fut: F) -> SendBoxFuture<F::Item, F::Error>
+        where F: Future + Send + 'static,
+              F::Item: Send + 'static,
+              F::Error: Send + 'static
+    {
+        let erased = fut
+            .map(|item| Box::new(item) as AnyBox)
+            .map_err(|err| Box::new(err) as AnyBox);
+
+        let fut = self.inner
+            .dyn_offload(Box::new(erased))
+            .map(|item| *item.downcast::<F::Item>()
+                .expect("[BUG] offloaded future item changed it's type"))
+            .map_err(|err| *err.downcast::<F::Error>()
+                .expect("[BUG] offloaded future error changed it's type"));
+
+        Box::new(fut)
+    }
+
+    fn auto_submitted(&self) -> Option<AutoSubmitted> {
+        self.inner.dyn_auto_submitted()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use soft_ascii_string::SoftAsciiString;
+    use headers::header_components::{Domain, MediaType};
+
+    use crate::{
+        IRI, UseMediaType,
+        utils::Disabled,
+        context::CompositeContext,
+        default_impl::{FsResourceLoader, HashedIdGen, simple_cpu_pool}
+    };
+    use super::*;
+
+    fn ctx_with(mux: Mux) -> impl Context {
+        let domain = Domain::from_unchecked("mux.test".to_owned());
+        let unique_part = SoftAsciiString::from_unchecked("m3u4x");
+        let id_gen = HashedIdGen::new(domain, unique_part).unwrap();
+        CompositeContext::new(mux, simple_cpu_pool(), id_gen)
+    }
+
+    fn source(iri: &str) -> Source {
+        Source {
+            iri: IRI::new(iri).unwrap(),
+            use_media_type: UseMediaType::Default(MediaType::parse("text/plain").unwrap()),
+            use_file_name: None
+        }
+    }
+
+    fn fs_loader() -> FsResourceLoader<Disabled> {
+        FsResourceLoader::new(env::current_dir().unwrap().join("test_resources"))
+    }
+
+    #[test]
+    fn registering_a_scheme_twice_fails() {
+        let mut mux = Mux::new();
+        assert_ok!(mux.register("path", fs_loader()));
+        let err = assert_err!(mux.register("Path", fs_loader()));
+        assert_eq!(err.scheme, "path");
+        assert!(mux.replace("path", fs_loader()));
+        assert!(mux.unregister("PATH"));
+        assert_not!(mux.has_scheme("path"));
+    }
+
+    #[test]
+    fn dispatches_based_on_scheme() {
+        let mux = Mux::new()
+            .with_loader("path", fs_loader()).unwrap()
+            .with_loader("res", fs_loader()).unwrap();
+
+        let ctx = ctx_with(mux);
+        for iri in &["path:text.txt", "res:text.txt"] {
+            let resource = Resource::Source(source(iri));
+            let enc_data = assert_ok!(Context::load_transfer_encoded_resource(&ctx, &resource).wait());
+            assert_eq!(enc_data.file_meta().file_name, Some("text.txt".to_owned()));
+        }
+    }
+
+    #[test]
+    fn version_lookup_is_delegated() {
+        let mux = Mux::new()
+            .with_versioned_loader("path", fs_loader()).unwrap()
+            .with_loader("res", fs_loader()).unwrap();
+
+        assert!(mux.resource_version(&source("path:text.txt")).is_some());
+        assert!(mux.resource_version(&source("res:text.txt")).is_none());
+        assert!(mux.resource_version(&source("s3:text.txt")).is_none());
+    }
+
+    #[test]
+    fn replacing_keeps_the_version_lookup_only_if_requested() {
+        let mut mux = Mux::new()
+            .with_versioned_loader("path", fs_loader()).unwrap();
+
+        assert!(mux.replace_versioned("path", fs_loader()));
+        assert!(mux.resource_version(&source("path:text.txt")).is_some());
+
+        assert!(mux.replace("path", fs_loader()));
+        assert!(mux.resource_version(&source("path:text.txt")).is_none());
+
+        assert_not!(mux.replace_versioned("res", fs_loader()));
+        assert!(mux.resource_version(&source("res:text.txt")).is_some());
+    }
+
+    #[test]
+    fn unknown_scheme_fails() {
+        let ctx = ctx_with(Mux::new().with_loader("path", fs_loader()).unwrap());
+        let err = Context::load_resource(&ctx, &source("s3:bucket/text.txt")).wait()
+            .err().expect("loading unknown scheme should fail");
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::UnknownScheme);
+        assert_eq!(err.source_iri().map(|iri| iri.as_str()), Some("s3:bucket/text.txt"));
+    }
+
+    #[test]
+    fn dyn_context_offload_keeps_item_and_error() {
+        let ctx = DynContext::new(&ctx_with(Mux::new()));
+        assert_eq!(assert_ok!(ctx.offload(Ok::<_, String>(42u32).into_future()).wait()), 42);
+        let err = assert_err!(ctx.offload(Err::<u32, _>("failed".to_owned()).into_future()).wait());
+        assert_eq!(err, "failed");
+    }
+}
@@ -0,0 +1,569 @@
+//! Post-processing steps applied to freshly loaded resources.
+//!
+//! A `PostProcess` pipeline is a list of `PostProcessStep`s which
+//! are applied in order to the `Data` produced by a resource loader
+//! (e.g. the `FsResourceLoader`) before it is transfer encoded.
+use std::{
+    fmt::{self, Debug},
+    sync::Arc
+};
+
+use media_type::{TEXT, CHARSET, APPLICATION, OCTET_STREAM};
+
+use failure::Fail;
+
+use headers::header_components::MediaType;
+use internals::bind::charset::{CharsetConverter, BuiltinCharsets};
+
+use crate::{
+    error::{
+        ResourceLoadingError,
+        ResourceLoadingErrorKind
+    },
+    resource::{
+        Data,
+        Metadata
+    }
+};
+
+/// A single step of a `PostProcess` pipeline.
+///
+/// A step takes the loaded `Data` and either returns a (potentially
+/// modified) `Data` instance or fails the loading of the resource.
+pub trait PostProcessStep: Debug + Send + Sync + 'static {
+
+    /// Applies this step to the given data.
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError>;
+
+    /// The maximal size in bytes the data passed to this step may have.
+    ///
+    /// Resource loaders can use this to reject too large resources
+    /// before loading them. `process` still has to check the size
+    /// itself. Defaults to no limit.
+    fn size_limit(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A configurable pipeline of `PostProcessStep`s.
+///
+/// Steps are applied in the order they were added. Cloning a
+/// `PostProcess` is cheap as the steps are shared through an `Arc`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// use mail_core::default_impl::{
+///     FsResourceLoader, PostProcess,
+///     FixNewlines, DetectCharset, SizeLimit
+/// };
+///
+/// # fn main() {
+/// let post_process = PostProcess::new()
+///     .with_step(SizeLimit::new(10 * 1024 * 1024))
+///     .with_step(FixNewlines)
+///     .with_step(DetectCharset);
+///
+/// let loader: FsResourceLoader = FsResourceLoader::new("./templates")
+///     .with_post_process(post_process);
+/// # let _ = loader;
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PostProcess {
+    steps: Vec<Arc<dyn PostProcessStep>>
+}
+
+impl PostProcess {
+
+    /// Creates a new pipeline without any steps.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a step to the end of the pipeline.
+    pub fn push(&mut self, step: impl PostProcessStep) {
+        self.steps.push(Arc::new(step))
+    }
+
+    /// Appends a step to the end of the pipeline and returns self.
+    pub fn with_step(mut self, step: impl PostProcessStep) -> Self {
+        self.push(step);
+        self
+    }
+
+    /// Returns true if the pipeline doesn't contain any steps.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Returns the number of steps in this pipeline.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// The maximal size in bytes the unprocessed data may have.
+    ///
+    /// This is the smallest limit of the `SizeLimit` steps at the start
+    /// of the pipeline. Limits of steps after other steps are not
+    /// included as the other steps might change the size of the data.
+    pub fn size_limit(&self) -> Option<usize> {
+        self.steps.iter()
+            .map(|step| step.size_limit())
+            .take_while(Option::is_some)
+            .flatten()
+            .min()
+    }
+
+    /// Applies all steps in order to the given data.
+    ///
+    /// Stops at the first step which fails.
+    pub fn apply(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        self.steps.iter()
+            .try_fold(data, |data, step| step.process(data))
+    }
+}
+
+/// Two pipelines are equal if they share the same step instances.
+impl PartialEq for PostProcess {
+    fn eq(&self, other: &PostProcess) -> bool {
+        self.steps.len() == other.steps.len()
+            && self.steps.iter().zip(other.steps.iter())
+                .all(|(left, right)| Arc::ptr_eq(left, right))
+    }
+}
+
+/// Normalizes line endings of `text/*` data to `"\r\n"`.
+///
+/// Lone `'\r'` and lone `'\n'` are both turned into `"\r\n"`,
+/// non text data is returned unmodified.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FixNewlines;
+
+impl PostProcessStep for FixNewlines {
+
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        if data.media_type().type_() != TEXT {
+            return Ok(data);
+        }
+
+        let fixed = match fix_newlines(data.buffer()) {
+            Some(fixed) => fixed,
+            None => return Ok(data)
+        };
+
+        Ok(Data::new(fixed, data.metadata().clone()))
+    }
+}
+
+/// Returns `None` if the buffer already only contains `"\r\n"` line endings.
+pub(crate) fn fix_newlines(buffer: &[u8]) -> Option<Vec<u8>> {
+    let needs_fixing = buffer.iter().enumerate().any(|(idx, bch)| match *bch {
+        b'\r' => buffer.get(idx + 1) != Some(&b'\n'),
+        b'\n' => idx == 0 || buffer[idx - 1] != b'\r',
+        _ => false
+    });
+
+    if !needs_fixing {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(buffer.len() + buffer.len() / 32);
+    let mut iter = buffer.iter().cloned().peekable();
+    while let Some(bch) = iter.next() {
+        match bch {
+            b'\r' => {
+                if iter.peek() == Some(&b'\n') {
+                    iter.next();
+                }
+                out.extend_from_slice(b"\r\n");
+            },
+            b'\n' => out.extend_from_slice(b"\r\n"),
+            other => out.push(other)
+        }
+    }
+    Some(out)
+}
+
+/// Sets the `charset` parameter of `text/*` data if it is missing.
+///
+/// Data which is pure us-ascii gets `charset=us-ascii`, valid utf-8
+/// gets `charset=utf-8` and anything else gets `charset=unknown-8bit`
+/// (see RFC 1428). Data which already has a charset is not touched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct DetectCharset;
+
+impl PostProcessStep for DetectCharset {
+
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        {
+            let media_type = data.media_type();
+            if media_type.type_() != TEXT || media_type.get_param(CHARSET).is_some() {
+                return Ok(data);
+            }
+        }
+
+        let charset = detect_charset(data.buffer());
+        let mut meta: Metadata = (**data.metadata()).clone();
+        meta.media_type.set_param(CHARSET, charset);
+        Ok(Data::new(data.buffer().clone(), meta))
+    }
+}
+
+fn detect_charset(buffer: &[u8]) -> &'static str {
+    if buffer.is_ascii() {
+        "us-ascii"
+    } else if ::std::str::from_utf8(buffer).is_ok() {
+        "utf-8"
+    } else {
+        "unknown-8bit"
+    }
+}
+
+/// Re-encodes `text/*` data to a given charset.
+///
+/// The text is decoded based on the `charset` parameter of the media type
+/// (see `Data::decode_text_with`), encoded to the target charset and the
+/// `charset` parameter is updated. Non text data and data which already
+/// has the target charset is not changed.
+///
+/// For example `ConvertCharset::new("utf-8")` turns text loaded in a legacy
+/// charset into `utf-8` and `ConvertCharset::new("iso-8859-1")` can be used
+/// when sending mails to systems which can not handle `utf-8`.
+///
+/// By default `BuiltinCharsets` is used for the conversion, use
+/// `with_converter` to support additional charsets.
+///
+/// If the text can not be converted the loading fails with
+/// `ResourceLoadingErrorKind::LoadingFailed`.
+#[derive(Debug, Clone)]
+pub struct ConvertCharset {
+    charset: String,
+    converter: Arc<dyn CharsetConverter>
+}
+
+impl ConvertCharset {
+
+    /// Creates a new step converting text to the given charset.
+    pub fn new(charset: impl Into<String>) -> Self {
+        ConvertCharset {
+            charset: charset.into(),
+            converter: Arc::new(BuiltinCharsets)
+        }
+    }
+
+    /// Uses the given converter instead of `BuiltinCharsets`.
+    pub fn with_converter(mut self, converter: impl CharsetConverter + 'static) -> Self {
+        self.converter = Arc::new(converter);
+        self
+    }
+
+    /// The charset text is converted to.
+    pub fn charset(&self) -> &str {
+        &self.charset
+    }
+}
+
+impl PostProcessStep for ConvertCharset {
+
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        {
+            let media_type = data.media_type();
+            let has_charset = media_type.get_param(CHARSET)
+                .map(|charset| charset.to_content().eq_ignore_ascii_case(&self.charset))
+                .unwrap_or(false);
+            if media_type.type_() != TEXT || has_charset {
+                return Ok(data);
+            }
+        }
+
+        data.reencode_text(&self.charset, &*self.converter)
+            .map_err(|err| err.context(ResourceLoadingErrorKind::LoadingFailed).into())
+    }
+}
+
+/// Replaces a `application/octet-stream` media type based on the content.
+///
+/// This is a _conservative_ sniffer, it only recognizes a small number
+/// of formats based on their magic numbers and falls back to
+/// `text/plain` if the data is valid utf-8 without control characters
+/// (except whitespace). If it can't detect anything the media type
+/// is kept as it is.
+///
+/// Data which has a media type other than `application/octet-stream` is
+/// never changed, as such this step can be used to refine a media type
+/// which was set as a generic default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct SniffMediaType;
+
+impl PostProcessStep for SniffMediaType {
+
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        {
+            let media_type = data.media_type();
+            if media_type.type_() != APPLICATION || media_type.subtype() != OCTET_STREAM {
+                return Ok(data);
+            }
+        }
+
+        let media_type = match sniff_media_type(data.buffer()) {
+            Some(media_type) => media_type,
+            None => return Ok(data)
+        };
+
+        let mut meta: Metadata = (**data.metadata()).clone();
+        meta.media_type = media_type;
+        Ok(Data::new(data.buffer().clone(), meta))
+    }
+}
+
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+];
+
+fn sniff_media_type(buffer: &[u8]) -> Option<MediaType> {
+    for &(magic, media_type) in MAGIC_NUMBERS {
+        if buffer.starts_with(magic) {
+            //UNWRAP_SAFE: all media types in the table are valid
+            return Some(MediaType::parse(media_type).unwrap());
+        }
+    }
+
+    if buffer.len() >= 12 && &buffer[..4] == b"RIFF" && &buffer[8..12] == b"WEBP" {
+        return Some(MediaType::parse("image/webp").unwrap());
+    }
+
+    let text = ::std::str::from_utf8(buffer).ok()?;
+    let is_text = text.chars()
+        .all(|ch| !ch.is_control() || ch == '\r' || ch == '\n' || ch == '\t');
+
+    if is_text {
+        Some(MediaType::parse("text/plain; charset=utf-8").unwrap())
+    } else {
+        None
+    }
+}
+
+/// Fails the loading if the data is larger then the given number of bytes.
+///
+/// The error returned has the kind `ResourceLoadingErrorKind::SizeLimitExceeded`.
+///
+/// If placed at the start of the pipeline the `FsResourceLoader` already
+/// checks the size of the file before reading it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SizeLimit {
+    max_size: usize
+}
+
+impl SizeLimit {
+
+    /// Create a new size limit step allowing up to `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        SizeLimit { max_size }
+    }
+
+    /// The maximal number of bytes the data is allowed to have.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+}
+
+impl PostProcessStep for SizeLimit {
+
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        if data.buffer().len() > self.max_size {
+            Err(ResourceLoadingErrorKind::SizeLimitExceeded.into())
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn size_limit(&self) -> Option<usize> {
+        Some(self.max_size)
+    }
+}
+
+/// Wraps a function/closure so that it can be used as a `PostProcessStep`.
+///
+/// This is meant for custom post processing like e.g. down scaling
+/// images or passing the data to a virus scanner.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// use mail_core::default_impl::{PostProcess, FnStep};
+///
+/// # fn main() {
+/// let post_process = PostProcess::new()
+///     .with_step(FnStep::new("log", |data| {
+///         println!("loaded {} bytes", data.buffer().len());
+///         Ok(data)
+///     }));
+/// # let _ = post_process;
+/// # }
+/// ```
+pub struct FnStep<F> {
+    name: &'static str,
+    func: F
+}
+
+impl<F> FnStep<F>
+    where F: Fn(Data) -> Result<Data, ResourceLoadingError> + Send + Sync + 'static
+{
+    /// Creates a new step from a function.
+    ///
+    /// The `name` is only used for the `Debug` implementation.
+    pub fn new(name: &'static str, func: F) -> Self {
+        FnStep { name, func }
+    }
+}
+
+impl<F> Debug for FnStep<F> {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_struct("FnStep")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl<F> PostProcessStep for FnStep<F>
+    where F: Fn(Data) -> Result<Data, ResourceLoadingError> + Send + Sync + 'static
+{
+    fn process(&self, data: Data) -> Result<Data, ResourceLoadingError> {
+        (self.func)(data)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use headers::header_components::FileMeta;
+    use crate::context::Context;
+    use super::super::test_context;
+    use super::*;
+
+    fn data(buffer: &[u8], media_type: &str) -> Data {
+        Data::new(buffer.to_owned(), Metadata {
+            file_meta: FileMeta::default(),
+            media_type: MediaType::parse(media_type).unwrap(),
+            content_id: test_context().generate_content_id()
+        })
+    }
+
+    #[test]
+    fn fix_newlines_normalizes_text() {
+        let input = data(b"a\nb\rc\r\nd", "text/plain");
+        let output = assert_ok!(FixNewlines.process(input));
+        assert_eq!(&**output.buffer(), b"a\r\nb\r\nc\r\nd");
+    }
+
+    #[test]
+    fn fix_newlines_ignores_non_text() {
+        let input = data(b"a\nb", "application/octet-stream");
+        let output = assert_ok!(FixNewlines.process(input));
+        assert_eq!(&**output.buffer(), b"a\nb");
+    }
+
+    #[test]
+    fn detect_charset() {
+        let output = assert_ok!(DetectCharset.process(data(b"abc", "text/plain")));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=us-ascii");
+
+        let output = assert_ok!(DetectCharset.process(data("ä".as_bytes(), "text/plain")));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=utf-8");
+
+        let output = assert_ok!(DetectCharset.process(data(b"\xe4", "text/plain")));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=unknown-8bit");
+
+        let output = assert_ok!(DetectCharset.process(data(b"\xe4", "text/plain; charset=latin1")));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=latin1");
+    }
+
+    #[test]
+    fn convert_charset() {
+        let to_latin1 = ConvertCharset::new("iso-8859-1");
+        let output = assert_ok!(to_latin1.process(data("Café".as_bytes(), "text/plain; charset=utf-8")));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=iso-8859-1");
+        assert_eq!(&**output.buffer(), b"Caf\xe9");
+
+        let to_utf8 = ConvertCharset::new("utf-8");
+        let output = assert_ok!(to_utf8.process(output));
+        assert_eq!(output.media_type().as_str_repr(), "text/plain; charset=utf-8");
+        assert_eq!(assert_ok!(output.decode_text()), "Café");
+
+        let output = assert_ok!(to_latin1.process(data(b"\xe9", "image/png")));
+        assert_eq!(&**output.buffer(), b"\xe9");
+
+        let err = assert_err!(to_latin1.process(data("€".as_bytes(), "text/plain; charset=utf-8")));
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::LoadingFailed);
+    }
+
+    #[test]
+    fn sniff_media_type_from_magic_numbers() {
+        let input = data(b"\x89PNG\r\n\x1a\n\0\0", "application/octet-stream");
+        let output = assert_ok!(SniffMediaType.process(input));
+        assert_eq!(output.media_type().as_str_repr(), "image/png");
+
+        let input = data(b"\x89PNG\r\n\x1a\n\0\0", "image/x-custom");
+        let output = assert_ok!(SniffMediaType.process(input));
+        assert_eq!(output.media_type().as_str_repr(), "image/x-custom");
+
+        let input = data(b"\0\x01\x02", "application/octet-stream");
+        let output = assert_ok!(SniffMediaType.process(input));
+        assert_eq!(output.media_type().as_str_repr(), "application/octet-stream");
+    }
+
+    #[test]
+    fn size_limit() {
+        assert_ok!(SizeLimit::new(3).process(data(b"abc", "text/plain")));
+        let err = assert_err!(SizeLimit::new(2).process(data(b"abc", "text/plain")));
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::SizeLimitExceeded);
+    }
+
+    #[test]
+    fn steps_are_applied_in_order() {
+        let post_process = PostProcess::new()
+            .with_step(FixNewlines)
+            .with_step(SizeLimit::new(3));
+
+        assert_ok!(post_process.apply(data(b"abc", "text/plain")));
+        let err = assert_err!(post_process.apply(data(b"a\nb", "text/plain")));
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::SizeLimitExceeded);
+    }
+
+    #[test]
+    fn size_limit_of_leading_steps() {
+        assert_eq!(PostProcess::new().size_limit(), None);
+
+        let post_process = PostProcess::new()
+            .with_step(SizeLimit::new(10))
+            .with_step(SizeLimit::new(5))
+            .with_step(FixNewlines)
+            .with_step(SizeLimit::new(2));
+        assert_eq!(post_process.size_limit(), Some(5));
+
+        let post_process = PostProcess::new()
+            .with_step(FixNewlines)
+            .with_step(SizeLimit::new(2));
+        assert_eq!(post_process.size_limit(), None);
+    }
+
+    #[test]
+    fn fn_step_is_called() {
+        let post_process = PostProcess::new()
+            .with_step(FnStep::new("reject", |_data| {
+                Err(ResourceLoadingErrorKind::LoadingFailed.into())
+            }));
+
+        let err = assert_err!(post_process.apply(data(b"abc", "text/plain")));
+        assert_eq!(err.kind(), ResourceLoadingErrorKind::LoadingFailed);
+    }
+}
@@ -0,0 +1,740 @@
+//! This module provides a builder and a parser for delivery status notifications (rfc3464).
+//!
+//! Delivery status notifications (DSN), also known as bounces, are send by
+//! mail transfer agents to notify the sender of a mail about the (non-)delivery
+//! of the mail to some or all of it's recipients.
+//!
+//! A DSN is a `multipart/report; report-type=delivery-status` mail containing:
+//!
+//! 1. a human readable `text/plain` body
+//! 2. a machine readable `message/delivery-status` body
+//! 3. optionally the original mail (`message/rfc822`) or
+//!    just it's headers (`text/rfc822-headers`)
+//!
+//! `Dsn::create_mail` creates such a mail, `Dsn::parse` parses it from
+//! a received raw mail. Note that DSNs should be send with a empty
+//! (`<>`) smtp reverse path so that they can not cause further DSNs.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    borrow::Cow
+};
+
+use media_type::MULTIPART;
+use vec1::Vec1;
+
+use headers::{
+    HeaderTryFrom,
+    header_components::{MediaType, MessageId, TypedAddress}
+};
+
+use crate::{
+    context::Context,
+    error::DsnParseError,
+    mail::{Mail, new_unencoded_body},
+    mdn::push_field,
+    resource::Resource,
+    raw_mail::{
+        field, parse_field_groups, parse_fields,
+        split_header_section, split_multipart, transfer_decode
+    }
+};
+
+/// The name of a mail transfer agent prefixed with it's name type, e.g. `dns; mx.example.com`.
+///
+/// It uses the same syntax as `TypedAddress`.
+pub type MtaName = TypedAddress;
+
+/// A diagnostic code prefixed with it's type, e.g. `smtp; 550 5.1.1 user unknown`.
+///
+/// It uses the same syntax as `TypedAddress`.
+pub type DiagnosticCode = TypedAddress;
+
+/// The action a mail transfer agent performed for a recipient.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum DsnAction {
+    /// The mail could not be delivered to the recipient.
+    Failed,
+    /// The mail could not be delivered yet, but delivery will be retried.
+    Delayed,
+    /// The mail was successfully delivered to the recipient.
+    Delivered,
+    /// The mail was relayed to a system which does not send DSNs.
+    Relayed,
+    /// The mail was delivered and forwarded to multiple additional recipients.
+    Expanded
+}
+
+impl DsnAction {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            DsnAction::Failed => "failed",
+            DsnAction::Delayed => "delayed",
+            DsnAction::Delivered => "delivered",
+            DsnAction::Relayed => "relayed",
+            DsnAction::Expanded => "expanded"
+        }
+    }
+}
+
+impl FromStr for DsnAction {
+    type Err = DsnParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        use self::DsnAction::*;
+        let trimmed = value.trim();
+        for &action in &[Failed, Delayed, Delivered, Relayed, Expanded] {
+            if trimmed.eq_ignore_ascii_case(action.as_str()) {
+                return Ok(action);
+            }
+        }
+        Err(DsnParseError::MalformedField { name: "Action", value: value.to_owned() })
+    }
+}
+
+/// A enhanced mail system status code (rfc3463), e.g. `5.1.1`.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct StatusCode {
+    class: u8,
+    subject: u16,
+    detail: u16
+}
+
+impl StatusCode {
+
+    /// Creates a new status code.
+    ///
+    /// Returns `None` if the class is not 2 (success), 4 (transient
+    /// failure) or 5 (permanent failure) or subject or detail are
+    /// larger then 999.
+    pub fn new(class: u8, subject: u16, detail: u16) -> Option<Self> {
+        let valid = (class == 2 || class == 4 || class == 5)
+            && subject <= 999 && detail <= 999;
+
+        if valid {
+            Some(StatusCode { class, subject, detail })
+        } else {
+            None
+        }
+    }
+
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    pub fn subject(&self) -> u16 {
+        self.subject
+    }
+
+    pub fn detail(&self) -> u16 {
+        self.detail
+    }
+
+    /// True if the status code indicates a successful delivery (`2.X.X`).
+    pub fn is_success(&self) -> bool {
+        self.class == 2
+    }
+
+    /// True if the status code indicates a transient failure (`4.X.X`).
+    pub fn is_transient_failure(&self) -> bool {
+        self.class == 4
+    }
+
+    /// True if the status code indicates a permanent failure (`5.X.X`).
+    pub fn is_permanent_failure(&self) -> bool {
+        self.class == 5
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}.{}.{}", self.class, self.subject, self.detail)
+    }
+}
+
+impl FromStr for StatusCode {
+    type Err = DsnParseError;
+
+    /// Parses a status code, ignoring any trailing comment like in `5.1.1 (user unknown)`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let malformed = || DsnParseError::MalformedField { name: "Status", value: value.to_owned() };
+
+        let code = value.split_whitespace().next().ok_or_else(malformed)?;
+        let mut parts = code.split('.');
+        let mut next_number = || -> Option<u16> {
+            let part = parts.next()?;
+            if part.is_empty() || part.len() > 3 {
+                return None;
+            }
+            part.parse().ok()
+        };
+
+        let class = next_number().ok_or_else(malformed)?;
+        let subject = next_number().ok_or_else(malformed)?;
+        let detail = next_number().ok_or_else(malformed)?;
+        if parts.next().is_some() || class > 9 {
+            return Err(malformed());
+        }
+
+        StatusCode::new(class as u8, subject, detail).ok_or_else(malformed)
+    }
+}
+
+/// The per-recipient fields of a delivery status notification.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct RecipientStatus {
+    /// The recipient as originally specified by the sender, if known.
+    pub original_recipient: Option<TypedAddress>,
+
+    /// The recipient the reporting mail transfer agent tried to deliver to.
+    pub final_recipient: TypedAddress,
+
+    /// What the reporting mail transfer agent did.
+    pub action: DsnAction,
+
+    /// The status code describing the delivery status.
+    pub status: StatusCode,
+
+    /// The mail transfer agent which reported the delivery status, if any.
+    pub remote_mta: Option<MtaName>,
+
+    /// The actual diagnostic code reported by the remote mail transfer agent, if any.
+    pub diagnostic_code: Option<DiagnosticCode>
+}
+
+impl RecipientStatus {
+
+    /// Creates a new instance without any optional fields.
+    pub fn new(final_recipient: TypedAddress, action: DsnAction, status: StatusCode) -> Self {
+        RecipientStatus {
+            original_recipient: None,
+            final_recipient,
+            action,
+            status,
+            remote_mta: None,
+            diagnostic_code: None
+        }
+    }
+
+    /// Sets the `Original-Recipient` field.
+    pub fn with_original_recipient(mut self, recipient: TypedAddress) -> Self {
+        self.original_recipient = Some(recipient);
+        self
+    }
+
+    /// Sets the `Remote-MTA` field.
+    pub fn with_remote_mta(mut self, remote_mta: MtaName) -> Self {
+        self.remote_mta = Some(remote_mta);
+        self
+    }
+
+    /// Sets the `Diagnostic-Code` field.
+    pub fn with_diagnostic_code(mut self, diagnostic_code: DiagnosticCode) -> Self {
+        self.diagnostic_code = Some(diagnostic_code);
+        self
+    }
+}
+
+/// The content of the machine readable `message/delivery-status` part of a DSN.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct DeliveryStatus {
+    /// The envelope id given by the sender with the smtp `ENVID` parameter, if any.
+    pub original_envelope_id: Option<String>,
+
+    /// The mail transfer agent which created the DSN.
+    pub reporting_mta: MtaName,
+
+    /// The delivery status for each recipient the DSN is about.
+    pub recipients: Vec1<RecipientStatus>
+}
+
+impl DeliveryStatus {
+
+    /// Creates a new delivery status.
+    pub fn new(reporting_mta: MtaName, recipients: Vec1<RecipientStatus>) -> Self {
+        DeliveryStatus {
+            original_envelope_id: None,
+            reporting_mta,
+            recipients
+        }
+    }
+
+    /// Parses the content of a `message/delivery-status` body.
+    ///
+    /// Unknown fields (including extension fields) are ignored.
+    pub fn parse(text: &str) -> Result<Self, DsnParseError> {
+        let mut groups = parse_field_groups(text).into_iter();
+
+        let per_message = groups.next().unwrap_or_default();
+        let reporting_mta = typed_field(&per_message, "Reporting-MTA")?
+            .ok_or(DsnParseError::MissingField("Reporting-MTA"))?;
+        let original_envelope_id = field(&per_message, "Original-Envelope-Id")
+            .map(|value| value.trim().to_owned());
+
+        let mut recipients = Vec::new();
+        for group in groups {
+            let final_recipient = typed_field(&group, "Final-Recipient")?
+                .ok_or(DsnParseError::MissingField("Final-Recipient"))?;
+            let action = field(&group, "Action")
+                .ok_or(DsnParseError::MissingField("Action"))?
+                .parse()?;
+            let status = field(&group, "Status")
+                .ok_or(DsnParseError::MissingField("Status"))?
+                .parse()?;
+
+            recipients.push(RecipientStatus {
+                original_recipient: typed_field(&group, "Original-Recipient")?,
+                final_recipient,
+                action,
+                status,
+                remote_mta: typed_field(&group, "Remote-MTA")?,
+                diagnostic_code: typed_field(&group, "Diagnostic-Code")?
+            });
+        }
+
+        let recipients = Vec1::try_from_vec(recipients)
+            .map_err(|_| DsnParseError::NoRecipients)?;
+
+        Ok(DeliveryStatus { original_envelope_id, reporting_mta, recipients })
+    }
+}
+
+impl Display for DeliveryStatus {
+
+    /// Formats the delivery status as used in the `message/delivery-status` body.
+    ///
+    /// Control characters (e.g. line breaks) in the original envelope id are
+    /// replaced by spaces so that they can not be used to inject fields.
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        if let Some(ref envelope_id) = self.original_envelope_id {
+            let envelope_id = envelope_id.chars()
+                .map(|ch| if ch.is_control() { ' ' } else { ch })
+                .collect::<String>();
+            push_field(&mut out, "Original-Envelope-Id", &envelope_id);
+        }
+        push_field(&mut out, "Reporting-MTA", &self.reporting_mta.to_string());
+
+        for recipient in self.recipients.iter() {
+            out.push_str("\r\n");
+            if let Some(ref original_recipient) = recipient.original_recipient {
+                push_field(&mut out, "Original-Recipient", &original_recipient.to_string());
+            }
+            push_field(&mut out, "Final-Recipient", &recipient.final_recipient.to_string());
+            push_field(&mut out, "Action", recipient.action.as_str());
+            push_field(&mut out, "Status", &recipient.status.to_string());
+            if let Some(ref remote_mta) = recipient.remote_mta {
+                push_field(&mut out, "Remote-MTA", &remote_mta.to_string());
+            }
+            if let Some(ref diagnostic_code) = recipient.diagnostic_code {
+                push_field(&mut out, "Diagnostic-Code", &diagnostic_code.to_string());
+            }
+        }
+        fter.write_str(&out)
+    }
+}
+
+/// The original mail (or parts of it) a DSN is about.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum OriginalMessage {
+    /// The complete raw original mail, included as `message/rfc822`.
+    ///
+    /// It's included as is (using the `7bit` or `8bit` transfer encoding)
+    /// as `message/rfc822` bodies must not be base64 encoded (rfc2046).
+    Full(Vec<u8>),
+    /// The raw headers of the original mail, included as `text/rfc822-headers`.
+    Headers(String)
+}
+
+impl OriginalMessage {
+
+    /// Returns the raw header section of the original mail.
+    pub fn headers(&self) -> Cow<'_, str> {
+        match *self {
+            OriginalMessage::Full(ref raw) => String::from_utf8_lossy(split_header_section(raw).0),
+            OriginalMessage::Headers(ref headers) => Cow::Borrowed(headers)
+        }
+    }
+
+    /// Returns the `Message-Id` of the original mail, if it has a (valid) one.
+    pub fn message_id(&self) -> Option<MessageId> {
+        let headers = self.headers();
+        let fields = parse_fields(&headers);
+        let value = field(&fields, "Message-Id")?.trim();
+        let value = value.trim_start_matches('<').trim_end_matches('>');
+        MessageId::try_from(value).ok()
+    }
+}
+
+/// A delivery status notification (rfc3464).
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// # extern crate mail_headers;
+/// # extern crate vec1;
+/// # use vec1::Vec1;
+/// # use mail_headers::{HeaderTryFrom, header_components::{Domain, TypedAddress}};
+/// # use mail_core::default_impl::simple_context;
+/// use mail_core::dsn::{Dsn, DeliveryStatus, RecipientStatus, DsnAction, OriginalMessage};
+/// # fn main() {
+/// # let domain = Domain::from_unchecked("example.com".to_owned());
+/// # let ctx = simple_context::new(domain, "xqi93".parse().unwrap()).unwrap();
+/// # let original_headers = "Message-Id: <ab1c@sender.example>\r\nSubject: Hy\r\n".to_owned();
+/// let recipient = RecipientStatus::new(
+///     TypedAddress::try_from("rfc822;unknown@example.com").unwrap(),
+///     DsnAction::Failed,
+///     "5.1.1".parse().unwrap()
+/// ).with_diagnostic_code(TypedAddress::try_from("smtp; 550 5.1.1 user unknown").unwrap());
+///
+/// let status = DeliveryStatus::new(
+///     TypedAddress::try_from("dns; mx.example.com").unwrap(),
+///     Vec1::new(recipient)
+/// );
+///
+/// let dsn = Dsn::new(status)
+///     .with_original(OriginalMessage::Headers(original_headers));
+///
+/// let mail = dsn.create_mail("Your mail could not be delivered.", &ctx);
+/// // set From/To/Subject and send it with an empty smtp reverse path
+/// # let _ = mail;
+/// # }
+/// ```
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Dsn {
+    /// The machine readable delivery status.
+    pub status: DeliveryStatus,
+
+    /// The original mail or it's headers.
+    pub original: Option<OriginalMessage>
+}
+
+impl Dsn {
+
+    /// Creates a new DSN with given delivery status.
+    pub fn new(status: DeliveryStatus) -> Self {
+        Dsn { status, original: None }
+    }
+
+    /// Includes the original mail (or it's headers) in the DSN.
+    pub fn with_original(mut self, original: OriginalMessage) -> Self {
+        self.original = Some(original);
+        self
+    }
+
+    /// Returns the `Message-Id` of the original mail, if known.
+    ///
+    /// This is only available if the DSN includes the original
+    /// mail or it's headers.
+    pub fn original_message_id(&self) -> Option<MessageId> {
+        self.original.as_ref()?.message_id()
+    }
+
+    /// Creates the DSN mail using `Mail::new_multipart_mail`.
+    ///
+    /// The mail only contains the body and `Content-Type` of the DSN,
+    /// headers like `From`, `To` and `Subject` still have to be set.
+    ///
+    /// If the delivery status is not ascii (e.g. because of a
+    /// internationalized recipient) `message/global-delivery-status`
+    /// (rfc6533) is used instead of `message/delivery-status`.
+    pub fn create_mail(&self, human_readable: impl Into<String>, ctx: &impl Context) -> Mail {
+        let status = self.status.to_string();
+        let status_type =
+            if status.is_ascii() {
+                "message/delivery-status"
+            } else {
+                "message/global-delivery-status"
+            };
+
+        let mut bodies = vec![
+            Mail::new_singlepart_mail(Resource::plain_text(human_readable, ctx)),
+            new_unencoded_body(status.into_bytes(), status_type, ctx)
+        ];
+
+        match self.original {
+            Some(OriginalMessage::Full(ref raw)) =>
+                bodies.push(new_unencoded_body(raw.clone(), "message/rfc822", ctx)),
+            Some(OriginalMessage::Headers(ref headers)) =>
+                bodies.push(new_unencoded_body(headers.clone().into_bytes(), "text/rfc822-headers", ctx)),
+            None => {}
+        }
+
+        //UNWRAP_SAFE: it's a valid media type
+        let content_type = MediaType::new_with_params(
+            MULTIPART, "report", vec![("report-type", "delivery-status")]
+        ).unwrap();
+
+        Mail::new_multipart_mail(content_type, bodies)
+    }
+
+    /// Parses a DSN from a raw (received) mail.
+    ///
+    /// The mail has to be a `multipart/report` mail containing a
+    /// `message/delivery-status` part. If it contains a `message/rfc822`
+    /// or `text/rfc822-headers` part it's used as original mail.
+    /// Other parts, like the human readable one, are ignored.
+    ///
+    /// Only the top level multipart body is searched, DSNs nested into
+    /// other multipart bodies are not supported.
+    pub fn parse(raw_mail: &[u8]) -> Result<Self, DsnParseError> {
+        let (header_section, body) = split_header_section(raw_mail);
+        let fields = parse_fields(&String::from_utf8_lossy(header_section));
+
+        let content_type = field(&fields, "Content-Type")
+            .and_then(|value| MediaType::parse(value.trim()).ok())
+            .ok_or(DsnParseError::NotAReport)?;
+
+        if !content_type.full_type().eq_ignore_ascii_case("multipart/report") {
+            return Err(DsnParseError::NotAReport);
+        }
+
+        let boundary = content_type.get_param("boundary")
+            .ok_or(DsnParseError::NotAReport)?
+            .to_content();
+
+        let mut status = None;
+        let mut original = None;
+        for part in split_multipart(body, &boundary) {
+            let (part_header_section, part_body) = split_header_section(part);
+            let part_fields = parse_fields(&String::from_utf8_lossy(part_header_section));
+            let part_type = field(&part_fields, "Content-Type")
+                .and_then(|value| MediaType::parse(value.trim()).ok())
+                .map(|media_type| media_type.full_type().to_ascii_lowercase())
+                .unwrap_or_else(|| "text/plain".to_owned());
+
+            let is_status = part_type == "message/delivery-status"
+                || part_type == "message/global-delivery-status";
+            let is_full = part_type == "message/rfc822" || part_type == "message/global";
+            let is_headers = part_type == "text/rfc822-headers"
+                || part_type == "message/global-headers";
+
+            if !(is_status || is_full || is_headers) {
+                continue;
+            }
+
+            let decoded = transfer_decode(&part_fields, part_body)
+                .map_err(|_| DsnParseError::MalformedTransferEncoding(part_type.clone()))?;
+
+            if is_status {
+                if status.is_none() {
+                    status = Some(DeliveryStatus::parse(&String::from_utf8_lossy(&decoded))?);
+                }
+            } else if original.is_none() {
+                original = Some(if is_full {
+                    OriginalMessage::Full(decoded)
+                } else {
+                    OriginalMessage::Headers(String::from_utf8_lossy(&decoded).into_owned())
+                });
+            }
+        }
+
+        let status = status.ok_or(DsnParseError::NoDeliveryStatus)?;
+        Ok(Dsn { status, original })
+    }
+}
+
+/// Returns the (first) value of the field with given name parsed as `TypedAddress`.
+fn typed_field(fields: &[(String, String)], name: &'static str)
+    -> Result<Option<TypedAddress>, DsnParseError>
+{
+    match field(fields, name) {
+        Some(value) => TypedAddress::try_from(value)
+            .map(Some)
+            .map_err(|_| DsnParseError::MalformedField { name, value: value.to_owned() }),
+        None => Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use vec1::Vec1;
+
+    use internals::MailType;
+    use headers::{
+        HeaderTryFrom,
+        headers::{_From, _To, Subject},
+        header_components::TypedAddress
+    };
+
+    use default_impl::test_context;
+    use super::*;
+
+    fn delivery_status() -> DeliveryStatus {
+        let failed = RecipientStatus::new(
+            TypedAddress::try_from("rfc822;unknown@recipient.test").unwrap(),
+            DsnAction::Failed,
+            StatusCode::new(5, 1, 1).unwrap()
+        )
+            .with_original_recipient(TypedAddress::try_from("rfc822;alias@recipient.test").unwrap())
+            .with_remote_mta(TypedAddress::try_from("dns; mx.recipient.test").unwrap())
+            .with_diagnostic_code(TypedAddress::try_from("smtp; 550 5.1.1 user unknown").unwrap());
+
+        let delayed = RecipientStatus::new(
+            TypedAddress::try_from("rfc822;busy@recipient.test").unwrap(),
+            DsnAction::Delayed,
+            StatusCode::new(4, 2, 0).unwrap()
+        );
+
+        let mut status = DeliveryStatus::new(
+            TypedAddress::try_from("dns; relay.test").unwrap(),
+            Vec1::try_from_vec(vec![failed, delayed]).unwrap()
+        );
+        status.original_envelope_id = Some("env\r\n42".to_owned());
+        status
+    }
+
+    #[test]
+    fn parse_status_code() {
+        let code: StatusCode = "5.1.1 (user unknown)".parse().unwrap();
+        assert_eq!(code, StatusCode::new(5, 1, 1).unwrap());
+        assert!(code.is_permanent_failure());
+        assert_eq!(code.to_string(), "5.1.1");
+
+        assert!("4.7.100".parse::<StatusCode>().unwrap().is_transient_failure());
+        assert!("3.1.1".parse::<StatusCode>().is_err());
+        assert!("5.1".parse::<StatusCode>().is_err());
+        assert!("5.1.1.1".parse::<StatusCode>().is_err());
+        assert!("5.1000.1".parse::<StatusCode>().is_err());
+    }
+
+    #[test]
+    fn format_delivery_status() {
+        assert_eq!(delivery_status().to_string(), concat!(
+            "Original-Envelope-Id: env  42\r\n",
+            "Reporting-MTA: dns;relay.test\r\n",
+            "\r\n",
+            "Original-Recipient: rfc822;alias@recipient.test\r\n",
+            "Final-Recipient: rfc822;unknown@recipient.test\r\n",
+            "Action: failed\r\n",
+            "Status: 5.1.1\r\n",
+            "Remote-MTA: dns;mx.recipient.test\r\n",
+            "Diagnostic-Code: smtp;550 5.1.1 user unknown\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822;busy@recipient.test\r\n",
+            "Action: delayed\r\n",
+            "Status: 4.2.0\r\n"
+        ));
+    }
+
+    #[test]
+    fn parse_delivery_status() {
+        let status = DeliveryStatus::parse(concat!(
+            "reporting-mta: dns;\r\n",
+            " relay.test\r\n",
+            "X-Postfix-Queue-ID: 12ab\r\n",
+            "\r\n",
+            "Final-Recipient: RFC822; unknown@recipient.test\r\n",
+            "Action: Failed\r\n",
+            "Status: 5.1.1 (user unknown)\r\n"
+        )).unwrap();
+
+        assert_eq!(status.reporting_mta.address(), "relay.test");
+        assert_eq!(status.recipients.len(), 1);
+        let recipient = status.recipients.first();
+        assert!(recipient.final_recipient.is_rfc822());
+        assert_eq!(recipient.action, DsnAction::Failed);
+        assert_eq!(recipient.status, StatusCode::new(5, 1, 1).unwrap());
+        assert_eq!(recipient.diagnostic_code, None);
+    }
+
+    #[test]
+    fn parse_delivery_status_errors() {
+        assert_eq!(
+            DeliveryStatus::parse("Reporting-MTA: dns; relay.test\r\n"),
+            Err(DsnParseError::NoRecipients)
+        );
+        assert_eq!(
+            DeliveryStatus::parse("Reporting-MTA: dns; relay.test\r\n\r\nAction: failed\r\n"),
+            Err(DsnParseError::MissingField("Final-Recipient"))
+        );
+        assert_eq!(
+            DeliveryStatus::parse(concat!(
+                "Reporting-MTA: dns; relay.test\r\n\r\n",
+                "Final-Recipient: rfc822;a@b.test\r\nAction: bounced\r\nStatus: 5.0.0\r\n"
+            )),
+            Err(DsnParseError::MalformedField { name: "Action", value: "bounced".to_owned() })
+        );
+    }
+
+    fn encode(dsn: &Dsn) -> Vec<u8> {
+        let ctx = test_context();
+        let mut mail = dsn.create_mail("Your mail could not be delivered.", &ctx);
+        mail.insert_headers(headers! {
+            _From: ["mailer-daemon@relay.test"],
+            _To: ["sender@sender.test"],
+            Subject: "Undelivered Mail Returned to Sender"
+        }.unwrap());
+
+        let encodable = mail.into_encodable_mail(ctx).wait().unwrap();
+        encodable.encode_into_bytes(MailType::Ascii).unwrap()
+    }
+
+    #[test]
+    fn create_and_parse_with_headers() {
+        let dsn = Dsn::new(delivery_status())
+            .with_original(OriginalMessage::Headers(
+                "Message-Id: <ab1c@sender.test>\r\nSubject: Hy\r\n".to_owned()));
+
+        let raw = encode(&dsn);
+        let raw_str = String::from_utf8(raw.clone()).unwrap();
+        assert!(raw_str.contains(concat!(
+            "Content-Transfer-Encoding: 7bit\r\n",
+            "Content-Type: text/rfc822-headers\r\n",
+            "\r\n",
+            "Message-Id: <ab1c@sender.test>\r\nSubject: Hy\r\n"
+        )));
+
+        let parsed = Dsn::parse(&raw).unwrap();
+
+        let mut expected_status = delivery_status();
+        expected_status.original_envelope_id = Some("env  42".to_owned());
+        assert_eq!(parsed.status, expected_status);
+        assert_eq!(parsed.original_message_id().unwrap().as_str(), "ab1c@sender.test");
+    }
+
+    #[test]
+    fn create_and_parse_with_full_mail() {
+        let original = b"Message-Id:\r\n <ab1c@sender.test>\r\nSubject: Hy\r\n\r\nbody\r\n".to_vec();
+        let dsn = Dsn::new(delivery_status())
+            .with_original(OriginalMessage::Full(original.clone()));
+
+        let raw = encode(&dsn);
+        let raw_str = String::from_utf8(raw.clone()).unwrap();
+        assert!(raw_str.contains(concat!(
+            "Content-Transfer-Encoding: 7bit\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Message-Id:\r\n <ab1c@sender.test>\r\nSubject: Hy\r\n\r\nbody\r\n"
+        )));
+        assert!(raw_str.contains(concat!(
+            "Content-Transfer-Encoding: 7bit\r\n",
+            "Content-Type: message/delivery-status\r\n"
+        )));
+
+        let parsed = Dsn::parse(&raw).unwrap();
+        assert_eq!(parsed.original, Some(OriginalMessage::Full(original)));
+        assert_eq!(parsed.original_message_id().unwrap().as_str(), "ab1c@sender.test");
+    }
+
+    #[test]
+    fn parse_non_report_mail() {
+        let raw = b"Content-Type: text/plain\r\n\r\nhy\r\n";
+        assert_eq!(Dsn::parse(raw), Err(DsnParseError::NotAReport));
+
+        let raw = concat!(
+            "Content-Type: multipart/report; report-type=delivery-status;\r\n",
+            " boundary=\"=_abc\"\r\n",
+            "\r\n",
+            "--=_abc\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "hy\r\n",
+            "--=_abc--\r\n"
+        );
+        assert_eq!(Dsn::parse(raw.as_bytes()), Err(DsnParseError::NoDeliveryStatus));
+    }
+}
@@ -6,6 +6,7 @@ use soft_ascii_string::{
 use media_type::BOUNDARY;
 
 use internals::{
+    MailType,
     encoder::{
         EncodingBuffer, EncodingWriter,
     },
@@ -23,7 +24,8 @@ use ::{
     mail::{
         Mail,
         EncodableMail,
-        assume_encoded
+        assume_encoded,
+        is_multipart_signed
     }
 };
 
@@ -66,6 +68,24 @@ fn _encode_mail(
     Ok(())
 }
 
+/// Encodes a (non top-level) mail body on it's own in canonical form.
+///
+/// The canonical form uses CRLF line endings and is always
+/// 7bit, i.e. it's encoded with `MailType::Ascii`. This is the
+/// form which is signed/encrypted, e.g. for PGP/MIME.
+///
+/// The returned buffer ends with a CRLF, which for signing has
+/// to be stripped as it is part of the following boundary.
+///
+/// # Panics
+/// if the body is not yet resolved, i.e. the resources are not
+/// yet loaded and transfer encoded.
+pub(crate) fn encode_canonical_mail_body(mail: &Mail) -> Result<Vec<u8>, MailError> {
+    let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    _encode_mail(mail, false, &mut buffer)?;
+    Ok(buffer.into())
+}
+
 ///
 /// # Panics
 /// if the body is not yet resolved use `Body::poll_body` or `IntoFuture`
@@ -179,13 +199,26 @@ fn encode_mail_part(mail: &Mail, encoder:  &mut EncodingBuffer )
                     .with_str_context(orig_string.into_source())
                 )?;
 
+            let is_signed = mail.headers()
+                .get_single(ContentType)
+                .and_then(|res| res.ok())
+                .map(|content_type| is_multipart_signed(content_type))
+                .unwrap_or(false);
+
             for mail in bodies.iter() {
                 encoder.write_header_line(|handle| {
                     handle.write_char(minus)?;
                     handle.write_char(minus)?;
                     handle.write_str(&*boundary)
                 })?;
-                _encode_mail(mail, false, encoder)?;
+                if is_signed {
+                    // the signed body has to be encoded exactly the same way
+                    // as it was encoded when signing it
+                    let canonical = encode_canonical_mail_body(mail)?;
+                    encoder.write_body_unchecked(&canonical);
+                } else {
+                    _encode_mail(mail, false, encoder)?;
+                }
             }
 
             if bodies.len() > 0 {
@@ -58,7 +58,25 @@ pub enum ResourceLoadingErrorKind {
     LoadingFailed,
 
     #[fail(display = "automatically detecting the media type failed")]
-    MediaTypeDetectionFailed
+    MediaTypeDetectionFailed,
+
+    /// The resource is larger then the configured size limit.
+    #[fail(display = "resource exceeds the size limit")]
+    SizeLimitExceeded,
+
+    /// No resource loader is available for the scheme of the IRI.
+    #[fail(display = "unknown iri scheme")]
+    UnknownScheme,
+
+    /// The IRI is malformed wrt. the scheme specific syntax (e.g. a broken `data:` IRI).
+    #[fail(display = "malformed iri")]
+    InvalidIRI,
+
+    /// The resource would be loaded from outside of the sandbox of the loader.
+    ///
+    /// E.g. a confined `FsResourceLoader` rejects paths escaping its root.
+    #[fail(display = "resource path violates the sandbox")]
+    SandboxViolation
 }
 
 /// The loading of an Resource failed.
@@ -227,7 +245,11 @@ pub enum MailError {
     /// E.g. the file to attach or the image to embedded could not
     /// be found.
     #[fail(display = "{}", _0)]
-    ResourceLoading(ResourceLoadingError)
+    ResourceLoading(ResourceLoadingError),
+
+    /// Signing or encrypting (a part of) the mail failed.
+    #[fail(display = "{}", _0)]
+    Crypto(CryptoError)
 }
 
 impl From<BuildInValidationError> for MailError {
@@ -276,6 +298,12 @@ impl From<ComponentCreationError> for MailError {
     }
 }
 
+impl From<CryptoError> for MailError {
+    fn from(err: CryptoError) -> Self {
+        MailError::Crypto(err)
+    }
+}
+
 
 /// Error returned when trying to _unload_ and `Resource` and it fails.
 #[derive(Copy, Clone, Debug, Fail)]
@@ -290,4 +318,113 @@ pub enum ResourceNotUnloadableError {
     /// the deletion/dropping of `Resource` instances.
     #[fail(display = "resource has no source, can't unload it")]
     NoSource
-}
\ No newline at end of file
+}
+
+/// Error returned when parsing a delivery status notification fails.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum DsnParseError {
+    /// The mail is not a `multipart/report` mail.
+    #[fail(display = "mail is not a multipart/report mail")]
+    NotAReport,
+
+    /// The report does not contain a `message/delivery-status` part.
+    #[fail(display = "report does not contain a delivery status")]
+    NoDeliveryStatus,
+
+    /// A part of the report could not be transfer decoded.
+    #[fail(display = "malformed transfer encoding in part with type {}", _0)]
+    MalformedTransferEncoding(String),
+
+    /// A required field is missing in the delivery status.
+    #[fail(display = "delivery status is missing the {} field", _0)]
+    MissingField(&'static str),
+
+    /// A field of the delivery status has a malformed value.
+    #[fail(display = "malformed {} field in delivery status: {:?}", name, value)]
+    MalformedField {
+        name: &'static str,
+        value: String
+    },
+
+    /// The delivery status does not contain any per-recipient fields.
+    #[fail(display = "delivery status does not contain any recipient")]
+    NoRecipients
+}
+
+/// Error returned by a crypto backend, e.g. a `OpenPgpBackend`.
+#[derive(Debug, Fail)]
+pub enum CryptoError {
+    /// No (usable) key is available for the given address.
+    #[fail(display = "no key available for {}", _0)]
+    MissingKey(String),
+
+    /// A certificate or key could not be parsed.
+    #[fail(display = "malformed key material: {}", _0)]
+    MalformedKeyMaterial(String),
+
+    /// A signature is invalid or it's signer is not trusted.
+    #[fail(display = "invalid signature: {}", _0)]
+    InvalidSignature(::failure::Error),
+
+    /// The backend failed to sign/encrypt the data.
+    #[fail(display = "crypto backend failed: {}", _0)]
+    Backend(::failure::Error)
+}
+
+/// Error returned when verifying or decrypting a received S/MIME mail.
+#[derive(Debug, Fail)]
+pub enum SmimeError {
+    /// The mail is not a `multipart/signed` S/MIME mail.
+    #[fail(display = "mail is not a S/MIME signed mail")]
+    NotSigned,
+
+    /// The mail is not a `application/pkcs7-mime` S/MIME mail.
+    #[fail(display = "mail is not a S/MIME encrypted mail")]
+    NotEncrypted,
+
+    /// The structure of the mail is malformed.
+    #[fail(display = "malformed S/MIME mail: {}", _0)]
+    Malformed(&'static str),
+
+    /// Verifying/decrypting the mail failed.
+    #[fail(display = "{}", _0)]
+    Crypto(CryptoError)
+}
+
+impl From<CryptoError> for SmimeError {
+    fn from(err: CryptoError) -> Self {
+        SmimeError::Crypto(err)
+    }
+}
+
+/// Error returned when sealing a mail with a ARC set fails.
+#[derive(Debug, Fail)]
+pub enum ArcError {
+    /// The instance is not in the range `1..=50`.
+    #[fail(display = "invalid ARC instance: {}", _0)]
+    InvalidInstance(u32),
+
+    /// The mail can not be sealed as it's malformed.
+    #[fail(display = "can not seal malformed mail: {}", _0)]
+    Malformed(&'static str),
+
+    /// Encoding the `ARC-Authentication-Results` header failed.
+    #[fail(display = "{}", _0)]
+    Encoding(EncodingError),
+
+    /// Hashing or signing failed.
+    #[fail(display = "{}", _0)]
+    Crypto(CryptoError)
+}
+
+impl From<CryptoError> for ArcError {
+    fn from(err: CryptoError) -> Self {
+        ArcError::Crypto(err)
+    }
+}
+
+impl From<EncodingError> for ArcError {
+    fn from(err: EncodingError) -> Self {
+        ArcError::Encoding(err)
+    }
+}
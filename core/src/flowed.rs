@@ -0,0 +1,274 @@
+//! This module provides `format=flowed` (rfc3676) encoding and decoding of plain text.
+//!
+//! In a `text/plain; format=flowed` body a line ending with a space is
+//! a "soft" line break, i.e. the line and the next line belong to the
+//! same paragraph and can be re-wrapped by the mail client to fit the
+//! display width. Lines without a trailing space end with a "hard" line
+//! break like in normal plain text.
+//!
+//! Lines starting with a space, `>` or `From ` are "space-stuffed" with
+//! a additional leading space, as a leading `>` marks quotes and lines
+//! starting with `From ` are often mangled by mail systems.
+//!
+//! Use `Data::flowed_text`/`Resource::flowed_text` to create a flowed body
+//! and `decode_text_body` to get the text of a (maybe) flowed body.
+use std::borrow::Cow;
+
+use headers::header_components::MediaType;
+
+/// The maximal length of a line (in characters) produced by `encode_flowed`.
+///
+/// Lines can still be longer if they contain a word which is longer.
+pub const MAX_LINE_LEN: usize = 78;
+
+/// The signature separator line, which is never treated as flowed.
+const SIGNATURE_SEPARATOR: &str = "-- ";
+
+/// Encodes the text as `format=flowed; delsp=no` text.
+///
+/// Lines longer then `MAX_LINE_LEN` are soft-wrapped at spaces, trailing
+/// spaces on the lines of `text` are removed (except for the signature
+/// separator `"-- "`) and lines are space-stuffed if needed.
+///
+/// The resulting text uses `"\r\n"` line endings.
+pub fn encode_flowed(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / MAX_LINE_LEN * 3);
+    let (text, ends_with_newline) = strip_final_newline(text);
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            out.push_str("\r\n");
+        }
+        let line = line.trim_end_matches('\r');
+        if line == SIGNATURE_SEPARATOR {
+            out.push_str(line);
+        } else {
+            encode_line(line.trim_end_matches(' '), &mut out);
+        }
+    }
+    if ends_with_newline {
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn encode_line(line: &str, out: &mut String) {
+    let mut rest = line;
+    loop {
+        let stuffed = needs_stuffing(rest);
+        let limit = if stuffed { MAX_LINE_LEN - 1 } else { MAX_LINE_LEN };
+        let break_at =
+            if rest.chars().count() <= limit {
+                None
+            } else {
+                soft_break_position(rest, limit)
+            };
+
+        if stuffed {
+            out.push(' ');
+        }
+        match break_at {
+            Some(position) => {
+                out.push_str(&rest[..position]);
+                out.push_str("\r\n");
+                rest = &rest[position..];
+            },
+            None => {
+                out.push_str(rest);
+                return;
+            }
+        }
+    }
+}
+
+/// Returns the byte position after the last space at which the line can be broken.
+///
+/// The line is only broken after the last space of a sequence of spaces
+/// which is not part of the indentation of the line. If there is no such
+/// position in the first `limit` characters the first position after it
+/// is used.
+fn soft_break_position(line: &str, limit: usize) -> Option<usize> {
+    let indentation = line.len() - line.trim_start_matches(' ').len();
+    let mut position = None;
+    for (count, (index, ch)) in line.char_indices().enumerate() {
+        if ch != ' ' || index < indentation || line[index + 1..].starts_with(' ') {
+            continue;
+        }
+        if count < limit || position.is_none() {
+            position = Some(index + 1);
+        }
+        if count >= limit {
+            break;
+        }
+    }
+    position.filter(|&position| position < line.len())
+}
+
+fn needs_stuffing(line: &str) -> bool {
+    line.starts_with(' ') || line.starts_with('>') || line.starts_with("From ")
+}
+
+/// Decodes `format=flowed` text, joining soft-wrapped lines.
+///
+/// If `delsp` is true the trailing space of soft-wrapped lines is removed
+/// (the `delsp=yes` parameter). Quoted lines are joined per quote depth
+/// and are returned with a `>` for each quote level followed by a space.
+///
+/// The resulting text uses `"\r\n"` line endings.
+pub fn decode_flowed(text: &str, delsp: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let (text, ends_with_newline) = strip_final_newline(text);
+    let mut paragraph: Option<(usize, String)> = None;
+
+    for line in text.split('\n') {
+        let line = line.trim_end_matches('\r');
+        let depth = line.bytes().take_while(|&ch| ch == b'>').count();
+        let mut content = &line[depth..];
+        if content.starts_with(' ') {
+            content = &content[1..];
+        }
+
+        let is_signature_separator = content == SIGNATURE_SEPARATOR;
+        let open_depth = paragraph.as_ref().map(|&(open_depth, _)| open_depth);
+        if is_signature_separator || open_depth.map(|open_depth| open_depth != depth).unwrap_or(false) {
+            if let Some((open_depth, open_text)) = paragraph.take() {
+                push_line(&mut out, open_depth, &open_text);
+            }
+        }
+
+        let flowed = content.ends_with(' ') && !is_signature_separator;
+        if flowed && delsp {
+            content = &content[..content.len() - 1];
+        }
+
+        let text = &mut paragraph.get_or_insert_with(|| (depth, String::new())).1;
+        text.push_str(content);
+
+        if !flowed {
+            if let Some((depth, text)) = paragraph.take() {
+                push_line(&mut out, depth, &text);
+            }
+        }
+    }
+    if let Some((depth, text)) = paragraph.take() {
+        push_line(&mut out, depth, &text);
+    }
+
+    if !ends_with_newline && out.ends_with("\r\n") {
+        let len = out.len() - 2;
+        out.truncate(len);
+    }
+    out
+}
+
+fn push_line(out: &mut String, depth: usize, text: &str) {
+    for _ in 0..depth {
+        out.push('>');
+    }
+    if depth > 0 && !text.is_empty() {
+        out.push(' ');
+    }
+    out.push_str(text);
+    out.push_str("\r\n");
+}
+
+fn strip_final_newline(text: &str) -> (&str, bool) {
+    match text.strip_suffix('\n') {
+        Some(text) => (text.trim_end_matches('\r'), true),
+        None => (text, false)
+    }
+}
+
+/// Returns true if the media type has a `format=flowed` parameter.
+pub fn is_flowed(media_type: &MediaType) -> bool {
+    media_type.get_param("format")
+        .map(|format| format.to_content().eq_ignore_ascii_case("flowed"))
+        .unwrap_or(false)
+}
+
+/// Returns the text of a text body, decoding it if it is `format=flowed`.
+///
+/// The `delsp` parameter of the media type is respected.
+pub fn decode_text_body<'a>(media_type: &MediaType, text: &'a str) -> Cow<'a, str> {
+    if is_flowed(media_type) {
+        let delsp = media_type.get_param("delsp")
+            .map(|delsp| delsp.to_content().eq_ignore_ascii_case("yes"))
+            .unwrap_or(false);
+        Cow::Owned(decode_flowed(text, delsp))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PARAGRAPH: &str = concat!(
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod ",
+        "tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam."
+    );
+
+    #[test]
+    fn long_lines_are_soft_wrapped() {
+        let text = format!("{}\nshort line   \n-- \nme\n", PARAGRAPH);
+        let encoded = encode_flowed(&text);
+        assert_eq!(encoded, concat!(
+            "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \r\n",
+            "tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam.\r\n",
+            "short line\r\n",
+            "-- \r\n",
+            "me\r\n"
+        ));
+        assert!(encoded.split("\r\n").all(|line| line.chars().count() <= MAX_LINE_LEN));
+
+        let decoded = decode_flowed(&encoded, false);
+        assert_eq!(decoded, format!("{}\r\nshort line\r\n-- \r\nme\r\n", PARAGRAPH));
+    }
+
+    #[test]
+    fn lines_are_space_stuffed() {
+        let text = ">not a quote\nFrom me\n  indented\nFromage";
+        let encoded = encode_flowed(text);
+        assert_eq!(encoded, " >not a quote\r\n From me\r\n   indented\r\nFromage");
+        assert_eq!(decode_flowed(&encoded, false), ">not a quote\r\nFrom me\r\n  indented\r\nFromage");
+    }
+
+    #[test]
+    fn long_words_are_not_broken() {
+        let word = "x".repeat(100);
+        let text = format!("{} {} end", word, word);
+        assert_eq!(encode_flowed(&text), format!("{} \r\n{} \r\nend", word, word));
+    }
+
+    #[test]
+    fn decode_quotes_and_delsp() {
+        let text = concat!(
+            "> Hy, this is \r\n",
+            "> quoted \r\n",
+            ">> deeper\r\n",
+            "Re ply \r\n",
+            "\r\n",
+            "-- \r\n",
+            "me\r\n"
+        );
+        assert_eq!(decode_flowed(text, false), concat!(
+            "> Hy, this is quoted \r\n",
+            ">> deeper\r\n",
+            "Re ply \r\n",
+            "-- \r\n",
+            "me\r\n"
+        ));
+
+        assert_eq!(decode_flowed("Super\r\ncali \r\nfragi \r\nlistic\r\n", true), "Super\r\ncalifragilistic\r\n");
+    }
+
+    #[test]
+    fn decode_text_body_respects_parameters() {
+        let flowed = MediaType::parse("text/plain; charset=utf-8; format=flowed; delsp=yes").unwrap();
+        let plain = MediaType::parse("text/plain; charset=utf-8").unwrap();
+        assert!(is_flowed(&flowed));
+        assert!(!is_flowed(&plain));
+        assert_eq!(decode_text_body(&flowed, "a \r\nb"), "ab");
+        assert_eq!(decode_text_body(&plain, "a \r\nb"), "a \r\nb");
+    }
+}
@@ -0,0 +1,341 @@
+//! A minimal html tokenizer used for processing html bodies.
+//!
+//! It is used by the `html_text` module and the html post-processing
+//! steps of the `mail-template` crate. It only splits the html into tags,
+//! text, comments and the raw text content of `<script>`/`<style>` elements,
+//! keeping the byte ranges of all tokens and attributes so that the html
+//! can be rewritten without changing anything but the parts which should
+//! be changed.
+use std::{
+    borrow::Cow,
+    ops::Range
+};
+
+#[derive(Debug)]
+pub enum Token<'a> {
+    /// Text between tags (this includes stray `<` characters).
+    Text(Range<usize>),
+    /// Comments, doctype declarations and processing instructions.
+    Other(Range<usize>),
+    /// The content of a `<script>` or `<style>` element.
+    RawText(Range<usize>),
+    Tag(Tag<'a>)
+}
+
+#[derive(Debug)]
+pub struct Tag<'a> {
+    /// The lowercase name of the element.
+    pub name: String,
+    /// If it is a end tag (`</name>`).
+    pub closing: bool,
+    /// If the tag ends with `/>`.
+    pub self_closing: bool,
+    pub attributes: Vec<Attribute<'a>>,
+    /// The range of the whole tag, including `<` and `>`.
+    pub range: Range<usize>
+}
+
+impl<'a> Tag<'a> {
+    /// Returns the first attribute with given (lowercase) name.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute<'a>> {
+        self.attributes.iter().find(|attr| attr.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Attribute<'a> {
+    /// The lowercase name of the attribute.
+    pub name: String,
+    /// The raw value, i.e. character references are not decoded.
+    pub value: &'a str,
+    /// The range of the whole attribute, including the name and quotes.
+    pub range: Range<usize>,
+    /// The range of the raw value, excluding quotes.
+    pub value_range: Range<usize>
+}
+
+impl<'a> Attribute<'a> {
+    /// Returns the value with decoded character references.
+    pub fn decoded_value(&self) -> Cow<'a, str> {
+        decode_entities(self.value)
+    }
+}
+
+/// Splits the html into tokens.
+///
+/// Stray `<` characters are treated as text and unclosed tags,
+/// comments or quotes end at the end of the input.
+pub fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let bytes = html.as_bytes();
+    // `to_ascii_lowercase` does not change any byte offsets
+    let lowercase = html.to_ascii_lowercase();
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        let end =
+            if html[start..].starts_with("<!--") {
+                html[start + 4..].find("-->").map(|end| start + 4 + end + 3).unwrap_or(html.len())
+            } else if bytes.get(start + 1) == Some(&b'!') || bytes.get(start + 1) == Some(&b'?') {
+                html[start..].find('>').map(|end| start + end + 1).unwrap_or(html.len())
+            } else {
+                match parse_tag(html, start) {
+                    Some(tag) => {
+                        push_text(&mut tokens, text_start..start);
+                        let end = tag.range.end;
+                        let raw_text = !tag.closing && (tag.name == "script" || tag.name == "style");
+                        let end_tag = format!("</{}", tag.name);
+                        tokens.push(Token::Tag(tag));
+                        pos = end;
+                        if raw_text {
+                            pos = lowercase[end..].find(&end_tag).map(|close| end + close).unwrap_or(html.len());
+                            if pos > end {
+                                tokens.push(Token::RawText(end..pos));
+                            }
+                        }
+                        text_start = pos;
+                    },
+                    None => {
+                        pos = start + 1;
+                    }
+                }
+                continue;
+            };
+
+        push_text(&mut tokens, text_start..start);
+        tokens.push(Token::Other(start..end));
+        pos = end;
+        text_start = end;
+    }
+    push_text(&mut tokens, text_start..html.len());
+
+    tokens
+}
+
+fn push_text(tokens: &mut Vec<Token>, range: Range<usize>) {
+    if range.start < range.end {
+        tokens.push(Token::Text(range));
+    }
+}
+
+/// Parses the tag starting at `start`, returns `None` if there is no tag.
+fn parse_tag(html: &str, start: usize) -> Option<Tag<'_>> {
+    let bytes = html.as_bytes();
+    let mut pos = start + 1;
+    let closing = bytes.get(pos) == Some(&b'/');
+    if closing {
+        pos += 1;
+    }
+    if !bytes.get(pos).map(|ch| ch.is_ascii_alphabetic()).unwrap_or(false) {
+        return None;
+    }
+
+    let name_start = pos;
+    while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'-') {
+        pos += 1;
+    }
+    let name = html[name_start..pos].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            None => break,
+            Some(&b'>') => {
+                pos += 1;
+                break;
+            },
+            Some(&b'/') => {
+                pos += 1;
+                self_closing = bytes.get(pos) == Some(&b'>');
+                continue;
+            },
+            _ => {}
+        }
+
+        let attr_start = pos;
+        while pos < bytes.len() && !b" \t\r\n/>=".contains(&bytes[pos]) {
+            pos += 1;
+        }
+        let name_end = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let value_range =
+            if bytes.get(pos) == Some(&b'=') {
+                pos += 1;
+                while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                match bytes.get(pos) {
+                    Some(&quote) if quote == b'"' || quote == b'\'' => {
+                        let value_start = pos + 1;
+                        let value_end = html[value_start..].find(quote as char)
+                            .map(|end| value_start + end)
+                            .unwrap_or(html.len());
+                        pos = (value_end + 1).min(html.len());
+                        value_start..value_end
+                    },
+                    _ => {
+                        let value_start = pos;
+                        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                            pos += 1;
+                        }
+                        value_start..pos
+                    }
+                }
+            } else {
+                pos = name_end;
+                name_end..name_end
+            };
+
+        if attr_start == pos {
+            // can not make progress, e.g. on a stray `=`
+            pos += 1;
+            continue;
+        }
+        attributes.push(Attribute {
+            name: html[attr_start..name_end].to_ascii_lowercase(),
+            value: &html[value_range.clone()],
+            range: attr_start..pos,
+            value_range
+        });
+    }
+
+    Some(Tag { name, closing, self_closing, attributes, range: start..pos })
+}
+
+/// Decodes character references, unknown references are kept as they are.
+pub fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..].find(';')
+            .filter(|&end| end > 0 && end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|ch| (ch, end + 2)));
+
+        match decoded {
+            Some((ch, len)) => {
+                out.push(ch);
+                rest = &rest[len..];
+            },
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        return match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => number.parse().ok()
+        }.and_then(::std::char::from_u32);
+    }
+    let ch = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "euro" => '€',
+        "times" => '×',
+        "bull" => '•',
+        "middot" => '·',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        _ => return None
+    };
+    Some(ch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_keeps_ranges() {
+        let html = concat!(
+            "<!DOCTYPE html><p class=\"a b\" hidden id=x>Hy<!-- <b> --> a < b</p>",
+            "<style>p > a { color: red }</style><br/>"
+        );
+        let tokens = tokenize(html);
+        let texts = tokens.iter()
+            .map(|token| match *token {
+                Token::Text(ref range) => format!("text:{}", &html[range.clone()]),
+                Token::Other(ref range) => format!("other:{}", &html[range.clone()]),
+                Token::RawText(ref range) => format!("raw:{}", &html[range.clone()]),
+                Token::Tag(ref tag) => format!("tag:{}", &html[tag.range.clone()])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec![
+            "other:<!DOCTYPE html>",
+            "tag:<p class=\"a b\" hidden id=x>",
+            "text:Hy",
+            "other:<!-- <b> -->",
+            "text: a < b",
+            "tag:</p>",
+            "tag:<style>",
+            "raw:p > a { color: red }",
+            "tag:</style>",
+            "tag:<br/>"
+        ]);
+
+        match tokens[1] {
+            Token::Tag(ref tag) => {
+                let attributes = tag.attributes.iter()
+                    .map(|attr| (&*attr.name, attr.value, &html[attr.range.clone()]))
+                    .collect::<Vec<_>>();
+                assert_eq!(attributes, vec![
+                    ("class", "a b", "class=\"a b\""),
+                    ("hidden", "", "hidden"),
+                    ("id", "x", "id=x")
+                ]);
+            },
+            _ => panic!("expected tag")
+        }
+        match tokens[9] {
+            Token::Tag(ref tag) => assert!(tag.self_closing),
+            _ => panic!("expected tag")
+        }
+    }
+
+    #[test]
+    fn attribute_values_can_be_decoded() {
+        let tokens = tokenize("<img src=\"a&amp;b&#x20;&unknown;.png\" alt=plain>");
+        let tag = match tokens[0] {
+            Token::Tag(ref tag) => tag,
+            _ => panic!("expected tag")
+        };
+        assert_eq!(tag.attribute("src").unwrap().decoded_value(), "a&b &unknown;.png");
+        assert_eq!(tag.attribute("alt").unwrap().decoded_value(), "plain");
+    }
+}
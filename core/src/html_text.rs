@@ -0,0 +1,407 @@
+//! This module provides a conversion of html bodies to readable plain text.
+//!
+//! It is used to derive a `text/plain` alternative body for mails which
+//! only have a `text/html` body (see `MailParts::with_plain_text_alternative`),
+//! as many spam filters penalize mails without a `text/plain` body.
+//!
+//! The conversion is not a full html renderer, it:
+//!
+//! - drops `<head>`, `<style>`, `<script>` and similar elements including their content
+//! - collapses whitespace (except in `<pre>`) and places line breaks at block elements
+//! - turns list items into `*` or `1.` bullets (indented for nested lists)
+//! - prefixes quoted lines with `> `
+//! - replaces images with their `alt` text
+//! - keeps links as footnotes, i.e. `text[1]` with `[1] <href>` at the end of the text
+//!
+//! The resulting text uses `"\r\n"` line endings.
+use std::borrow::Cow;
+
+use crate::html::{tokenize, decode_entities, Tag, Token};
+
+/// Elements which are dropped including their content.
+const DROPPED_ELEMENTS: &[&str] = &["head", "title", "style", "script", "template", "noscript"];
+
+/// Elements which are separated by a empty line from surrounding text.
+const PARAGRAPH_ELEMENTS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6",
+    "table", "blockquote", "pre", "dl", "figure"
+];
+
+/// Elements which start on a new line.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "div", "tr", "dt", "dd", "section", "article", "header", "footer",
+    "nav", "aside", "main", "address", "form", "fieldset", "caption", "center"
+];
+
+/// Converts a html document (or fragment) to plain text.
+pub fn html_to_plain_text(html: &str) -> String {
+    let mut writer = TextWriter::default();
+    // the name of the dropped element we are in, if any
+    let mut dropped: Option<String> = None;
+
+    for token in tokenize(html) {
+        if let Some(name) = dropped.take() {
+            match token {
+                Token::Tag(ref tag) if tag.closing && tag.name == name => {},
+                _ => dropped = Some(name)
+            }
+            continue;
+        }
+
+        match token {
+            Token::Tag(tag) => {
+                if tag.closing {
+                    writer.end_element(&tag.name);
+                } else if DROPPED_ELEMENTS.contains(&&*tag.name) {
+                    if !tag.self_closing {
+                        dropped = Some(tag.name);
+                    }
+                } else {
+                    writer.start_element(&tag);
+                }
+            },
+            Token::Text(range) => {
+                writer.push_text(&decode_entities(&html[range]));
+            },
+            // comments, doctypes and the content of `<script>`/`<style>`
+            Token::Other(_) | Token::RawText(_) => {}
+        }
+    }
+
+    writer.finish()
+}
+
+/// Returns the value of the attribute with decoded character references.
+fn attribute<'a>(tag: &Tag<'a>, name: &str) -> Option<Cow<'a, str>> {
+    tag.attribute(name).map(|attr| attr.decoded_value())
+}
+
+#[derive(Debug)]
+enum List {
+    Unordered,
+    Ordered(u32)
+}
+
+#[derive(Debug)]
+struct OpenLink {
+    href: String,
+    /// The length of the output when the link started.
+    text_start: usize
+}
+
+#[derive(Debug, Default)]
+struct TextWriter {
+    out: String,
+    /// The number of line breaks to insert before the next text.
+    pending_newlines: usize,
+    pending_space: bool,
+    /// If the prefix (quotes and indentation) of the current line still has to be written.
+    at_line_start: bool,
+    /// If there is no text on the current line (a list marker is no text).
+    line_empty: bool,
+    quote_depth: usize,
+    pre_depth: usize,
+    lists: Vec<List>,
+    open_links: Vec<OpenLink>,
+    links: Vec<String>
+}
+
+impl TextWriter {
+
+    fn start_element(&mut self, tag: &Tag) {
+        let name = &*tag.name;
+        match name {
+            "br" => {
+                if self.pending_newlines < 2 {
+                    self.pending_newlines += 1;
+                }
+            },
+            "hr" => {
+                self.block_break(2);
+                self.start_content();
+                self.out.push_str("----------");
+                self.block_break(2);
+            },
+            "ul" | "ol" => {
+                self.block_break(if self.lists.is_empty() { 2 } else { 1 });
+                let list =
+                    if name == "ol" {
+                        let start = attribute(tag, "start").and_then(|start| start.trim().parse().ok());
+                        List::Ordered(start.unwrap_or(1))
+                    } else {
+                        List::Unordered
+                    };
+                self.lists.push(list);
+            },
+            "li" => {
+                let marker = match self.lists.last_mut() {
+                    Some(&mut List::Ordered(ref mut number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    },
+                    _ => "* ".to_owned()
+                };
+                self.block_break(1);
+                self.start_line_with_marker(&marker);
+            },
+            "a" => {
+                let href = attribute(tag, "href").unwrap_or_default().trim().to_owned();
+                self.open_links.push(OpenLink { href, text_start: self.out.len() });
+            },
+            "img" => {
+                if let Some(alt) = attribute(tag, "alt") {
+                    self.push_text(&alt);
+                }
+            },
+            "td" | "th" => {
+                self.pending_space = true;
+            },
+            "blockquote" => {
+                self.block_break(2);
+                self.quote_depth += 1;
+            },
+            "pre" => {
+                self.block_break(2);
+                self.pre_depth += 1;
+            },
+            _ => self.block_break_for(name)
+        }
+    }
+
+    fn end_element(&mut self, name: &str) {
+        match name {
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.block_break(if self.lists.is_empty() { 2 } else { 1 });
+            },
+            "li" => self.block_break(1),
+            "a" => {
+                if let Some(link) = self.open_links.pop() {
+                    self.end_link(link);
+                }
+            },
+            "blockquote" => {
+                self.block_break(2);
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            },
+            "pre" => {
+                self.block_break(2);
+                self.pre_depth = self.pre_depth.saturating_sub(1);
+            },
+            _ => self.block_break_for(name)
+        }
+    }
+
+    fn block_break_for(&mut self, name: &str) {
+        if PARAGRAPH_ELEMENTS.contains(&name) {
+            self.block_break(2);
+        } else if BLOCK_ELEMENTS.contains(&name) {
+            self.block_break(1);
+        }
+    }
+
+    fn end_link(&mut self, link: OpenLink) {
+        let href = link.href;
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return;
+        }
+
+        let text = self.out[link.text_start..].trim();
+        if text == href || Some(text) == href.strip_prefix("mailto:") {
+            return;
+        }
+
+        let index = match self.links.iter().position(|known| *known == href) {
+            Some(index) => index,
+            None => {
+                self.links.push(href);
+                self.links.len() - 1
+            }
+        };
+
+        if self.line_empty {
+            self.start_content();
+        }
+        self.out.push_str(&format!("[{}]", index + 1));
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            if self.pre_depth > 0 {
+                match ch {
+                    '\n' => self.pending_newlines += 1,
+                    '\r' => {},
+                    _ => {
+                        self.start_content();
+                        self.out.push(ch);
+                    }
+                }
+            } else if ch.is_whitespace() {
+                if !self.line_empty {
+                    self.pending_space = true;
+                }
+            } else {
+                self.start_content();
+                self.out.push(ch);
+            }
+        }
+    }
+
+    /// Makes sure the next text starts on a new line, with `newlines - 1` empty lines in between.
+    fn block_break(&mut self, newlines: usize) {
+        if !self.out.is_empty() && self.pending_newlines < newlines {
+            self.pending_newlines = newlines;
+        }
+        self.pending_space = false;
+    }
+
+    /// Writes pending line breaks, line prefixes and spaces before text is written.
+    fn start_content(&mut self) {
+        self.write_pending_newlines();
+        if self.at_line_start {
+            let prefix = self.line_prefix(None);
+            self.out.push_str(&prefix);
+            self.at_line_start = false;
+        } else if self.pending_space && !self.line_empty {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+        self.line_empty = false;
+    }
+
+    fn start_line_with_marker(&mut self, marker: &str) {
+        self.write_pending_newlines();
+        if !self.at_line_start && !self.out.is_empty() {
+            self.out.push_str("\r\n");
+        }
+        let prefix = self.line_prefix(Some(marker));
+        self.out.push_str(&prefix);
+        self.at_line_start = false;
+        self.line_empty = true;
+        self.pending_space = false;
+    }
+
+    fn write_pending_newlines(&mut self) {
+        if self.out.is_empty() {
+            self.at_line_start = true;
+            self.line_empty = true;
+        } else if self.pending_newlines > 0 {
+            for _ in 0..self.pending_newlines {
+                self.out.push_str("\r\n");
+            }
+            self.at_line_start = true;
+            self.line_empty = true;
+        }
+        self.pending_newlines = 0;
+    }
+
+    fn line_prefix(&self, marker: Option<&str>) -> String {
+        let mut prefix = "> ".repeat(self.quote_depth);
+        let mut indent = self.lists.len();
+        if marker.is_some() {
+            indent = indent.saturating_sub(1);
+        }
+        prefix.push_str(&"  ".repeat(indent));
+        if let Some(marker) = marker {
+            prefix.push_str(marker);
+        }
+        prefix
+    }
+
+    fn finish(self) -> String {
+        let TextWriter { mut out, links, .. } = self;
+        let trimmed_len = out.trim_end().len();
+        out.truncate(trimmed_len);
+        if !out.is_empty() {
+            out.push_str("\r\n");
+        }
+        if !links.is_empty() {
+            out.push_str("\r\n");
+            for (index, href) in links.iter().enumerate() {
+                out.push_str(&format!("[{}] {}\r\n", index + 1, href));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::html_to_plain_text;
+
+    #[test]
+    fn drops_head_styles_and_scripts() {
+        let html = concat!(
+            "<!DOCTYPE html><html><head><title>Ignored</title>",
+            "<style>p { color: red; }</style></head>",
+            "<body><!-- a <b>comment</b> --><p>Hy  there,\n   how are\tyou?</p>",
+            "<script type=\"text/javascript\">if (a < b) { alert(\"</p>\"); }</script>",
+            "<p>Fish &amp; Chips&nbsp;&#8364;&#x21;</p></body></html>"
+        );
+        assert_eq!(
+            html_to_plain_text(html),
+            "Hy there, how are you?\r\n\r\nFish & Chips €!\r\n"
+        );
+    }
+
+    #[test]
+    fn links_become_footnotes() {
+        let html = concat!(
+            "<p>Visit <a href=\"https://example.test/a?b=1&amp;c=2\">our shop</a> or ",
+            "<a href='https://example.test/blog'><img src=\"cid:logo\" alt=\"the blog\"></a>.</p>",
+            "<p>Again <a href=\"https://example.test/a?b=1&amp;c=2\">the shop</a>, ",
+            "<a href=\"https://example.test/\">https://example.test/</a>, ",
+            "<a href=\"mailto:me@example.test\">me@example.test</a> ",
+            "and <a href=\"#top\">top</a></p>"
+        );
+        assert_eq!(html_to_plain_text(html), concat!(
+            "Visit our shop[1] or the blog[2].\r\n",
+            "\r\n",
+            "Again the shop[1], https://example.test/, me@example.test and top\r\n",
+            "\r\n",
+            "[1] https://example.test/a?b=1&c=2\r\n",
+            "[2] https://example.test/blog\r\n"
+        ));
+    }
+
+    #[test]
+    fn lists_become_bullets() {
+        let html = concat!(
+            "<p>Todo:</p><ul>\n  <li>buy milk</li>\n  <li>clean up<ol start=\"3\"><li>kitchen</li>",
+            "<li>bath<br>room</li></ol></li>\n</ul><p>Done</p>"
+        );
+        assert_eq!(html_to_plain_text(html), concat!(
+            "Todo:\r\n",
+            "\r\n",
+            "* buy milk\r\n",
+            "* clean up\r\n",
+            "  3. kitchen\r\n",
+            "  4. bath\r\n",
+            "    room\r\n",
+            "\r\n",
+            "Done\r\n"
+        ));
+    }
+
+    #[test]
+    fn quotes_pre_and_breaks() {
+        let html = concat!(
+            "<div>Hy,<br>see below</div><blockquote><p>quoted<br>text</p></blockquote>",
+            "<pre>let a  = 1;\nlet b = 2;</pre><hr>a < b"
+        );
+        assert_eq!(html_to_plain_text(html), concat!(
+            "Hy,\r\n",
+            "see below\r\n",
+            "\r\n",
+            "> quoted\r\n",
+            "> text\r\n",
+            "\r\n",
+            "let a  = 1;\r\n",
+            "let b = 2;\r\n",
+            "\r\n",
+            "----------\r\n",
+            "\r\n",
+            "a < b\r\n"
+        ));
+    }
+}
@@ -30,6 +30,12 @@ extern crate serde_test;
 extern crate futures_cpupool;
 #[cfg(feature="test-utils")]
 extern crate lazy_static;
+#[cfg(feature="pgp-rpgp")]
+extern crate pgp as rpgp_crate;
+#[cfg(feature="pgp-rpgp")]
+extern crate rand_core;
+#[cfg(any(feature="smime-openssl", feature="arc-openssl"))]
+extern crate openssl as openssl_crate;
 
 
 
@@ -44,7 +50,18 @@ pub mod context;
 mod resource;
 mod encode;
 mod mail;
+mod raw_mail;
 pub mod compose;
+pub mod mdn;
+pub mod dsn;
+pub mod pgp;
+pub mod crypto;
+pub mod smime;
+pub mod arc;
+pub mod calendar;
+pub mod html;
+pub mod html_text;
+pub mod flowed;
 #[cfg(feature="test-utils")]
 pub mod test_utils;
 
@@ -29,11 +29,17 @@ use headers::{
         ContentTransferEncoding,
         Date, MessageId,
         ContentDisposition,
-        ContentId
+        ContentId,
+        AutoSubmitted, XAutoResponseSuppress,
+        Importance, Priority, XPriority
     },
     header_components::{
+        self,
         DateTime,
-        MediaType
+        MediaType,
+        TransferEncoding,
+        AutoResponseSuppress,
+        MailPriority
     },
     error::{
         HeaderValidationError,
@@ -43,13 +49,16 @@ use headers::{
 use ::{
     utils::SendBoxFuture,
     mime::create_structured_random_boundary,
+    encode::encode_canonical_mail_body,
     error::{
+        CryptoError,
         MailError,
         OtherValidationError,
         ResourceLoadingError
     },
     resource::*,
-    context::Context
+    context::Context,
+    default_impl::fix_newlines
 };
 
 /// A type representing a Mail.
@@ -270,6 +279,34 @@ impl Mail {
         self.headers_mut().insert_all(headers);
     }
 
+    /// Sets the `Importance`, `Priority` and `X-Priority` headers consistently.
+    ///
+    /// Mail clients disagree about which of this headers they use,
+    /// so all of them are set, replacing any previously set value.
+    pub fn set_priority(&mut self, priority: MailPriority) {
+        self.insert_header(Importance::body(priority.importance()));
+        self.insert_header(Priority::body(priority.priority()));
+        self.insert_header(XPriority::body(priority.x_priority()));
+    }
+
+    /// Returns the priority of the mail based on it's priority headers.
+    ///
+    /// The `Importance` header is preferred over the `Priority` header
+    /// which is preferred over the `X-Priority` header. If none of them
+    /// is set (or valid) `None` is returned.
+    pub fn priority(&self) -> Option<MailPriority> {
+        let headers = self.headers();
+        if let Some(Ok(importance)) = headers.get_single(Importance) {
+            Some(MailPriority::from(*importance.body()))
+        } else if let Some(Ok(priority)) = headers.get_single(Priority) {
+            Some(MailPriority::from(*priority.body()))
+        } else if let Some(Ok(x_priority)) = headers.get_single(XPriority) {
+            Some(MailPriority::from(*x_priority.body()))
+        } else {
+            None
+        }
+    }
+
     /// Returns a reference to the currently set headers.
     ///
     /// Note that some headers namely `Content-Transfer-Encoding` as well
@@ -530,7 +567,7 @@ fn top_level_validation(mail: &Mail) -> Result<(), HeaderValidationError> {
     }
 }
 
-/// insert auto-generated headers like `Date`, `Message-Id` and `Content-Id`
+/// insert auto-generated headers like `Date`, `Message-Id`, `Content-Id` and `Auto-Submitted`
 fn auto_gen_headers<C: Context>(
     mail: &mut Mail,
     encoded_resources: Vec<EncData>,
@@ -545,20 +582,131 @@ fn auto_gen_headers<C: Context>(
         if !headers.contains(MessageId) {
             headers.insert(MessageId::body(ctx.generate_message_id()));
         }
+
+        if let Some(auto_submitted) = ctx.auto_submitted() {
+            if !headers.contains(AutoSubmitted) {
+                headers.insert(AutoSubmitted::body(auto_submitted));
+            }
+            if auto_submitted != header_components::AutoSubmitted::No
+                && !headers.contains(XAutoResponseSuppress)
+            {
+                headers.insert(XAutoResponseSuppress::body(AutoResponseSuppress::all()));
+            }
+        }
     }
 
+    replace_with_encoded_resources(mail, encoded_resources);
+
+    let mut boundary_count = 0;
+    recursive_auto_gen_headers(mail, &mut boundary_count, ctx);
+
+    // Make sure no **top-level** body has a content-id field, as it already has a Message-Id
+    mail.headers_mut().remove(ContentId);
+}
+
+/// replaces all resources with the given loaded and transfer encoded resources
+///
+/// The resources are expected to be in the order in which `visit_mail_bodies` visits them.
+fn replace_with_encoded_resources(mail: &mut Mail, encoded_resources: Vec<EncData>) {
     let mut iter = encoded_resources.into_iter();
     mail.visit_mail_bodies_mut(&mut move |resource: &mut Resource| {
         let enc_data = iter.next()
             .expect("[BUG] mail structure modified while turing it into encoded mail");
         mem::replace(resource, Resource::EncData(enc_data));
     });
+}
 
-    let mut boundary_count = 0;
-    recursive_auto_gen_headers(mail, &mut boundary_count, ctx);
+/// Loads all resources of a mail body and inserts the auto-generated body headers.
+///
+/// This does the same as `into_encodable_mail`, except that it does not
+/// do any top level validation and doesn't insert any top level headers
+/// (like `Date`). It's used to prepare a mail body which needs to be
+/// encoded on it's own, e.g. to sign or encrypt it.
+pub(crate) fn resolve_mail_body<C: Context>(mail: Mail, ctx: C)
+    -> SendBoxFuture<Mail, MailError>
+{
+    if let Err(err) = mail.generally_validate_mail() {
+        return Box::new(future::err(err));
+    }
 
-    // Make sure no **top-level** body has a content-id field, as it already has a Message-Id
-    mail.headers_mut().remove(ContentId);
+    let mut futures = Vec::new();
+    mail.visit_mail_bodies(&mut |resource: &Resource| {
+        futures.push(ctx.load_transfer_encoded_resource(resource));
+    });
+
+    let fut = future::join_all(futures)
+        .map_err(MailError::from)
+        .map(move |encoded_resources| {
+            let mut mail = mail;
+            replace_with_encoded_resources(&mut mail, encoded_resources);
+            let mut boundary_count = 0;
+            recursive_auto_gen_headers(&mut mail, &mut boundary_count, &ctx);
+            mail
+        });
+
+    Box::new(fut)
+}
+
+/// Resolves the mail body and signs it's canonical encoding.
+///
+/// This is shared by PGP/MIME and S/MIME: The mail body is resolved (see
+/// `resolve_mail_body`) and encoded in canonical form, `sign` is called with
+/// the encoding (through `Context::offload_fn`) and `wrap` creates the
+/// `multipart/signed` mail from the resolved mail and the signature.
+///
+/// The last CRLF of the encoding is not signed, as it belongs to the
+/// boundary following the signed body.
+pub(crate) fn sign_mail_body<C, S, W, R>(mail: Mail, ctx: C, sign: S, wrap: W)
+    -> SendBoxFuture<Mail, MailError>
+    where C: Context,
+          S: FnOnce(Vec<u8>) -> Result<R, CryptoError> + Send + 'static,
+          W: FnOnce(Mail, R, &C) -> Result<Mail, MailError> + Send + 'static,
+          R: Send + 'static
+{
+    let ctx2 = ctx.clone();
+    let fut = offload_with_canonical_body(mail, ctx, true, sign)
+        .and_then(move |(mail, signature)| wrap(mail, signature, &ctx2));
+
+    Box::new(fut)
+}
+
+/// Resolves the mail body and encrypts it's canonical encoding.
+///
+/// Like `sign_mail_body`, except that the whole encoding is passed to
+/// `encrypt` and `wrap` creates the encrypted mail from it's result.
+pub(crate) fn encrypt_mail_body<C, E, W>(mail: Mail, ctx: C, encrypt: E, wrap: W)
+    -> SendBoxFuture<Mail, MailError>
+    where C: Context,
+          E: FnOnce(Vec<u8>) -> Result<Vec<u8>, CryptoError> + Send + 'static,
+          W: FnOnce(Vec<u8>, &C) -> Mail + Send + 'static
+{
+    let ctx2 = ctx.clone();
+    let fut = offload_with_canonical_body(mail, ctx, false, encrypt)
+        .map(move |(_, encrypted)| wrap(encrypted, &ctx2));
+
+    Box::new(fut)
+}
+
+fn offload_with_canonical_body<C, O, R>(mail: Mail, ctx: C, strip_last_crlf: bool, operation: O)
+    -> impl Future<Item=(Mail, R), Error=MailError> + Send
+    where C: Context,
+          O: FnOnce(Vec<u8>) -> Result<R, CryptoError> + Send + 'static,
+          R: Send + 'static
+{
+    resolve_mail_body(mail, ctx.clone())
+        .and_then(move |mail| {
+            let mut data = encode_canonical_mail_body(&mail)?;
+            if strip_last_crlf && data.ends_with(b"\r\n") {
+                let new_len = data.len() - 2;
+                data.truncate(new_len);
+            }
+            Ok((mail, data))
+        })
+        .and_then(move |(mail, data)| {
+            ctx.offload_fn(move || operation(data))
+                .map_err(MailError::from)
+                .map(move |result| (mail, result))
+        })
 }
 
 /// returns the `EncData` from a resource
@@ -573,6 +721,53 @@ pub(crate) fn assume_encoded(resource: &Resource) -> &EncData {
     }
 }
 
+/// Creates a singlepart mail with given content and media type.
+///
+/// # Panic
+///
+/// Panics if the media type can not be parsed.
+pub(crate) fn new_data_body(content: Vec<u8>, media_type: &'static str, ctx: &impl Context) -> Mail {
+    let meta = Metadata {
+        file_meta: Default::default(),
+        media_type: MediaType::parse(media_type).unwrap(),
+        content_id: ctx.generate_content_id()
+    };
+    let data = Data::new(content, meta);
+    Mail::new_singlepart_mail(Resource::Data(data))
+}
+
+/// Creates a singlepart mail with given content which is not transfer encoded.
+///
+/// This is needed e.g. for `message/*` bodies which (mostly) can only use
+/// the `7bit` or `8bit` transfer encoding (rfc2046). Line endings are
+/// normalized to `"\r\n"` and `8bit` is used if the content isn't ascii.
+///
+/// As the `"\r\n"` in front of a multipart boundary belongs to the boundary
+/// (rfc2046) a additional `"\r\n"` is added if the content ends with one.
+///
+/// # Panic
+///
+/// Panics if the media type can not be parsed.
+pub(crate) fn new_unencoded_body(content: Vec<u8>, media_type: &'static str, ctx: &impl Context) -> Mail {
+    let mut content = fix_newlines(&content).unwrap_or(content);
+    if content.ends_with(b"\r\n") {
+        content.extend_from_slice(b"\r\n");
+    }
+    let encoding =
+        if content.is_ascii() {
+            TransferEncoding::_7Bit
+        } else {
+            TransferEncoding::_8Bit
+        };
+    let meta = Metadata {
+        file_meta: Default::default(),
+        media_type: MediaType::parse(media_type).unwrap(),
+        content_id: ctx.generate_content_id()
+    };
+    let enc_data = EncData::new(content, meta, encoding);
+    Mail::new_singlepart_mail(Resource::EncData(enc_data))
+}
+
 /// Auto-generates some headers for any body including non top-level mail bodies.
 ///
 /// For mails which are not multipart mails this does:
@@ -582,7 +777,9 @@ pub(crate) fn assume_encoded(resource: &Resource) -> &EncData {
 ///
 /// For multipart mails this does:
 /// - create/overwrite the boundary for the `Content-Type` header
-/// - call this method for all bodies in the multipart body
+/// - call this method for all bodies in the multipart body, except
+///   if it's a `multipart/signed` body, as the signed body was already
+///   prepared when signing it and must not be changed
 fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, boundary_count: &mut usize, ctx: &C) {
     let &mut Mail { ref mut headers, ref mut body } = mail;
     match body {
@@ -607,6 +804,10 @@ fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, boundary_count: &mut
             *boundary_count += 1;
             content_type.set_param(BOUNDARY, boundary);
 
+            if is_multipart_signed(content_type) {
+                return;
+            }
+
             for sub_mail in bodies {
                 recursive_auto_gen_headers(sub_mail, boundary_count, ctx);
             }
@@ -614,6 +815,11 @@ fn recursive_auto_gen_headers<C: Context>(mail: &mut Mail, boundary_count: &mut
     }
 }
 
+/// returns true if the content type is `multipart/signed`
+pub(crate) fn is_multipart_signed(content_type: &MediaType) -> bool {
+    content_type.is_multipart() && content_type.subtype() == "signed"
+}
+
 pub(crate) fn validate_multipart_headermap(headers: &HeaderMap)
     -> Result<(), MailError>
 {
@@ -771,6 +977,29 @@ mod test {
             assert!(mail.headers().contains(Comments));
         });
 
+        test!(set_priority_sets_all_priority_headers, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            assert_eq!(mail.priority(), None);
+
+            mail.set_priority(MailPriority::High);
+            mail.set_priority(MailPriority::Low);
+
+            let headers = mail.headers();
+            assert_eq!(headers.len(), 3);
+            assert_eq!(*headers.get_single(Importance).unwrap()?.body(), header_components::Importance::Low);
+            assert_eq!(*headers.get_single(Priority).unwrap()?.body(), header_components::Priority::NonUrgent);
+            assert_eq!(*headers.get_single(XPriority).unwrap()?.body(), header_components::XPriority::LOWEST);
+            assert_eq!(mail.priority(), Some(MailPriority::Low));
+        });
+
+        test!(priority_falls_back_to_x_priority, {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r0", &ctx);
+            mail.insert_header(XPriority::auto_body("2 (High)")?);
+            assert_eq!(mail.priority(), Some(MailPriority::High));
+        });
+
     }
 
     mod EncodableMail {
@@ -899,6 +1128,56 @@ mod test {
             assert_eq!(&**used_date.body(), &provided_date);
         });
 
+        test!(inserts_auto_submitted_if_context_is_configured, {
+            let ctx = test_context()
+                .with_auto_submitted(header_components::AutoSubmitted::AutoGenerated);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let auto_submitted = enc_mail.headers()
+                .get_single(AutoSubmitted)
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(auto_submitted.body(), &header_components::AutoSubmitted::AutoGenerated);
+            assert!(enc_mail.headers().contains(XAutoResponseSuppress));
+        });
+
+        test!(does_not_override_auto_submitted_if_set, {
+            let ctx = test_context()
+                .with_auto_submitted(header_components::AutoSubmitted::AutoGenerated);
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"],
+                Subject: "hoho",
+                AutoSubmitted: "auto-replied"
+            }?);
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            let auto_submitted = enc_mail.headers()
+                .get_single(AutoSubmitted)
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(auto_submitted.body(), &header_components::AutoSubmitted::AutoReplied);
+        });
+
+        #[test]
+        fn does_not_insert_auto_submitted_by_default() {
+            let ctx = test_context();
+            let mut mail = Mail::plain_text("r9", &ctx);
+            mail.insert_headers(headers! {
+                _From: ["random@this.is.no.mail"]
+            }.unwrap());
+
+            let enc_mail = assert_ok!(mail.into_encodable_mail(ctx).wait());
+            assert_not!(enc_mail.headers().contains(AutoSubmitted));
+            assert_not!(enc_mail.headers().contains(XAutoResponseSuppress));
+        }
     }
 
 }
\ No newline at end of file
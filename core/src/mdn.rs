@@ -0,0 +1,394 @@
+//! This module provides a builder for message disposition notifications (rfc8098).
+//!
+//! A message disposition notification (MDN) is what is commonly called a
+//! "read receipt". The sender of a mail can request them by including a
+//! `Disposition-Notification-To` header. The recipient (or its mail user agent)
+//! then can send a MDN to the mailboxes in this header to notify the sender
+//! about the mail being displayed, deleted etc.
+//!
+//! A MDN is a `multipart/report; report-type=disposition-notification` mail
+//! containing:
+//!
+//! 1. a human readable `text/plain` body
+//! 2. a machine readable `message/disposition-notification` body
+//! 3. optionally the headers of the original mail (`text/rfc822-headers`)
+//!
+//! Note that while mail user agents are free to not send MDNs at all they
+//! should (normally) not send them without the user being asked first.
+
+use std::fmt::{self, Display};
+
+use media_type::MULTIPART;
+
+use headers::{
+    HeaderMap,
+    headers::{MessageId as MessageIdHeader, OriginalRecipient},
+    header_components::{MediaType, MessageId, TypedAddress}
+};
+
+use crate::{
+    context::Context,
+    mail::{Mail, new_unencoded_body},
+    resource::Resource
+};
+
+/// Whether the disposition was caused by the user or happened automatically.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum ActionMode {
+    /// The disposition was caused by a explicit action of the user (`manual-action`).
+    Manual,
+    /// The disposition was caused automatically, e.g. by a filter (`automatic-action`).
+    Automatic
+}
+
+impl ActionMode {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ActionMode::Manual => "manual-action",
+            ActionMode::Automatic => "automatic-action"
+        }
+    }
+}
+
+/// Whether the user explicitly agreed to sending the MDN.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum SendingMode {
+    /// The user explicitly agreed to send the MDN (`MDN-sent-manually`).
+    Manual,
+    /// The MDN is send automatically, e.g. because of a user setting (`MDN-sent-automatically`).
+    Automatic
+}
+
+impl SendingMode {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SendingMode::Manual => "MDN-sent-manually",
+            SendingMode::Automatic => "MDN-sent-automatically"
+        }
+    }
+}
+
+/// What happened with the original mail.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum DispositionType {
+    /// The mail was displayed to the user (note that this does not mean it was read).
+    Displayed,
+    /// The mail was deleted without being displayed.
+    Deleted,
+    /// The mail was send somewhere else (e.g. printed, forwarded) without being displayed.
+    Dispatched,
+    /// The mail was processed in some other way without being displayed.
+    Processed
+}
+
+impl DispositionType {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            DispositionType::Displayed => "displayed",
+            DispositionType::Deleted => "deleted",
+            DispositionType::Dispatched => "dispatched",
+            DispositionType::Processed => "processed"
+        }
+    }
+}
+
+/// The value of the `Disposition` field of a MDN,
+/// e.g. `manual-action/MDN-sent-manually; displayed`.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct MdnDisposition {
+    pub action_mode: ActionMode,
+    pub sending_mode: SendingMode,
+    pub disposition_type: DispositionType
+}
+
+impl MdnDisposition {
+
+    /// The disposition of a mail the user opened and agreed to send a MDN for.
+    pub fn displayed_manually() -> Self {
+        MdnDisposition {
+            action_mode: ActionMode::Manual,
+            sending_mode: SendingMode::Manual,
+            disposition_type: DispositionType::Displayed
+        }
+    }
+}
+
+impl Display for MdnDisposition {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}/{}; {}",
+            self.action_mode.as_str(),
+            self.sending_mode.as_str(),
+            self.disposition_type.as_str())
+    }
+}
+
+/// A builder for message disposition notifications (rfc8098).
+///
+/// The mail created by `Mdn::create_mail` only contains the
+/// body and `Content-Type` of the MDN, headers like `From`,
+/// `To` (normally the mailboxes from the `Disposition-Notification-To`
+/// header of the original mail) and `Subject` still have to be
+/// set.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_core;
+/// # #[macro_use] extern crate mail_headers;
+/// # use mail_headers::{HeaderTryFrom, headers::*, header_components::{Domain, TypedAddress}};
+/// # use mail_core::default_impl::simple_context;
+/// use mail_core::mdn::{Mdn, MdnDisposition};
+/// # fn main() {
+/// # let domain = Domain::from_unchecked("example.com".to_owned());
+/// # let ctx = simple_context::new(domain, "xqi93".parse().unwrap()).unwrap();
+/// # let original_headers = headers! {
+/// #     MessageId: "ab1c@sender.example",
+/// #     DispositionNotificationTo: ["sender@sender.example"]
+/// # }.unwrap();
+/// let final_recipient = TypedAddress::try_from("rfc822;me@example.com").unwrap();
+///
+/// let mdn = Mdn::for_original(&original_headers, final_recipient, MdnDisposition::displayed_manually())
+///     .expect("original mail has a Message-Id")
+///     .with_reporting_ua("example.com; SuperMail 1.0");
+///
+/// let mut mail = mdn.create_mail("Your mail was displayed.", &ctx);
+/// mail.insert_headers(headers! {
+///     _From: ["me@example.com"],
+///     _To: ["sender@sender.example"],
+///     Subject: "Read: Hy there"
+/// }.unwrap());
+/// # let _ = mail;
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mdn {
+    /// The `Message-Id` of the mail this MDN is about.
+    pub original_message_id: MessageId,
+
+    /// The `Original-Recipient` of the mail this MDN is about, if known.
+    pub original_recipient: Option<TypedAddress>,
+
+    /// The recipient of the mail this MDN is about, i.e. the mailbox
+    /// of the user on which behalf the MDN is send.
+    pub final_recipient: TypedAddress,
+
+    /// What happened with the mail.
+    pub disposition: MdnDisposition,
+
+    /// The mail user agent creating this MDN, e.g. `host.example; SuperMail 1.0`.
+    pub reporting_ua: Option<String>,
+
+    /// The headers of the original mail, using `"\r\n"` line endings.
+    pub original_headers: Option<String>
+}
+
+impl Mdn {
+
+    /// Creates a new MDN for the mail with the given `Message-Id`.
+    pub fn new(
+        original_message_id: MessageId,
+        final_recipient: TypedAddress,
+        disposition: MdnDisposition
+    ) -> Self {
+        Mdn {
+            original_message_id,
+            original_recipient: None,
+            final_recipient,
+            disposition,
+            reporting_ua: None,
+            original_headers: None
+        }
+    }
+
+    /// Creates a new MDN taking the `Message-Id` and `Original-Recipient` from the original mail.
+    ///
+    /// Returns `None` if the original mail has no (valid) `Message-Id` header. A invalid
+    /// `Original-Recipient` header is ignored.
+    pub fn for_original(
+        original: &HeaderMap,
+        final_recipient: TypedAddress,
+        disposition: MdnDisposition
+    ) -> Option<Self> {
+        let message_id = original.get_single(MessageIdHeader)?.ok()?;
+        let original_recipient = original.get_single(OriginalRecipient)
+            .and_then(|res| res.ok())
+            .map(|header| header.body().clone());
+
+        let mdn = Mdn::new(message_id.body().clone(), final_recipient, disposition);
+        Some(Mdn { original_recipient, ..mdn })
+    }
+
+    /// Sets the `Original-Recipient` field.
+    pub fn with_original_recipient(mut self, recipient: TypedAddress) -> Self {
+        self.original_recipient = Some(recipient);
+        self
+    }
+
+    /// Sets the `Reporting-UA` field.
+    pub fn with_reporting_ua(mut self, reporting_ua: impl Into<String>) -> Self {
+        self.reporting_ua = Some(reporting_ua.into());
+        self
+    }
+
+    /// Includes the headers of the original mail as last part of the MDN.
+    pub fn with_original_headers(mut self, headers: impl Into<String>) -> Self {
+        self.original_headers = Some(headers.into());
+        self
+    }
+
+    /// Returns the machine readable part of the MDN.
+    ///
+    /// Control characters (e.g. line breaks) in the reporting UA are
+    /// replaced by spaces so that they can not be used to inject fields.
+    pub fn disposition_notification(&self) -> String {
+        let mut out = String::new();
+        if let Some(ref reporting_ua) = self.reporting_ua {
+            let reporting_ua = reporting_ua.chars()
+                .map(|ch| if ch.is_control() { ' ' } else { ch })
+                .collect::<String>();
+            push_field(&mut out, "Reporting-UA", &reporting_ua);
+        }
+        if let Some(ref original_recipient) = self.original_recipient {
+            push_field(&mut out, "Original-Recipient", &original_recipient.to_string());
+        }
+        push_field(&mut out, "Final-Recipient", &self.final_recipient.to_string());
+        push_field(&mut out, "Original-Message-ID",
+            &format!("<{}>", self.original_message_id.as_str()));
+        push_field(&mut out, "Disposition", &self.disposition.to_string());
+        out
+    }
+
+    /// Creates the MDN mail using `Mail::new_multipart_mail`.
+    ///
+    /// If the machine readable part is not ascii (e.g. because of a
+    /// internationalized final recipient) `message/global-disposition-notification`
+    /// (rfc6533) is used instead of `message/disposition-notification`.
+    pub fn create_mail(&self, human_readable: impl Into<String>, ctx: &impl Context) -> Mail {
+        let notification = self.disposition_notification();
+        let notification_type =
+            if notification.is_ascii() {
+                "message/disposition-notification"
+            } else {
+                "message/global-disposition-notification"
+            };
+
+        let mut bodies = vec![
+            Mail::new_singlepart_mail(Resource::plain_text(human_readable, ctx)),
+            new_unencoded_body(notification.into_bytes(), notification_type, ctx)
+        ];
+
+        if let Some(ref headers) = self.original_headers {
+            bodies.push(new_unencoded_body(headers.clone().into_bytes(), "text/rfc822-headers", ctx));
+        }
+
+        //UNWRAP_SAFE: it's a valid media type
+        let content_type = MediaType::new_with_params(
+            MULTIPART, "report", vec![("report-type", "disposition-notification")]
+        ).unwrap();
+
+        Mail::new_multipart_mail(content_type, bodies)
+    }
+}
+
+pub(crate) fn push_field(out: &mut String, name: &str, value: &str) {
+    out.push_str(name);
+    out.push_str(": ");
+    out.push_str(value);
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod test {
+    use headers::{
+        HeaderTryFrom,
+        headers::{ContentType, DispositionNotificationTo},
+        header_components::{MessageId, TransferEncoding}
+    };
+
+    use default_impl::test_context;
+    use mail::MailBody;
+    use super::*;
+
+    fn mdn() -> Mdn {
+        Mdn::new(
+            MessageId::try_from("ab1c@sender.test").unwrap(),
+            TypedAddress::try_from("rfc822;me@recipient.test").unwrap(),
+            MdnDisposition::displayed_manually()
+        )
+    }
+
+    #[test]
+    fn machine_readable_part() {
+        let mdn = mdn()
+            .with_reporting_ua("recipient.test;\r\nInjected: field")
+            .with_original_recipient(TypedAddress::try_from("rfc822;alias@recipient.test").unwrap());
+
+        assert_eq!(mdn.disposition_notification(), concat!(
+            "Reporting-UA: recipient.test;  Injected: field\r\n",
+            "Original-Recipient: rfc822;alias@recipient.test\r\n",
+            "Final-Recipient: rfc822;me@recipient.test\r\n",
+            "Original-Message-ID: <ab1c@sender.test>\r\n",
+            "Disposition: manual-action/MDN-sent-manually; displayed\r\n"
+        ));
+    }
+
+    #[test]
+    fn take_message_id_and_original_recipient_from_original() {
+        let original = headers! {
+            MessageIdHeader: "ab1c@sender.test",
+            OriginalRecipient: "rfc822;alias@recipient.test",
+            DispositionNotificationTo: ["sender@sender.test"]
+        }.unwrap();
+
+        let mdn = Mdn::for_original(
+            &original,
+            TypedAddress::try_from("rfc822;me@recipient.test").unwrap(),
+            MdnDisposition::displayed_manually()
+        ).unwrap();
+
+        assert_eq!(mdn.original_message_id.as_str(), "ab1c@sender.test");
+        assert_eq!(mdn.original_recipient.unwrap().address(), "alias@recipient.test");
+
+        assert!(Mdn::for_original(
+            &HeaderMap::new(),
+            TypedAddress::try_from("rfc822;me@recipient.test").unwrap(),
+            MdnDisposition::displayed_manually()
+        ).is_none());
+    }
+
+    #[test]
+    fn create_report_mail() {
+        let ctx = test_context();
+        let mail = mdn()
+            .with_original_headers("Subject: hy\r\n")
+            .create_mail("displayed", &ctx);
+
+        let content_type = mail.headers().get_single(ContentType).unwrap().unwrap();
+        assert_eq!(content_type.full_type(), "multipart/report");
+        assert_eq!(
+            content_type.get_param("report-type").unwrap().to_content(),
+            "disposition-notification"
+        );
+
+        let bodies = match *mail.body() {
+            MailBody::MultipleBodies { ref bodies, .. } => bodies,
+            _ => panic!("expected multipart mail")
+        };
+        assert_eq!(bodies.len(), 3);
+
+        let media_types = bodies.iter()
+            .map(|body| match *body.body() {
+                MailBody::SingleBody { body: Resource::Data(ref data) } =>
+                    (data.media_type().full_type().to_string(), None),
+                MailBody::SingleBody { body: Resource::EncData(ref enc_data) } =>
+                    (enc_data.media_type().full_type().to_string(), Some(enc_data.encoding())),
+                _ => panic!("expected data body")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(media_types, vec![
+            ("text/plain".to_owned(), None),
+            ("message/disposition-notification".to_owned(), Some(TransferEncoding::_7Bit)),
+            ("text/rfc822-headers".to_owned(), Some(TransferEncoding::_7Bit))
+        ]);
+    }
+}
@@ -0,0 +1,351 @@
+//! This module provides PGP/MIME (rfc3156) signing and encryption of mails.
+//!
+//! A signed mail is a `multipart/signed` mail containing the signed
+//! body and a detached signature (`application/pgp-signature`) over
+//! the canonical encoding of the signed body.
+//!
+//! A encrypted mail is a `multipart/encrypted` mail containing a
+//! `application/pgp-encrypted` version body and the encrypted
+//! canonical encoding of the original body (`application/octet-stream`).
+//!
+//! To sign a mail the signed body is loaded and encoded on it's own
+//! (with `MailType::Ascii`, i.e. 7bit), which is the same way it
+//! will be encoded when encoding the whole mail. Because of this the
+//! sub-bodies of a `multipart/signed` body are not changed when turning
+//! the mail into a `EncodableMail`, e.g. no new boundaries are generated
+//! for them.
+//!
+//! The OpenPGP operations are done by a `OpenPgpBackend`. If the
+//! `pgp-rpgp` feature is enabled a backend based on the pure-Rust
+//! rPGP implementation is provided by the `rpgp` sub-module, other
+//! implementations (e.g. calling out to GnuPG) can be plugged in by
+//! implementing the trait.
+//!
+//! Use `MailParts::compose_with_pgp` to directly compose a protected mail.
+use std::sync::Arc;
+
+use futures::Future;
+use media_type::MULTIPART;
+use vec1::Vec1;
+
+use headers::header_components::{Email, MediaType};
+
+use crate::{
+    context::Context,
+    error::{CryptoError, MailError},
+    mail::{Mail, sign_mail_body, encrypt_mail_body, new_data_body, new_unencoded_body},
+    utils::SendBoxFuture
+};
+
+#[cfg(feature="pgp-rpgp")]
+pub mod rpgp;
+
+/// Trait for providing the OpenPGP operations used for PGP/MIME.
+///
+/// The backend is responsible for looking up the keys for the
+/// given addresses. All returned data has to be ascii armored.
+///
+/// The methods are called using `Context::offload_fn`, so they
+/// can be blocking.
+pub trait OpenPgpBackend: Send + Sync + 'static {
+
+    /// Creates a detached signature of `data` with the key of `signer`.
+    fn sign(&self, signer: &Email, data: &[u8]) -> Result<PgpSignature, CryptoError>;
+
+    /// Encrypts `data` for all `recipients`.
+    ///
+    /// The result is a ascii armored OpenPGP message.
+    fn encrypt(&self, recipients: &[Email], data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+}
+
+/// A detached signature created by a `OpenPgpBackend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgpSignature {
+    /// The used hash algorithm, e.g. `"SHA256"`.
+    ///
+    /// It's used for the `micalg` parameter of the
+    /// `multipart/signed` content type (e.g. `pgp-sha256`).
+    pub hash_algorithm: String,
+
+    /// The ascii armored signature.
+    pub armored: Vec<u8>
+}
+
+/// The kind of PGP/MIME protection applied to a mail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgpProtection {
+    /// Sign the mail with the key of the signer.
+    Sign { signer: Email },
+
+    /// Encrypt the mail for all recipients.
+    ///
+    /// Note that normally the sender should be included in the
+    /// recipients, so that it can still read the send mail.
+    Encrypt { recipients: Vec1<Email> },
+
+    /// Sign the mail and then encrypt the signed mail (rfc3156 section 6.1).
+    SignAndEncrypt { signer: Email, recipients: Vec1<Email> }
+}
+
+/// Applies the given protection to the mail.
+///
+/// See `sign`, `encrypt` and `sign_and_encrypt`.
+pub fn protect<C>(
+    mail: Mail,
+    protection: PgpProtection,
+    backend: Arc<dyn OpenPgpBackend>,
+    ctx: C
+) -> SendBoxFuture<Mail, MailError>
+    where C: Context
+{
+    match protection {
+        PgpProtection::Sign { signer } =>
+            sign(mail, signer, backend, ctx),
+        PgpProtection::Encrypt { recipients } =>
+            encrypt(mail, recipients.into_vec(), backend, ctx),
+        PgpProtection::SignAndEncrypt { signer, recipients } =>
+            sign_and_encrypt(mail, signer, recipients.into_vec(), backend, ctx)
+    }
+}
+
+/// Signs the mail, returning a `multipart/signed` mail.
+///
+/// This loads all resources of the mail and encodes it in
+/// canonical form to sign it. The mail is meant to be a mail
+/// body, i.e. top level headers like `From` or `Subject` should
+/// be added to the returned mail.
+pub fn sign<C>(
+    mail: Mail,
+    signer: Email,
+    backend: Arc<dyn OpenPgpBackend>,
+    ctx: C
+) -> SendBoxFuture<Mail, MailError>
+    where C: Context
+{
+    sign_mail_body(
+        mail, ctx,
+        move |data| backend.sign(&signer, &data),
+        create_signed_mail
+    )
+}
+
+/// Encrypts the mail, returning a `multipart/encrypted` mail.
+///
+/// This loads all resources of the mail and encodes it in
+/// canonical form to encrypt it. The mail is meant to be a mail
+/// body, i.e. top level headers like `From` or `Subject` should
+/// be added to the returned mail.
+pub fn encrypt<C>(
+    mail: Mail,
+    recipients: Vec<Email>,
+    backend: Arc<dyn OpenPgpBackend>,
+    ctx: C
+) -> SendBoxFuture<Mail, MailError>
+    where C: Context
+{
+    encrypt_mail_body(
+        mail, ctx,
+        move |data| backend.encrypt(&recipients, &data),
+        create_encrypted_mail
+    )
+}
+
+/// Signs the mail and then encrypts the signed mail.
+pub fn sign_and_encrypt<C>(
+    mail: Mail,
+    signer: Email,
+    recipients: Vec<Email>,
+    backend: Arc<dyn OpenPgpBackend>,
+    ctx: C
+) -> SendBoxFuture<Mail, MailError>
+    where C: Context
+{
+    let backend2 = backend.clone();
+    let ctx2 = ctx.clone();
+    let fut = sign(mail, signer, backend, ctx)
+        .and_then(move |signed| encrypt(signed, recipients, backend2, ctx2));
+
+    Box::new(fut)
+}
+
+fn create_signed_mail(mail: Mail, signature: PgpSignature, ctx: &impl Context)
+    -> Result<Mail, MailError>
+{
+    let micalg = format!("pgp-{}", signature.hash_algorithm.to_ascii_lowercase());
+    let content_type = MediaType::new_with_params(MULTIPART, "signed", vec![
+        ("protocol", "application/pgp-signature"),
+        ("micalg", micalg.as_str())
+    ])?;
+
+    let signature = new_unencoded_body(signature.armored, "application/pgp-signature", ctx);
+    Ok(Mail::new_multipart_mail(content_type, vec![mail, signature]))
+}
+
+fn create_encrypted_mail(encrypted: Vec<u8>, ctx: &impl Context) -> Mail {
+    //UNWRAP_SAFE: it's a valid media type
+    let content_type = MediaType::new_with_params(MULTIPART, "encrypted", vec![
+        ("protocol", "application/pgp-encrypted")
+    ]).unwrap();
+
+    let bodies = vec![
+        new_unencoded_body(b"Version: 1\r\n".to_vec(), "application/pgp-encrypted", ctx),
+        new_data_body(encrypted, "application/octet-stream", ctx)
+    ];
+    Mail::new_multipart_mail(content_type, bodies)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str;
+    use std::sync::Mutex;
+
+    use futures::Future;
+
+    use internals::MailType;
+    use headers::{
+        HeaderTryFrom, HeaderKind,
+        headers::{_From, Subject, ContentType}
+    };
+
+    use default_impl::test_context;
+    use mail::MailBody;
+    use super::*;
+
+    /// A fake backend recording the data it is called with.
+    #[derive(Default)]
+    struct RecordingBackend {
+        signed: Mutex<Vec<Vec<u8>>>,
+        encrypted: Mutex<Vec<Vec<u8>>>
+    }
+
+    impl OpenPgpBackend for RecordingBackend {
+        fn sign(&self, _signer: &Email, data: &[u8]) -> Result<PgpSignature, CryptoError> {
+            self.signed.lock().unwrap().push(data.to_vec());
+            Ok(PgpSignature {
+                hash_algorithm: "SHA256".to_owned(),
+                armored: b"-----BEGIN PGP SIGNATURE-----\r\n\r\nfake\r\n-----END PGP SIGNATURE-----\r\n".to_vec()
+            })
+        }
+
+        fn encrypt(&self, recipients: &[Email], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+            if recipients.is_empty() {
+                return Err(CryptoError::MissingKey("<no recipients>".to_owned()));
+            }
+            self.encrypted.lock().unwrap().push(data.to_vec());
+            Ok(b"-----BEGIN PGP MESSAGE-----\r\n\r\nfake\r\n-----END PGP MESSAGE-----\r\n".to_vec())
+        }
+    }
+
+    fn email(email: &str) -> Email {
+        Email::try_from(email).unwrap()
+    }
+
+    fn encode(mut mail: Mail) -> String {
+        let ctx = test_context();
+        mail.insert_header(_From::auto_body(["me@example.test"]).unwrap());
+        mail.insert_header(Subject::auto_body("Hy").unwrap());
+        let encodable = mail.into_encodable_mail(ctx).wait().unwrap();
+        let bytes = encodable.encode_into_bytes(MailType::Internationalized).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn signed_body_is_encoded_as_it_was_signed() {
+        let ctx = test_context();
+        let backend = Arc::new(RecordingBackend::default());
+        let body = Mail::plain_text("Hy there ✓", &ctx)
+            .wrap_with_mixed(vec![Mail::plain_text("attachment", &ctx)]);
+
+        let signed = sign(body, email("me@example.test"), backend.clone(), ctx.clone())
+            .wait().unwrap();
+
+        {
+            let content_type = signed.headers().get_single(ContentType).unwrap().unwrap();
+            assert_eq!(content_type.subtype().as_ref(), "signed");
+            assert_eq!(content_type.get_param("protocol").unwrap().to_content(), "application/pgp-signature");
+            assert_eq!(content_type.get_param("micalg").unwrap().to_content(), "pgp-sha256");
+        }
+
+        let encoded = encode(signed);
+        let signed_data = backend.signed.lock().unwrap().pop().unwrap();
+        let signed_data = str::from_utf8(&signed_data).unwrap();
+
+        assert!(signed_data.is_ascii());
+        assert!(signed_data.starts_with("Content-Type: multipart/mixed;"));
+        assert!(!signed_data.ends_with("\r\n"));
+        // the signed data must appear exactly as signed, followed by the next boundary
+        let idx = encoded.find(signed_data).unwrap();
+        assert!(encoded[idx + signed_data.len()..].starts_with("\r\n--"));
+        assert!(encoded.contains(concat!(
+            "Content-Transfer-Encoding: 7bit\r\n",
+            "Content-Type: application/pgp-signature\r\n",
+            "\r\n",
+            "-----BEGIN PGP SIGNATURE-----\r\n"
+        )));
+    }
+
+    #[test]
+    fn encrypt_creates_multipart_encrypted() {
+        let ctx = test_context();
+        let backend = Arc::new(RecordingBackend::default());
+        let body = Mail::plain_text("secret", &ctx);
+
+        let encrypted = encrypt(body, vec![email("you@example.test")], backend.clone(), ctx.clone())
+            .wait().unwrap();
+
+        {
+            let content_type = encrypted.headers().get_single(ContentType).unwrap().unwrap();
+            assert_eq!(content_type.subtype().as_ref(), "encrypted");
+            assert_eq!(content_type.get_param("protocol").unwrap().to_content(), "application/pgp-encrypted");
+        }
+        match *encrypted.body() {
+            MailBody::MultipleBodies { ref bodies, .. } => assert_eq!(bodies.len(), 2),
+            _ => panic!("expected multipart body")
+        }
+
+        let encrypted_data = backend.encrypted.lock().unwrap().pop().unwrap();
+        let encrypted_data = str::from_utf8(&encrypted_data).unwrap();
+        assert!(encrypted_data.contains("\r\nContent-Type: text/plain"));
+        assert!(encrypted_data.ends_with("\r\n"));
+
+        let encoded = encode(encrypted);
+        assert!(encoded.contains(concat!(
+            "Content-Transfer-Encoding: 7bit\r\n",
+            "Content-Type: application/pgp-encrypted\r\n",
+            "\r\n",
+            "Version: 1\r\n"
+        )));
+        assert!(encoded.contains("Content-Type: application/octet-stream"));
+    }
+
+    #[test]
+    fn sign_and_encrypt_encrypts_the_signed_mail() {
+        let ctx = test_context();
+        let backend = Arc::new(RecordingBackend::default());
+        let body = Mail::plain_text("secret", &ctx);
+
+        let protection = PgpProtection::SignAndEncrypt {
+            signer: email("me@example.test"),
+            recipients: Vec1::new(email("you@example.test"))
+        };
+        assert_ok!(protect(body, protection, backend.clone(), ctx).wait());
+
+        let signed_data = backend.signed.lock().unwrap().pop().unwrap();
+        let encrypted_data = backend.encrypted.lock().unwrap().pop().unwrap();
+        let encrypted_data = str::from_utf8(&encrypted_data).unwrap();
+        assert!(encrypted_data.starts_with("Content-Type: multipart/signed;"));
+        assert!(encrypted_data.contains(str::from_utf8(&signed_data).unwrap()));
+    }
+
+    #[test]
+    fn backend_errors_are_returned() {
+        let ctx = test_context();
+        let backend = Arc::new(RecordingBackend::default());
+        let body = Mail::plain_text("secret", &ctx);
+
+        match encrypt(body, Vec::new(), backend, ctx).wait() {
+            Err(MailError::Crypto(CryptoError::MissingKey(_))) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Minimal parsing of received raw mails.
+//!
+//! This is not a full mail parser, it only provides what is needed to
+//! inspect received mails in the `dsn`, `smime` and `arc` modules, i.e.
+//! splitting of the header section and of multipart bodies, parsing of
+//! (unfolded) header fields and decoding of transfer encoded bodies.
+use internals::{
+    bind::{base64, quoted_printable},
+    error::EncodingError
+};
+
+/// Decodes the body of a part based on it's `Content-Transfer-Encoding` field.
+pub(crate) fn transfer_decode(fields: &[(String, String)], body: &[u8])
+    -> Result<Vec<u8>, EncodingError>
+{
+    let transfer_encoding = field(fields, "Content-Transfer-Encoding")
+        .map(|value| value.trim().to_ascii_lowercase());
+    match transfer_encoding.as_deref() {
+        Some("base64") => base64::normal_decode(body),
+        Some("quoted-printable") => quoted_printable::normal_decode(body),
+        _ => Ok(body.to_owned())
+    }
+}
+
+/// Returns the (first) value of the field with given name.
+pub(crate) fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields.iter()
+        .find(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parses header like fields, unfolding folded lines.
+///
+/// Parsing stops at the first empty line.
+pub(crate) fn parse_fields(text: &str) -> Vec<(String, String)> {
+    parse_field_groups(text).into_iter().next().unwrap_or_default()
+}
+
+/// Parses groups of header like fields separated by empty lines.
+///
+/// Lines which are neither fields nor continuation lines are ignored.
+pub(crate) fn parse_field_groups(text: &str) -> Vec<Vec<(String, String)>> {
+    let mut groups = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                groups.push(current);
+                current = Vec::new();
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(&mut (_, ref mut value)) = current.last_mut() {
+                value.push_str(line);
+            }
+        } else if let Some(idx) = line.find(':') {
+            let name = line[..idx].trim().to_owned();
+            let value = line[idx+1..].trim_start().to_owned();
+            current.push((name, value));
+        }
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+    groups
+}
+
+/// Splits a raw mail (or body part) into the header section and the body.
+pub(crate) fn split_header_section(raw: &[u8]) -> (&[u8], &[u8]) {
+    let mut offset = 0;
+    for line in raw.split(|&bch| bch == b'\n') {
+        let line_end = offset + line.len() + 1;
+        if line == b"" || line == b"\r" {
+            return (&raw[..offset], &raw[line_end.min(raw.len())..]);
+        }
+        offset = line_end;
+    }
+    (raw, &[])
+}
+
+/// Splits a multipart body into its parts, ignoring the preamble and epilogue.
+pub(crate) fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let close_delimiter = format!("--{}--", boundary);
+
+    let mut parts = Vec::new();
+    let mut part_start = None;
+    let mut offset = 0;
+    for line in body.split(|&bch| bch == b'\n') {
+        let line_start = offset;
+        offset += line.len() + 1;
+
+        let trimmed = trim_end_whitespace(line);
+        let is_close = trimmed == close_delimiter.as_bytes();
+        if !is_close && trimmed != delimiter.as_bytes() {
+            continue;
+        }
+
+        if let Some(start) = part_start {
+            // the line break before the delimiter belongs to the delimiter
+            let mut part: &[u8] = &body[start..line_start];
+            if part.ends_with(b"\n") {
+                part = &part[..part.len() - 1];
+            }
+            if part.ends_with(b"\r") {
+                part = &part[..part.len() - 1];
+            }
+            parts.push(part);
+        }
+        if is_close {
+            break;
+        }
+        part_start = Some(offset.min(body.len()));
+    }
+    parts
+}
+
+fn trim_end_whitespace(line: &[u8]) -> &[u8] {
+    let end = line.iter()
+        .rposition(|bch| !bch.is_ascii_whitespace())
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    &line[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fields_unfolds_lines() {
+        let fields = parse_fields("Subject: Hy\r\n there\r\nX-Empty:\r\n\r\nBody: no field\r\n");
+        assert_eq!(fields, vec![
+            ("Subject".to_owned(), "Hy there".to_owned()),
+            ("X-Empty".to_owned(), "".to_owned())
+        ]);
+        assert_eq!(field(&fields, "subject"), Some("Hy there"));
+        assert_eq!(field(&fields, "To"), None);
+    }
+
+    #[test]
+    fn split_header_section_and_multipart() {
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=\"=_abc\"\r\n",
+            "\r\n",
+            "preamble\r\n",
+            "--=_abc\r\n",
+            "\r\n",
+            "first\r\n",
+            "\r\n",
+            "--=_abc  \r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "c2Vjb25k\r\n",
+            "--=_abc--\r\n",
+            "epilogue\r\n"
+        ).as_bytes();
+
+        let (header_section, body) = split_header_section(raw);
+        assert_eq!(header_section, &b"Content-Type: multipart/mixed; boundary=\"=_abc\"\r\n"[..]);
+
+        let parts = split_multipart(body, "=_abc");
+        assert_eq!(parts.len(), 2);
+        assert_eq!(split_header_section(parts[0]), (&b""[..], &b"first\r\n"[..]));
+
+        let (header_section, body) = split_header_section(parts[1]);
+        let fields = parse_fields(&String::from_utf8_lossy(header_section));
+        assert_eq!(transfer_decode(&fields, body).unwrap(), b"second".to_vec());
+    }
+}
@@ -11,7 +11,16 @@ use serde::{
     de::{Deserializer}
 };
 
-use internals::bind::{base64, quoted_printable};
+use media_type::CHARSET;
+
+use internals::{
+    bind::{
+        base64, quoted_printable,
+        charset::{self, CharsetConverter, BuiltinCharsets}
+    },
+    error::{EncodingError, US_ASCII}
+};
+use flowed;
 use headers::header_components::{
     MediaType,
     FileMeta,
@@ -114,6 +123,20 @@ impl Data {
         Self::new(buf, meta)
     }
 
+    /// Create a `format=flowed` (rfc3676) plain text instance.
+    ///
+    /// The text is soft-wrapped using `flowed::encode_flowed` and the media type
+    /// is `text/plain; charset=utf-8; format=flowed; delsp=no`.
+    pub fn flowed_text(text: impl AsRef<str>, cid: ContentId) -> Data {
+        let buf = flowed::encode_flowed(text.as_ref()).into_bytes();
+        let meta = Metadata {
+            file_meta: Default::default(),
+            media_type: MediaType::parse("text/plain; charset=utf-8; format=flowed; delsp=no").unwrap(),
+            content_id: cid
+        };
+        Self::new(buf, meta)
+    }
+
     /// Access the raw data buffer of this instance.
     pub fn buffer(&self) -> &Arc<[u8]> {
         &self.buffer
@@ -139,6 +162,68 @@ impl Data {
         &self.meta.content_id
     }
 
+    /// Decodes the text in the buffer into a string.
+    ///
+    /// The `charset` parameter of the media type is used to decode the
+    /// buffer, see `BuiltinCharsets` for the supported charsets. If there
+    /// is no `charset` parameter the buffer is decoded as `utf-8` if it is
+    /// valid `utf-8` and as `us-ascii` (which is decoded like `windows-1252`)
+    /// else.
+    ///
+    /// A `format=flowed` encoding is not undone, use
+    /// `flowed::decode_text_body` on the result for this.
+    ///
+    /// # Error
+    ///
+    /// Fails if the charset is not supported.
+    pub fn decode_text(&self) -> Result<String, EncodingError> {
+        self.decode_text_with(&BuiltinCharsets)
+    }
+
+    /// Decodes the text in the buffer using the given charset converter.
+    ///
+    /// See `decode_text`.
+    pub fn decode_text_with(&self, converter: &dyn CharsetConverter)
+        -> Result<String, EncodingError>
+    {
+        let buffer = &**self.buffer();
+        match self.media_type().get_param(CHARSET) {
+            Some(charset) => converter.decode(&charset.to_content(), buffer),
+            None => match ::std::str::from_utf8(buffer) {
+                Ok(text) => Ok(text.to_owned()),
+                Err(_) => converter.decode(US_ASCII, buffer)
+            }
+        }
+    }
+
+    /// Re-encodes the text in the buffer to the given charset.
+    ///
+    /// The text is decoded with `decode_text_with` and then encoded to the
+    /// new charset. The returned instance has the same metadata except that
+    /// the `charset` parameter of the media type is set to `charset`.
+    ///
+    /// This can be used to send text to recipients which can not handle
+    /// `utf-8` or to turn loaded text in a legacy charset into `utf-8`.
+    ///
+    /// # Error
+    ///
+    /// Fails if one of the charsets is not supported or the text contains
+    /// characters which can not be represented in the new charset.
+    pub fn reencode_text(&self, charset: &str, converter: &dyn CharsetConverter)
+        -> Result<Data, EncodingError>
+    {
+        let text = self.decode_text_with(converter)?;
+        let buffer =
+            if charset::is_utf8(charset) {
+                text.into_bytes()
+            } else {
+                converter.encode(charset, &text)?
+            };
+        let mut meta: Metadata = (**self.metadata()).clone();
+        meta.media_type.set_param(CHARSET, charset);
+        Ok(Data::new(buffer, meta))
+    }
+
     /// Transfer encode the given data.
     ///
     /// This function will be called by the context implementation when
@@ -242,10 +327,11 @@ impl EncData {
 }
 
 /// Hint to change how data should be transfer encoded.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
 pub enum TransferEncodingHint {
     /// Use Base64 encoding.
+    #[default]
     UseBase64,
 
     /// Use Quoted-Printable encoding.
@@ -267,12 +353,6 @@ pub enum TransferEncodingHint {
     __NonExhaustive { }
 }
 
-impl Default for TransferEncodingHint {
-    fn default() -> Self {
-        TransferEncodingHint::UseBase64
-    }
-}
-
 /// Transfer encodes Data.
 ///
 /// Util we have a reasonable "non latin letter text" heuristic
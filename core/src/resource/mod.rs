@@ -182,6 +182,13 @@ impl Resource {
         Resource::Data(Data::plain_text(content, ctx.generate_content_id()))
     }
 
+    /// Creates a new `format=flowed` text `Resource` (see `Data::flowed_text`).
+    ///
+    /// The `Context` is used to generate a `ContentId`.
+    pub fn flowed_text(content: impl AsRef<str>, ctx: &impl Context) -> Resource {
+        Resource::Data(Data::flowed_text(content, ctx.generate_content_id()))
+    }
+
     /// Return the content id, if there is any.
     pub fn content_id(&self) -> Option<&ContentId> {
         match self {
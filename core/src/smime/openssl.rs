@@ -0,0 +1,266 @@
+//! A `SmimeBackend` implementation using OpenSSL (feature `smime-openssl`).
+use openssl_crate::{
+    error::ErrorStack,
+    pkcs12::Pkcs12,
+    pkcs7::{Pkcs7, Pkcs7Flags},
+    pkey::{PKey, Private},
+    stack::Stack,
+    symm::Cipher,
+    x509::{
+        X509,
+        store::{X509Store, X509StoreBuilder}
+    }
+};
+
+use crate::error::CryptoError;
+use super::{Certificate, PrivateKey, SmimeBackend, SmimeIdentity, SmimeSignature};
+
+/// A `SmimeBackend` using OpenSSL's PKCS#7 implementation.
+///
+/// Signatures are created using SHA-256 and data is encrypted using AES-256-CBC.
+pub struct OpensslSmimeBackend {
+    trust_store: X509Store
+}
+
+impl OpensslSmimeBackend {
+
+    /// Creates a backend only trusting the given (CA) certificates when verifying signatures.
+    pub fn new(trusted: &[Certificate]) -> Result<Self, CryptoError> {
+        Self::create(trusted, false)
+    }
+
+    /// Creates a backend trusting the given certificates and the default system CAs.
+    pub fn with_system_trust(trusted: &[Certificate]) -> Result<Self, CryptoError> {
+        Self::create(trusted, true)
+    }
+
+    fn create(trusted: &[Certificate], use_system_trust: bool) -> Result<Self, CryptoError> {
+        let mut builder = X509StoreBuilder::new().map_err(backend_error)?;
+        if use_system_trust {
+            builder.set_default_paths().map_err(backend_error)?;
+        }
+        for certificate in trusted {
+            builder.add_cert(parse_certificate(certificate)?).map_err(backend_error)?;
+        }
+        Ok(OpensslSmimeBackend { trust_store: builder.build() })
+    }
+}
+
+impl SmimeBackend for OpensslSmimeBackend {
+
+    fn sign(&self, identity: &SmimeIdentity, data: &[u8]) -> Result<SmimeSignature, CryptoError> {
+        let certificate = parse_certificate(&identity.certificate)?;
+        let key = parse_private_key(&identity.key)?;
+        let chain = certificate_stack(&identity.chain)?;
+
+        let flags = Pkcs7Flags::DETACHED | Pkcs7Flags::BINARY;
+        let signature = Pkcs7::sign(&certificate, &key, &chain, data, flags)
+            .and_then(|signature| signature.to_der())
+            .map_err(backend_error)?;
+
+        Ok(SmimeSignature { micalg: "sha-256".to_owned(), der: signature })
+    }
+
+    fn encrypt(&self, recipients: &[Certificate], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let recipients = certificate_stack(recipients)?;
+        Pkcs7::encrypt(&recipients, data, Cipher::aes_256_cbc(), Pkcs7Flags::BINARY)
+            .and_then(|enveloped| enveloped.to_der())
+            .map_err(backend_error)
+    }
+
+    fn verify(&self, data: &[u8], signature: &[u8]) -> Result<Vec<Certificate>, CryptoError> {
+        let signature = Pkcs7::from_der(signature)
+            .map_err(|err| CryptoError::InvalidSignature(err.into()))?;
+        let no_certs = Stack::new().map_err(backend_error)?;
+
+        signature.verify(&no_certs, &self.trust_store, Some(data), None, Pkcs7Flags::BINARY)
+            .map_err(|err| CryptoError::InvalidSignature(err.into()))?;
+
+        let signers = signature.signers(&no_certs, Pkcs7Flags::empty()).map_err(backend_error)?;
+        signers.iter()
+            .map(|signer| signer.to_der().map(Certificate::from_der).map_err(backend_error))
+            .collect()
+    }
+
+    fn decrypt(&self, identity: &SmimeIdentity, enveloped: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let certificate = parse_certificate(&identity.certificate)?;
+        let key = parse_private_key(&identity.key)?;
+        Pkcs7::from_der(enveloped)
+            .and_then(|enveloped| enveloped.decrypt(&key, &certificate, Pkcs7Flags::BINARY))
+            .map_err(backend_error)
+    }
+
+    fn parse_pkcs12(&self, der: &[u8], password: &str) -> Result<SmimeIdentity, CryptoError> {
+        let parsed = Pkcs12::from_der(der)
+            .and_then(|pkcs12| pkcs12.parse2(password))
+            .map_err(|err| CryptoError::MalformedKeyMaterial(err.to_string()))?;
+
+        let missing = |what: &str| CryptoError::MalformedKeyMaterial(format!("no {} in PKCS#12", what));
+        let certificate = parsed.cert.ok_or_else(|| missing("certificate"))?;
+        let key = parsed.pkey.ok_or_else(|| missing("private key"))?;
+
+        let mut chain = Vec::new();
+        if let Some(ca) = parsed.ca {
+            for certificate in &ca {
+                chain.push(Certificate::from_der(certificate.to_der().map_err(backend_error)?));
+            }
+        }
+
+        Ok(SmimeIdentity {
+            certificate: Certificate::from_der(certificate.to_der().map_err(backend_error)?),
+            chain,
+            key: PrivateKey::from_der(key.private_key_to_der().map_err(backend_error)?)
+        })
+    }
+}
+
+fn backend_error(err: ErrorStack) -> CryptoError {
+    CryptoError::Backend(err.into())
+}
+
+fn parse_certificate(certificate: &Certificate) -> Result<X509, CryptoError> {
+    X509::from_der(certificate.as_der())
+        .map_err(|err| CryptoError::MalformedKeyMaterial(err.to_string()))
+}
+
+fn parse_private_key(key: &PrivateKey) -> Result<PKey<Private>, CryptoError> {
+    PKey::private_key_from_der(key.as_der())
+        .map_err(|err| CryptoError::MalformedKeyMaterial(err.to_string()))
+}
+
+fn certificate_stack(certificates: &[Certificate]) -> Result<Stack<X509>, CryptoError> {
+    let mut stack = Stack::new().map_err(backend_error)?;
+    for certificate in certificates {
+        stack.push(parse_certificate(certificate)?).map_err(backend_error)?;
+    }
+    Ok(stack)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{slice, str};
+    use std::sync::Arc;
+
+    use futures::Future;
+    use openssl_crate::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        hash::MessageDigest,
+        pkcs12::Pkcs12,
+        rsa::Rsa,
+        x509::{X509Builder, X509NameBuilder}
+    };
+
+    use internals::MailType;
+    use headers::{
+        HeaderKind,
+        headers::{_From, Subject}
+    };
+
+    use default_impl::test_context;
+    use error::SmimeError;
+    use mail::Mail;
+    use smime::{self, SmimeProtection};
+    use vec1::Vec1;
+    use super::*;
+
+    fn self_signed(common_name: &str) -> (X509, PKey<Private>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (builder.build(), key)
+    }
+
+    fn identity(certificate: &X509, key: &PKey<Private>) -> SmimeIdentity {
+        let cert_pem = String::from_utf8(certificate.to_pem().unwrap()).unwrap();
+        let key_pem = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        SmimeIdentity::from_pem(&cert_pem, &key_pem).unwrap()
+    }
+
+    fn encode(mut mail: Mail) -> Vec<u8> {
+        let ctx = test_context();
+        mail.insert_header(_From::auto_body(["me@example.test"]).unwrap());
+        mail.insert_header(Subject::auto_body("Hy").unwrap());
+        let encodable = mail.into_encodable_mail(ctx).wait().unwrap();
+        encodable.encode_into_bytes(MailType::Ascii).unwrap()
+    }
+
+    #[test]
+    fn sign_encrypt_decrypt_and_verify() {
+        let ctx = test_context();
+        let (certificate, key) = self_signed("me@example.test");
+        let identity = identity(&certificate, &key);
+        let backend = Arc::new(OpensslSmimeBackend::new(slice::from_ref(&identity.certificate)).unwrap());
+
+        let body = Mail::plain_text("Hy there ✓", &ctx)
+            .wrap_with_mixed(vec![Mail::plain_text("attachment", &ctx)]);
+        let protection = SmimeProtection::SignAndEncrypt {
+            identity: identity.clone(),
+            recipients: Vec1::new(identity.certificate.clone())
+        };
+        let encrypted = encode(smime::protect(body, protection, backend.clone(), ctx).wait().unwrap());
+
+        let decrypted = assert_ok!(smime::decrypt(&encrypted, &identity, &*backend));
+        assert!(str::from_utf8(&decrypted).unwrap().starts_with("Content-Type: multipart/signed;"));
+
+        let verified = assert_ok!(smime::verify(&decrypted, &*backend));
+        assert_eq!(verified.signers, vec![identity.certificate.clone()]);
+        assert!(str::from_utf8(&verified.content).unwrap().starts_with("Content-Type: multipart/mixed;"));
+    }
+
+    #[test]
+    fn tampered_and_untrusted_signatures_are_rejected() {
+        let ctx = test_context();
+        let (certificate, key) = self_signed("me@example.test");
+        let identity = identity(&certificate, &key);
+        let backend = Arc::new(OpensslSmimeBackend::new(slice::from_ref(&identity.certificate)).unwrap());
+
+        let signed = smime::sign(Mail::plain_text("Hy there", &ctx), identity, backend.clone(), ctx)
+            .wait().unwrap();
+        let encoded = encode(signed);
+        assert_ok!(smime::verify(&encoded, &*backend));
+
+        let untrusting = OpensslSmimeBackend::new(&[]).unwrap();
+        match smime::verify(&encoded, &untrusting) {
+            Err(SmimeError::Crypto(CryptoError::InvalidSignature(_))) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        // base64 of "Hy there" starts with "SHkg", change it to "Hy!"
+        let text = String::from_utf8(encoded).unwrap().replacen("SHkgdGhlcmU=", "SHkhdGhlcmU=", 1);
+        match smime::verify(text.as_bytes(), &*backend) {
+            Err(SmimeError::Crypto(CryptoError::InvalidSignature(_))) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn parse_pkcs12() {
+        let (certificate, key) = self_signed("me@example.test");
+        let pkcs12 = Pkcs12::builder()
+            .name("me")
+            .pkey(&key)
+            .cert(&certificate)
+            .build2("secret")
+            .unwrap()
+            .to_der()
+            .unwrap();
+
+        let backend = OpensslSmimeBackend::new(&[]).unwrap();
+        let identity = assert_ok!(backend.parse_pkcs12(&pkcs12, "secret"));
+        assert_eq!(identity.certificate.as_der(), &*certificate.to_der().unwrap());
+        assert_err!(backend.parse_pkcs12(&pkcs12, "wrong"));
+    }
+}
@@ -78,7 +78,10 @@ pub enum BuildInValidationError {
     ResentDateFieldMissing,
 
     #[fail(display = "Resent-From field in resent block without a Resent-Sender field")]
-    MultiMailboxResentFromWithoutResentSender
+    MultiMailboxResentFromWithoutResentSender,
+
+    #[fail(display = "List-Unsubscribe-Post field without a https uri in a List-Unsubscribe field")]
+    ListUnsubscribePostWithoutHttpsUri
 }
 
 macro_rules! header_validation_bail {
@@ -0,0 +1,183 @@
+use std::slice;
+
+use soft_ascii_string::SoftAsciiChar;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+
+use super::{Mailbox, Phrase};
+
+/// The display name used by `Address::undisclosed_recipients`.
+const UNDISCLOSED_RECIPIENTS: &str = "undisclosed-recipients";
+
+/// An address as used in e.g. the `To`/`Cc`/`Bcc`/`Reply-To` headers (rfc5322).
+///
+/// An address is either a single mailbox or a named group of mailboxes,
+/// e.g. `Team: a@example.com, b@example.com;`. Groups can be empty, which
+/// is commonly used to not disclose the recipients of a mail, e.g.
+/// `To: undisclosed-recipients:;`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub enum Address {
+    Mailbox(Mailbox),
+    Group {
+        display_name: Phrase,
+        members: Vec<Mailbox>
+    }
+}
+
+impl Address {
+
+    /// Creates a group address with given display name and members.
+    pub fn group<P, I>(display_name: P, members: I) -> Result<Self, ComponentCreationError>
+        where P: HeaderTryInto<Phrase>, I: IntoIterator, I::Item: HeaderTryInto<Mailbox>
+    {
+        let display_name = display_name.try_into()?;
+        let mut out = Vec::new();
+        for member in members {
+            out.push(member.try_into()?);
+        }
+        Ok(Address::Group { display_name, members: out })
+    }
+
+    /// Creates the empty group `undisclosed-recipients:;`.
+    pub fn undisclosed_recipients() -> Self {
+        Address::Group {
+            //UNWRAP_SAFE: it's a valid phrase
+            display_name: Phrase::new(UNDISCLOSED_RECIPIENTS).unwrap(),
+            members: Vec::new()
+        }
+    }
+
+    /// Returns true if this is a group address.
+    pub fn is_group(&self) -> bool {
+        match *self {
+            Address::Mailbox(_) => false,
+            Address::Group { .. } => true
+        }
+    }
+
+    /// Returns the mailboxes of this address.
+    ///
+    /// For a mailbox address this is the mailbox itself, for a group
+    /// address it's the members of the group (which might be none).
+    pub fn mailboxes(&self) -> &[Mailbox] {
+        match *self {
+            Address::Mailbox(ref mailbox) => slice::from_ref(mailbox),
+            Address::Group { ref members, .. } => members
+        }
+    }
+}
+
+impl From<Mailbox> for Address {
+    fn from(mailbox: Mailbox) -> Self {
+        Address::Mailbox(mailbox)
+    }
+}
+
+impl<M> HeaderTryFrom<M> for Address
+    where M: HeaderTryInto<Mailbox>
+{
+    fn try_from(mailbox: M) -> Result<Self, ComponentCreationError> {
+        Ok(Address::Mailbox(mailbox.try_into()?))
+    }
+}
+
+impl EncodableInHeader for  Address {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        match *self {
+            Address::Mailbox(ref mailbox) => mailbox.encode(handle),
+            Address::Group { ref display_name, ref members } => {
+                display_name.encode(handle)?;
+                handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+                sep_for!{ mailbox in members.iter();
+                    sep {
+                        handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                    };
+                    handle.write_fws();
+                    mailbox.encode(handle)?;
+                }
+                handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use ::header_components::{Email, Phrase};
+    use super::*;
+
+    ec_test!{ mailbox, {
+        Address::try_from("affen@haus")?
+    } => ascii => [
+        Text "<",
+        MarkFWS,
+        Text "affen",
+        MarkFWS,
+        Text "@",
+        MarkFWS,
+        Text "haus",
+        MarkFWS,
+        Text ">"
+    ]}
+
+    ec_test!{ empty_group, {
+        Address::undisclosed_recipients()
+    } => ascii => [
+        Text "undisclosed-recipients:;"
+    ]}
+
+    ec_test!{ group, {
+        Address::Group {
+            display_name: Phrase::try_from("Team")?,
+            members: vec![
+                Mailbox::from(Email::try_from("a@x")?),
+                Mailbox::from(Email::try_from("b@x")?)
+            ]
+        }
+    } => ascii => [
+        Text "Team:",
+        MarkFWS,
+        Text " <",
+        MarkFWS,
+        Text "a",
+        MarkFWS,
+        Text "@",
+        MarkFWS,
+        Text "x",
+        MarkFWS,
+        Text ">,",
+        MarkFWS,
+        Text " <",
+        MarkFWS,
+        Text "b",
+        MarkFWS,
+        Text "@",
+        MarkFWS,
+        Text "x",
+        MarkFWS,
+        Text ">;"
+    ]}
+
+    #[test]
+    fn mailboxes_of_group_and_mailbox() {
+        let single = Address::try_from("a@x").unwrap();
+        assert!(!single.is_group());
+        assert_eq!(single.mailboxes().len(), 1);
+
+        let group = Address::group("Team", vec!["a@x", "b@x"]).unwrap();
+        assert!(group.is_group());
+        assert_eq!(group.mailboxes().len(), 2);
+
+        assert!(Address::undisclosed_recipients().mailboxes().is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+use std::iter::IntoIterator;
+use vec1::Vec1;
+use soft_ascii_string::SoftAsciiChar;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::{ HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+
+use super::{Address, Mailbox};
+
+/// A list of addresses as used in e.g. the `To`/`Cc`/`Bcc`/`Reply-To` headers (rfc5322).
+///
+/// Each address is either a mailbox or a (potentially empty) group of mailboxes.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct AddressList( pub Vec1<Address> );
+
+impl AddressList {
+    pub fn from_single( address: Address ) -> Self {
+        AddressList( Vec1::new( address ) )
+    }
+
+    /// Iterates over all mailboxes in this list, including the members of groups.
+    pub fn mailboxes(&self) -> impl Iterator<Item=&Mailbox> {
+        self.0.iter().flat_map(|address| address.mailboxes())
+    }
+}
+
+impl IntoIterator for AddressList {
+    type Item = <Vec1<Address> as IntoIterator>::Item;
+    type IntoIter = <Vec1<Address> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for AddressList
+    where T: HeaderTryInto<Address>
+{
+    fn try_from(vec: Vec<T>) -> Result<Self, ComponentCreationError> {
+        try_from_into_iter( vec )
+    }
+}
+
+fn try_from_into_iter<IT>( addresses: IT ) -> Result<AddressList, ComponentCreationError>
+    where IT: IntoIterator, IT::Item: HeaderTryInto<Address>
+{
+    let mut iter = addresses.into_iter();
+    let mut vec = if let Some( first) = iter.next() {
+        Vec1::new( first.try_into()? )
+    } else {
+        return Err(ComponentCreationError::new("AddressList"));
+    };
+    for address in iter {
+        vec.push( address.try_into()? );
+    }
+    Ok( AddressList( vec ) )
+}
+
+macro_rules! impl_header_try_from_array {
+    ($($len:tt)*) => ($(
+        impl<T> HeaderTryFrom<[T; $len]> for AddressList
+            where T: HeaderTryInto<Address>
+        {
+            fn try_from( vec: [T; $len] ) -> Result<Self, ComponentCreationError> {
+                //due to only supporting arrays halfheartedly for now
+                let heapified: Box<[T]> = Box::new(vec);
+                let vecified: Vec<_> = heapified.into();
+                try_from_into_iter( vecified )
+            }
+        }
+    )*);
+}
+
+impl_header_try_from_array! {
+        1  2  3  4  5  6  7  8  9
+    10 11 12 13 14 15 16 17 18 19
+    20 21 22 23 24 25 26 27 28 29
+    30 31 32
+}
+
+macro_rules! impl_header_try_from_tuple {
+    (_AddressList [ $($vs:ident),* ]) => (
+        impl< $($vs),* > HeaderTryFrom<( $($vs,)* )> for AddressList
+            where $($vs: HeaderTryInto<Address>),*
+        {
+            #[allow(non_snake_case)]
+            fn try_from( ($($vs,)*): ($($vs,)*) ) -> Result<Self, ComponentCreationError> {
+                // we use the type names as variable names,
+                // not nice but it works
+                let mut out = Vec::new();
+                $(
+                    let $vs = $vs.try_into()?;
+                    out.push($vs);
+                )*
+                Ok( AddressList(
+                    //UNWRAP_SAFE: len 0 is not implemented with the macro
+                    $crate::vec1::Vec1::try_from_vec(out).unwrap()
+                ) )
+            }
+        }
+    );
+    ([]) => ();
+    ([$first_vs:ident $(, $vs:ident)*]) => (
+        impl_header_try_from_tuple!{ _AddressList [$first_vs $(, $vs)* ] }
+        impl_header_try_from_tuple!{ [$($vs),*] }
+    );
+}
+
+impl_header_try_from_tuple! {
+    [
+        A0,  A1,  A2,  A3,  A4,  A5,  A6,  A7,
+        A8,  A9,  A10, A11, A12, A13, A14, A15,
+        A16, A17, A18, A19, A20, A21, A22, A23,
+        A24, A25, A26, A27, A28, A29, A30, A31
+    ]
+}
+
+impl HeaderTryFrom<Address> for AddressList {
+    fn try_from(address: Address) -> Result<Self, ComponentCreationError> {
+        Ok( AddressList::from_single( address ) )
+    }
+}
+
+impl EncodableInHeader for  AddressList {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        sep_for!{ address in self.0.iter();
+            sep {
+                handle.write_char( SoftAsciiChar::from_unchecked(',') )?;
+                handle.write_fws();
+            };
+            address.encode( handle )?;
+        }
+        Ok( () )
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+deref0!{ +mut AddressList => Vec1<Address> }
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use ::header_components::Email;
+    use super::*;
+
+    ec_test! { mailbox_and_group, {
+        AddressList::try_from((
+            "a@x",
+            Address::group("Team", vec!["b@x"])?
+        ))?
+    } => ascii => [
+        Text "<",
+        MarkFWS,
+        Text "a",
+        MarkFWS,
+        Text "@",
+        MarkFWS,
+        Text "x",
+        MarkFWS,
+        Text ">,",
+        MarkFWS,
+        Text " Team:",
+        MarkFWS,
+        Text " <",
+        MarkFWS,
+        Text "b",
+        MarkFWS,
+        Text "@",
+        MarkFWS,
+        Text "x",
+        MarkFWS,
+        Text ">;"
+    ]}
+
+    ec_test! { undisclosed_recipients, {
+        AddressList::from_single( Address::undisclosed_recipients() )
+    } => ascii => [
+        Text "undisclosed-recipients:;"
+    ]}
+
+    #[test]
+    fn mailboxes_expands_groups() {
+        let list = AddressList::try_from([
+            Address::try_from("a@x").unwrap(),
+            Address::undisclosed_recipients(),
+            Address::group("Team", vec!["b@x", "c@x"]).unwrap()
+        ]).unwrap();
+
+        let emails = list.mailboxes()
+            .map(|mailbox| mailbox.email.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(emails, vec![
+            Email::try_from("a@x").unwrap(),
+            Email::try_from("b@x").unwrap(),
+            Email::try_from("c@x").unwrap()
+        ]);
+    }
+
+    #[test]
+    fn empty_list_is_rejected() {
+        let res = AddressList::try_from(Vec::<Address>::new());
+        assert_err!(res);
+    }
+}
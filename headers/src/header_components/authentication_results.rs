@@ -0,0 +1,682 @@
+//! Components for the `Authentication-Results` (rfc8601) and
+//! `ARC-Authentication-Results` (rfc8617) headers.
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+use quoted_string;
+
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::grammar::{is_ctl, is_token, is_token_char};
+use internals::bind::quoted_string::{MailQsSpec, InternationalizedMailQsSpec};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+/// The body of the `Authentication-Results` header (rfc8601).
+///
+/// It's used by a receiving server (identified by the `authserv_id`)
+/// to report the results of message authentication checks like SPF,
+/// DKIM or DMARC, e.g.:
+///
+/// ```text
+/// mx.example.com 1; spf=pass smtp.mailfrom=example.net;
+///  dkim=pass reason="good signature" header.d=example.net
+/// ```
+///
+/// If `results` is empty it's encoded as `<authserv-id>; none`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// use mail_headers::HeaderTryFrom;
+/// use mail_headers::header_components::{AuthenticationResults, MethodResult};
+///
+/// # fn main() {
+/// let results = AuthenticationResults::new("mx.example.com")
+///     .with_result(MethodResult::new("spf", "pass")
+///         .with_property("smtp", "mailfrom", "example.net"));
+///
+/// let parsed = AuthenticationResults::try_from(
+///     "mx.example.com; spf=pass (sender is authorized) smtp.mailfrom=example.net"
+/// ).unwrap();
+///
+/// assert_eq!(parsed, results);
+/// assert!(parsed.method("SPF").unwrap().result_is("pass"));
+/// # }
+/// ```
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct AuthenticationResults {
+    /// The identifier of the server which did the checks.
+    pub authserv_id: String,
+    /// The version of the header format, `None` means version 1.
+    pub version: Option<u32>,
+    /// The results of the checks.
+    pub results: Vec<MethodResult>
+}
+
+impl AuthenticationResults {
+
+    pub fn new<I>(authserv_id: I) -> Self
+        where I: Into<String>
+    {
+        AuthenticationResults {
+            authserv_id: authserv_id.into(),
+            version: None,
+            results: Vec::new()
+        }
+    }
+
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn with_result(mut self, result: MethodResult) -> Self {
+        self.results.push(result);
+        self
+    }
+
+    /// Returns the first result for given method, the method name is compared case insensitive.
+    pub fn method(&self, method: &str) -> Option<&MethodResult> {
+        self.results.iter()
+            .find(|result| result.method.eq_ignore_ascii_case(method))
+    }
+}
+
+/// The result of a single authentication method, e.g. `dkim=pass header.d=example.net`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct MethodResult {
+    /// The method, e.g. `spf`, `dkim` or `dmarc`.
+    pub method: String,
+    /// The version of the method, `None` means version 1.
+    pub method_version: Option<u32>,
+    /// The result, e.g. `pass`, `fail` or `none`.
+    pub result: String,
+    /// A human readable reason for the result.
+    pub reason: Option<String>,
+    /// Additional `ptype.property=value` information, e.g. `header.d=example.net`.
+    pub properties: Vec<ResultProperty>
+}
+
+impl MethodResult {
+
+    pub fn new<M, R>(method: M, result: R) -> Self
+        where M: Into<String>, R: Into<String>
+    {
+        MethodResult {
+            method: method.into(),
+            method_version: None,
+            result: result.into(),
+            reason: None,
+            properties: Vec::new()
+        }
+    }
+
+    pub fn with_method_version(mut self, version: u32) -> Self {
+        self.method_version = Some(version);
+        self
+    }
+
+    pub fn with_reason<R>(mut self, reason: R) -> Self
+        where R: Into<String>
+    {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_property<T, P, V>(mut self, ptype: T, property: P, value: V) -> Self
+        where T: Into<String>, P: Into<String>, V: Into<String>
+    {
+        self.properties.push(ResultProperty {
+            ptype: ptype.into(),
+            property: property.into(),
+            value: value.into()
+        });
+        self
+    }
+
+    /// Compares the result case insensitive with given result, e.g. `"pass"`.
+    pub fn result_is(&self, result: &str) -> bool {
+        self.result.eq_ignore_ascii_case(result)
+    }
+
+    /// Returns the value of the first property with given ptype and property name.
+    pub fn property(&self, ptype: &str, property: &str) -> Option<&str> {
+        self.properties.iter()
+            .find(|prop| {
+                prop.ptype.eq_ignore_ascii_case(ptype)
+                    && prop.property.eq_ignore_ascii_case(property)
+            })
+            .map(|prop| prop.value.as_str())
+    }
+}
+
+/// A `ptype.property=value` pair of a `MethodResult`, e.g. `smtp.mailfrom=example.net`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ResultProperty {
+    /// The property type, e.g. `smtp`, `header`, `body` or `policy`.
+    pub ptype: String,
+    /// The property, e.g. `mailfrom` or `d`.
+    pub property: String,
+    /// The value, e.g. `example.net`.
+    pub value: String
+}
+
+/// The body of the `ARC-Authentication-Results` header (rfc8617).
+///
+/// It's a `Authentication-Results` header prefixed with the
+/// instance number of the ARC set it belongs to, e.g.
+/// `i=1; mx.example.com; spf=pass smtp.mailfrom=example.net`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ArcAuthenticationResults {
+    /// The instance (`i=`) of the ARC set, starting with 1.
+    pub instance: u32,
+    pub results: AuthenticationResults
+}
+
+impl ArcAuthenticationResults {
+    pub fn new(instance: u32, results: AuthenticationResults) -> Self {
+        ArcAuthenticationResults { instance, results }
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AuthenticationResults {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let err = || ComponentCreationError::new_with_str("AuthenticationResults", value);
+        let stripped = strip_comments(value).ok_or_else(err)?;
+        let segments = split_segments(&stripped).ok_or_else(err)?;
+        parse_authentication_results(&segments).ok_or_else(err)
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ArcAuthenticationResults {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let err = || ComponentCreationError::new_with_str("ArcAuthenticationResults", value);
+        let stripped = strip_comments(value).ok_or_else(err)?;
+        let segments = split_segments(&stripped).ok_or_else(err)?;
+
+        let instance = {
+            let mut parser = Parser::new(&segments[0]);
+            parser.skip_ws();
+            let name = parser.keyword().ok_or_else(err)?;
+            parser.skip_ws();
+            if name != "i" || !parser.eat('=') {
+                return Err(err());
+            }
+            parser.skip_ws();
+            let instance = parser.number().ok_or_else(err)?;
+            parser.skip_ws();
+            if !parser.is_empty() {
+                return Err(err());
+            }
+            instance
+        };
+
+        let results = parse_authentication_results(&segments[1..]).ok_or_else(err)?;
+        Ok(ArcAuthenticationResults { instance, results })
+    }
+}
+
+/// Replaces all comments with a space, returns `None` if they are not balanced.
+fn strip_comments(value: &str) -> Option<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            out.push(ch);
+            match ch {
+                '\\' => out.push(chars.next()?),
+                '"' => in_quotes = false,
+                _ => {}
+            }
+        } else if depth > 0 {
+            match ch {
+                '\\' => { chars.next()?; },
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        } else {
+            match ch {
+                '(' => {
+                    depth += 1;
+                    out.push(' ');
+                },
+                ')' => return None,
+                '"' => {
+                    in_quotes = true;
+                    out.push(ch);
+                },
+                _ => out.push(ch)
+            }
+        }
+    }
+    if depth > 0 || in_quotes {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Splits the (comment free) value at all `';'` which are not quoted.
+fn split_segments(value: &str) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ';' if !in_quotes => {
+                segments.push(current);
+                current = String::new();
+                continue;
+            },
+            '\\' if in_quotes => {
+                current.push(ch);
+                current.push(chars.next()?);
+                continue;
+            },
+            '"' => in_quotes = !in_quotes,
+            _ => {}
+        }
+        current.push(ch);
+    }
+    segments.push(current);
+    Some(segments)
+}
+
+fn parse_authentication_results(segments: &[String]) -> Option<AuthenticationResults> {
+    let (first, rest) = segments.split_first()?;
+
+    let mut parser = Parser::new(first);
+    parser.skip_ws();
+    let authserv_id = parser.value()?;
+    parser.skip_ws();
+    let version =
+        if parser.is_empty() { None }
+        else { Some(parser.number()?) };
+    parser.skip_ws();
+    if !parser.is_empty() {
+        return None;
+    }
+
+    let mut results = Vec::new();
+    let is_no_result = rest.len() == 1 && rest[0].trim().eq_ignore_ascii_case("none");
+    if !is_no_result {
+        for segment in rest {
+            results.push(parse_method_result(segment)?);
+        }
+    }
+
+    Some(AuthenticationResults { authserv_id, version, results })
+}
+
+fn parse_method_result(segment: &str) -> Option<MethodResult> {
+    let mut parser = Parser::new(segment);
+    parser.skip_ws();
+    let method = parser.keyword()?.to_owned();
+    parser.skip_ws();
+    let method_version =
+        if parser.eat('/') {
+            parser.skip_ws();
+            let version = parser.number()?;
+            parser.skip_ws();
+            Some(version)
+        } else {
+            None
+        };
+    if !parser.eat('=') {
+        return None;
+    }
+    parser.skip_ws();
+    let result = parser.keyword()?.to_owned();
+
+    let mut method_result = MethodResult {
+        method, method_version, result,
+        reason: None,
+        properties: Vec::new()
+    };
+
+    loop {
+        parser.skip_ws();
+        if parser.is_empty() {
+            break;
+        }
+        let name = parser.keyword()?.to_owned();
+        parser.skip_ws();
+        if name.eq_ignore_ascii_case("reason") && method_result.properties.is_empty() {
+            if !parser.eat('=') {
+                return None;
+            }
+            parser.skip_ws();
+            method_result.reason = Some(parser.value()?);
+        } else {
+            if !parser.eat('.') {
+                return None;
+            }
+            parser.skip_ws();
+            let property = parser.keyword()?.to_owned();
+            parser.skip_ws();
+            if !parser.eat('=') {
+                return None;
+            }
+            parser.skip_ws();
+            let value = parser.value()?;
+            method_result.properties.push(ResultProperty { ptype: name, property, value });
+        }
+    }
+
+    Some(method_result)
+}
+
+/// A minimal parser for a single (comment free) segment of the header.
+struct Parser<'a> {
+    input: &'a str
+}
+
+impl<'a> Parser<'a> {
+
+    fn new(input: &'a str) -> Self {
+        Parser { input }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    fn skip_ws(&mut self) {
+        self.input = self.input.trim_start();
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.input.starts_with(ch) {
+            self.input = &self.input[ch.len_utf8()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_while<F>(&mut self, pred: F) -> Option<&'a str>
+        where F: Fn(char) -> bool
+    {
+        let end = self.input.find(|ch: char| !pred(ch))
+            .unwrap_or(self.input.len());
+        if end == 0 {
+            return None;
+        }
+        let (taken, rest) = self.input.split_at(end);
+        self.input = rest;
+        Some(taken)
+    }
+
+    fn keyword(&mut self) -> Option<&'a str> {
+        self.take_while(is_keyword_char)
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        self.take_while(|ch| ch.is_ascii_digit())?
+            .parse().ok()
+    }
+
+    /// Parses a quoted string or a unquoted value (a token, domain or `local-part@domain`).
+    fn value(&mut self) -> Option<String> {
+        if self.eat('"') {
+            let mut out = String::new();
+            let mut chars = self.input.char_indices();
+            while let Some((idx, ch)) = chars.next() {
+                match ch {
+                    '"' => {
+                        self.input = &self.input[idx+1..];
+                        return Some(out);
+                    },
+                    '\\' => out.push(chars.next()?.1),
+                    '\r' | '\n' => {},
+                    ch => out.push(ch)
+                }
+            }
+            None
+        } else {
+            self.take_while(|ch| !ch.is_whitespace() && !is_ctl(ch) && ch != '"')
+                .map(|value| value.to_owned())
+        }
+    }
+}
+
+fn is_keyword_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'
+}
+
+fn is_keyword(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(is_keyword_char)
+}
+
+fn write_keyword(keyword: &str, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    if !is_keyword(keyword) {
+        return Err(EncodingError::from(EncodingErrorKind::Malformed)
+            .with_str_context(keyword));
+    }
+    handle.write_str(SoftAsciiStr::from_unchecked(keyword))
+}
+
+/// Writes a value as token, quoting it if necessary.
+///
+/// If `allow_at` is true a `'@'` doesn't require quoting, which is
+/// used for property values like `smtp.mailfrom=user@example.com`.
+fn write_value(value: &str, allow_at: bool, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    let is_unquoted =
+        if allow_at {
+            !value.is_empty() && value.chars().all(|ch| is_token_char(ch) || ch == '@')
+        } else {
+            is_token(value)
+        };
+
+    if is_unquoted {
+        return handle.write_str(SoftAsciiStr::from_unchecked(value));
+    }
+
+    let res =
+        if handle.mail_type().is_internationalized() {
+            quoted_string::quote::<InternationalizedMailQsSpec>(value)
+        } else {
+            quoted_string::quote::<MailQsSpec>(value)
+        };
+    let quoted = res.map_err(|_err| {
+        EncodingError::from(EncodingErrorKind::Malformed)
+            .with_str_context(value)
+    })?;
+    handle.write_str_unchecked(&quoted)
+}
+
+fn write_number(number: u32, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    handle.write_str(&SoftAsciiString::from_unchecked(number.to_string()))
+}
+
+fn write_method_result(result: &MethodResult, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    write_keyword(&result.method, handle)?;
+    if let Some(version) = result.method_version {
+        handle.write_char(SoftAsciiChar::from_unchecked('/'))?;
+        write_number(version, handle)?;
+    }
+    handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+    write_keyword(&result.result, handle)?;
+
+    if let Some(ref reason) = result.reason {
+        handle.write_fws();
+        handle.write_str(SoftAsciiStr::from_unchecked("reason="))?;
+        write_value(reason, false, handle)?;
+    }
+
+    for prop in result.properties.iter() {
+        handle.write_fws();
+        write_keyword(&prop.ptype, handle)?;
+        handle.write_char(SoftAsciiChar::from_unchecked('.'))?;
+        write_keyword(&prop.property, handle)?;
+        handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+        write_value(&prop.value, true, handle)?;
+    }
+    Ok(())
+}
+
+impl EncodableInHeader for  AuthenticationResults {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        write_value(&self.authserv_id, false, handle)?;
+        if let Some(version) = self.version {
+            handle.write_fws();
+            write_number(version, handle)?;
+        }
+
+        if self.results.is_empty() {
+            handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+            handle.write_fws();
+            handle.write_str(SoftAsciiStr::from_unchecked("none"))?;
+        }
+
+        for result in self.results.iter() {
+            handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+            handle.write_fws();
+            write_method_result(result, handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl EncodableInHeader for  ArcAuthenticationResults {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked("i="))?;
+        write_number(self.instance, handle)?;
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        self.results.encode(handle)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use super::*;
+
+    ec_test!{ no_results, {
+        AuthenticationResults::new("mx.example.com")
+    } => ascii => [
+        Text "mx.example.com;",
+        MarkFWS,
+        Text " none"
+    ]}
+
+    ec_test!{ with_results, {
+        AuthenticationResults::new("mx.example.com")
+            .with_version(1)
+            .with_result(MethodResult::new("spf", "pass")
+                .with_property("smtp", "mailfrom", "user@example.net"))
+            .with_result(MethodResult::new("dkim", "fail")
+                .with_reason("bad signature")
+                .with_property("header", "d", "example.net"))
+    } => ascii => [
+        Text "mx.example.com",
+        MarkFWS,
+        Text " 1;",
+        MarkFWS,
+        Text " spf=pass",
+        MarkFWS,
+        Text " smtp.mailfrom=user@example.net;",
+        MarkFWS,
+        Text " dkim=fail",
+        MarkFWS,
+        Text " reason=\"bad signature\"",
+        MarkFWS,
+        Text " header.d=example.net"
+    ]}
+
+    ec_test!{ arc, {
+        ArcAuthenticationResults::new(2, AuthenticationResults::new("mx.example.com")
+            .with_result(MethodResult::new("dmarc", "pass")))
+    } => ascii => [
+        Text "i=2;",
+        MarkFWS,
+        Text " mx.example.com;",
+        MarkFWS,
+        Text " dmarc=pass"
+    ]}
+
+    #[test]
+    fn encoding_invalid_method_fails() {
+        let results = AuthenticationResults::new("mx.example.com")
+            .with_result(MethodResult::new("sp f", "pass"));
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            assert_err!(results.encode(&mut handle));
+            handle.undo_header();
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let parsed = assert_ok!(AuthenticationResults::try_from(concat!(
+            "mx.example.com 1; (checked by the relay)\r\n",
+            " spf=pass (sender ip is 192.0.2.1) smtp.mailfrom=user@example.net;\r\n",
+            " dkim/1 = fail reason=\"bad \\\"signature\\\"\" header.d=example.net header.s=sel;\r\n",
+            " dmarc=pass policy.published-domain-policy=none"
+        )));
+
+        assert_eq!(parsed.authserv_id, "mx.example.com");
+        assert_eq!(parsed.version, Some(1));
+        assert_eq!(parsed.results.len(), 3);
+
+        let spf = parsed.method("spf").unwrap();
+        assert!(spf.result_is("pass"));
+        assert_eq!(spf.property("smtp", "mailfrom"), Some("user@example.net"));
+
+        let dkim = parsed.method("dkim").unwrap();
+        assert_eq!(dkim.method_version, Some(1));
+        assert!(dkim.result_is("fail"));
+        assert_eq!(dkim.reason.as_deref(), Some("bad \"signature\""));
+        assert_eq!(dkim.property("header", "d"), Some("example.net"));
+        assert_eq!(dkim.property("header", "s"), Some("sel"));
+
+        let dmarc = parsed.method("dmarc").unwrap();
+        assert_eq!(dmarc.property("policy", "published-domain-policy"), Some("none"));
+    }
+
+    #[test]
+    fn parse_none() {
+        let parsed = assert_ok!(AuthenticationResults::try_from("\"mx.example.com\"; none"));
+        assert_eq!(parsed, AuthenticationResults::new("mx.example.com"));
+    }
+
+    #[test]
+    fn parse_arc() {
+        let parsed = assert_ok!(ArcAuthenticationResults::try_from(
+            "i=1; mx.example.com; spf=pass smtp.mailfrom=example.net"));
+        assert_eq!(parsed.instance, 1);
+        assert_eq!(parsed.results.authserv_id, "mx.example.com");
+        assert!(parsed.results.method("spf").unwrap().result_is("pass"));
+
+        assert_err!(ArcAuthenticationResults::try_from("mx.example.com; spf=pass"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_err!(AuthenticationResults::try_from(""));
+        assert_err!(AuthenticationResults::try_from("mx.example.com; spf"));
+        assert_err!(AuthenticationResults::try_from("mx.example.com; spf=pass smtp"));
+        assert_err!(AuthenticationResults::try_from("mx.example.com; spf=pass (unclosed"));
+        assert_err!(AuthenticationResults::try_from("mx.example.com; spf=pass reason=\"unclosed"));
+        assert_err!(AuthenticationResults::try_from("mx.example.com version; spf=pass"));
+    }
+}
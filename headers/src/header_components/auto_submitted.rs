@@ -0,0 +1,290 @@
+use std::str::FromStr;
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+use vec1::Vec1;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+
+/// The body of the `Auto-Submitted` header (rfc3834, rfc5436).
+///
+/// Automatic responders (e.g. vacation responders) should not
+/// respond to mails with any value except `No`.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum AutoSubmitted {
+    /// The mail was created by a human.
+    No,
+    /// The mail was generated automatically, e.g. a notification.
+    AutoGenerated,
+    /// The mail is a automatic reply to another mail.
+    AutoReplied,
+    /// The mail is a automatic notification (rfc5436).
+    AutoNotified
+}
+
+impl AutoSubmitted {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AutoSubmitted::No => "no",
+            AutoSubmitted::AutoGenerated => "auto-generated",
+            AutoSubmitted::AutoReplied => "auto-replied",
+            AutoSubmitted::AutoNotified => "auto-notified"
+        }
+    }
+}
+
+impl FromStr for AutoSubmitted {
+    type Err = ComponentCreationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        Ok(if value.eq_ignore_ascii_case("no") {
+            AutoSubmitted::No
+        } else if value.eq_ignore_ascii_case("auto-generated") {
+            AutoSubmitted::AutoGenerated
+        } else if value.eq_ignore_ascii_case("auto-replied") {
+            AutoSubmitted::AutoReplied
+        } else if value.eq_ignore_ascii_case("auto-notified") {
+            AutoSubmitted::AutoNotified
+        } else {
+            return Err(ComponentCreationError::new_with_str("AutoSubmitted", value));
+        })
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutoSubmitted {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.parse()
+    }
+}
+
+impl EncodableInHeader for  AutoSubmitted {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// The body of the (non-standard but widely used) `Precedence` header (rfc2076).
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Precedence {
+    Bulk,
+    List,
+    Junk
+}
+
+impl Precedence {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Precedence::Bulk => "bulk",
+            Precedence::List => "list",
+            Precedence::Junk => "junk"
+        }
+    }
+}
+
+impl FromStr for Precedence {
+    type Err = ComponentCreationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        Ok(if value.eq_ignore_ascii_case("bulk") {
+            Precedence::Bulk
+        } else if value.eq_ignore_ascii_case("list") {
+            Precedence::List
+        } else if value.eq_ignore_ascii_case("junk") {
+            Precedence::Junk
+        } else {
+            return Err(ComponentCreationError::new_with_str("Precedence", value));
+        })
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Precedence {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.parse()
+    }
+}
+
+impl EncodableInHeader for  Precedence {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// A kind of automatic response which can be suppressed with `X-Auto-Response-Suppress`.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum AutoResponseKind {
+    /// Suppress no automatic responses (`None`).
+    Nothing,
+    /// Suppress all automatic responses (`All`).
+    All,
+    /// Delivery reports (`DR`).
+    Dr,
+    /// Non-delivery reports (`NDR`).
+    Ndr,
+    /// Read notifications (`RN`).
+    Rn,
+    /// Non-read notifications (`NRN`).
+    Nrn,
+    /// Out of office replies (`OOF`).
+    Oof,
+    /// Automatic replies other then out of office replies (`AutoReply`).
+    AutoReply
+}
+
+impl AutoResponseKind {
+    pub fn as_str(&self) -> &'static str {
+        use self::AutoResponseKind::*;
+        match *self {
+            Nothing => "None",
+            All => "All",
+            Dr => "DR",
+            Ndr => "NDR",
+            Rn => "RN",
+            Nrn => "NRN",
+            Oof => "OOF",
+            AutoReply => "AutoReply"
+        }
+    }
+}
+
+impl FromStr for AutoResponseKind {
+    type Err = ComponentCreationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        use self::AutoResponseKind::*;
+        let value = value.trim();
+        for &kind in &[Nothing, All, Dr, Ndr, Rn, Nrn, Oof, AutoReply] {
+            if value.eq_ignore_ascii_case(kind.as_str()) {
+                return Ok(kind);
+            }
+        }
+        Err(ComponentCreationError::new_with_str("AutoResponseKind", value))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutoResponseKind {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.parse()
+    }
+}
+
+/// The body of the (Microsoft specific) `X-Auto-Response-Suppress` header.
+///
+/// It's a comma separated list of the kinds of automatic responses which
+/// should not be send in response to the mail, e.g. `DR, OOF`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct AutoResponseSuppress(pub Vec1<AutoResponseKind>);
+
+impl AutoResponseSuppress {
+
+    /// Suppresses all automatic responses.
+    pub fn all() -> Self {
+        AutoResponseSuppress(Vec1::new(AutoResponseKind::All))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutoResponseSuppress {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let kinds = value.split(',')
+            .map(|kind| kind.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        //UNWRAP_SAFE: split always returns at last one element
+        Ok(AutoResponseSuppress(Vec1::try_from_vec(kinds).unwrap()))
+    }
+}
+
+impl HeaderTryFrom<AutoResponseKind> for AutoResponseSuppress {
+    fn try_from(kind: AutoResponseKind) -> Result<Self, ComponentCreationError> {
+        Ok(AutoResponseSuppress(Vec1::new(kind)))
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for AutoResponseSuppress
+    where T: HeaderTryInto<AutoResponseKind>
+{
+    fn try_from(kinds: Vec<T>) -> Result<Self, ComponentCreationError> {
+        let mut out = Vec::new();
+        for kind in kinds {
+            out.push(kind.try_into()?);
+        }
+        let out = Vec1::try_from_vec(out)
+            .map_err(|_| ComponentCreationError::new("AutoResponseSuppress"))?;
+        Ok(AutoResponseSuppress(out))
+    }
+}
+
+impl EncodableInHeader for  AutoResponseSuppress {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        sep_for!{ kind in self.0.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            handle.write_str(SoftAsciiStr::from_unchecked(kind.as_str()))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+deref0!{ +mut AutoResponseSuppress => Vec1<AutoResponseKind> }
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    ec_test!{ auto_generated, {
+        AutoSubmitted::AutoGenerated
+    } => ascii => [
+        Text "auto-generated"
+    ]}
+
+    #[test]
+    fn parse_auto_submitted() {
+        assert_eq!(assert_ok!(AutoSubmitted::try_from("Auto-Replied")), AutoSubmitted::AutoReplied);
+        assert_eq!(assert_ok!(AutoSubmitted::try_from(" no ")), AutoSubmitted::No);
+        assert_err!(AutoSubmitted::try_from("yes"));
+    }
+
+    ec_test!{ precedence, {
+        Precedence::try_from("BULK")?
+    } => ascii => [
+        Text "bulk"
+    ]}
+
+    ec_test!{ auto_response_suppress, {
+        AutoResponseSuppress::try_from("dr, oof,AutoReply")?
+    } => ascii => [
+        Text "DR,",
+        MarkFWS,
+        Text " OOF,",
+        MarkFWS,
+        Text " AutoReply"
+    ]}
+
+    #[test]
+    fn parse_auto_response_suppress() {
+        let suppress = assert_ok!(AutoResponseSuppress::try_from("None"));
+        assert_eq!(suppress.first(), &AutoResponseKind::Nothing);
+        assert_err!(AutoResponseSuppress::try_from("DR, Vacation"));
+        assert_err!(AutoResponseSuppress::try_from(""));
+    }
+}
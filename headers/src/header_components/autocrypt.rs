@@ -0,0 +1,280 @@
+//! Components for the `Autocrypt` and `Autocrypt-Gossip` headers (Autocrypt Level 1).
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::bind::base64;
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+use super::Email;
+
+/// The number of keydata bytes encoded per line, i.e. 64 base64 chars.
+const KEYDATA_CHUNK_LEN: usize = 48;
+
+/// The encryption preference of the sender of a `Autocrypt` header.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub enum PreferEncrypt {
+    /// The sender prefers to receive encrypted mails (`mutual`).
+    Mutual,
+    /// The sender has no preference, used if the attribute is omitted.
+    #[default]
+    NoPreference
+}
+
+/// The body of the `Autocrypt` header.
+///
+/// It contains the (OpenPGP) public key of the sender of the mail,
+/// which can be used to opportunistically encrypt mails send to
+/// the sender. The key is treated as opaque bytes.
+///
+/// `Autocrypt` headers can be parsed from their header value, e.g.
+/// `addr=me@example.com; prefer-encrypt=mutual; keydata=mQGiBE...`,
+/// so that inbound mails can be used to update a key store.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Autocrypt {
+    /// The address the key is for, it has to match the `From` address.
+    pub addr: Email,
+    /// The encryption preference of the sender.
+    pub prefer_encrypt: PreferEncrypt,
+    /// The (binary, not ascii armored) public key.
+    pub keydata: Vec<u8>
+}
+
+impl Autocrypt {
+
+    pub fn new(addr: Email, keydata: Vec<u8>) -> Self {
+        Autocrypt { addr, prefer_encrypt: PreferEncrypt::NoPreference, keydata }
+    }
+
+    pub fn with_prefer_encrypt(mut self, prefer_encrypt: PreferEncrypt) -> Self {
+        self.prefer_encrypt = prefer_encrypt;
+        self
+    }
+}
+
+/// The body of the `Autocrypt-Gossip` header.
+///
+/// It's placed in the encrypted part of a mail send to multiple
+/// recipients to share the keys of all recipients with each other.
+/// It uses the same format as the `Autocrypt` header, except that
+/// it has no `prefer-encrypt` attribute.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct AutocryptGossip {
+    /// The address of the recipient the key is for.
+    pub addr: Email,
+    /// The (binary, not ascii armored) public key.
+    pub keydata: Vec<u8>
+}
+
+impl AutocryptGossip {
+    pub fn new(addr: Email, keydata: Vec<u8>) -> Self {
+        AutocryptGossip { addr, keydata }
+    }
+}
+
+impl From<Autocrypt> for AutocryptGossip {
+    fn from(autocrypt: Autocrypt) -> Self {
+        AutocryptGossip { addr: autocrypt.addr, keydata: autocrypt.keydata }
+    }
+}
+
+/// The attributes of a parsed `Autocrypt`/`Autocrypt-Gossip` header.
+struct Attributes {
+    addr: Email,
+    prefer_encrypt: Option<PreferEncrypt>,
+    keydata: Vec<u8>
+}
+
+/// Parses the attributes of a `Autocrypt`/`Autocrypt-Gossip` header.
+///
+/// Unknown attributes starting with `'_'` are ignored, any other unknown
+/// attribute makes the header invalid.
+fn parse_attributes(component: &'static str, value: &str) -> Result<Attributes, ComponentCreationError> {
+    let mut addr = None;
+    let mut prefer_encrypt = None;
+    let mut keydata = None;
+
+    for attribute in value.split(';') {
+        let attribute = attribute.trim();
+        if attribute.is_empty() {
+            continue;
+        }
+        let eq_idx = attribute.find('=')
+            .ok_or_else(|| ComponentCreationError::new_with_str(component, attribute))?;
+        let name = attribute[..eq_idx].trim();
+        let value = attribute[eq_idx+1..].trim();
+
+        match name {
+            "addr" if addr.is_none() => {
+                addr = Some(Email::try_from(value)?);
+            },
+            "prefer-encrypt" if prefer_encrypt.is_none() => {
+                prefer_encrypt = Some(match value {
+                    "mutual" => PreferEncrypt::Mutual,
+                    "nopreference" => PreferEncrypt::NoPreference,
+                    _ => return Err(ComponentCreationError::new_with_str(component, attribute))
+                });
+            },
+            "keydata" if keydata.is_none() => {
+                let stripped = value.chars()
+                    .filter(|ch| !ch.is_whitespace())
+                    .collect::<String>();
+                let decoded = base64::normal_decode(stripped)
+                    .map_err(|_| ComponentCreationError::new_with_str(component, "keydata=<malformed>"))?;
+                keydata = Some(decoded);
+            },
+            name if name.starts_with('_') => {},
+            _ => return Err(ComponentCreationError::new_with_str(component, attribute))
+        }
+    }
+
+    match (addr, keydata) {
+        (Some(addr), Some(keydata)) if !keydata.is_empty() =>
+            Ok(Attributes { addr, prefer_encrypt, keydata }),
+        _ => Err(ComponentCreationError::new_with_str(component, "missing addr or keydata"))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Autocrypt {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let Attributes { addr, prefer_encrypt, keydata } = parse_attributes("Autocrypt", value)?;
+        Ok(Autocrypt {
+            addr,
+            prefer_encrypt: prefer_encrypt.unwrap_or_default(),
+            keydata
+        })
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutocryptGossip {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let Attributes { addr, prefer_encrypt, keydata } = parse_attributes("AutocryptGossip", value)?;
+        if prefer_encrypt.is_some() {
+            return Err(ComponentCreationError::new_with_str("AutocryptGossip", "prefer-encrypt"));
+        }
+        Ok(AutocryptGossip { addr, keydata })
+    }
+}
+
+fn encode_addr(addr: &Email, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    handle.write_str(SoftAsciiStr::from_unchecked("addr="))?;
+    let addr = format!("{}@{}", addr.local_part.as_str(), addr.domain.as_str());
+    if addr.is_ascii() {
+        handle.write_str(SoftAsciiStr::from_unchecked(&addr))?;
+    } else {
+        handle.write_utf8(&addr)?;
+    }
+    handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+    handle.write_fws();
+    Ok(())
+}
+
+/// Writes the keydata as base64, with folding positions between chunks.
+fn encode_keydata(keydata: &[u8], handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    handle.write_str(SoftAsciiStr::from_unchecked("keydata="))?;
+    // each chunk is encoded on it's own, as the encoded chunks are
+    // short enough to not contain any line breaks
+    sep_for!{ chunk in keydata.chunks(KEYDATA_CHUNK_LEN);
+        sep { handle.write_fws(); };
+        handle.write_str(&base64::normal_encode(chunk))?;
+    }
+    Ok(())
+}
+
+impl EncodableInHeader for  Autocrypt {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        encode_addr(&self.addr, handle)?;
+        if self.prefer_encrypt == PreferEncrypt::Mutual {
+            handle.write_str(SoftAsciiStr::from_unchecked("prefer-encrypt=mutual;"))?;
+            handle.write_fws();
+        }
+        encode_keydata(&self.keydata, handle)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl EncodableInHeader for  AutocryptGossip {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        encode_addr(&self.addr, handle)?;
+        encode_keydata(&self.keydata, handle)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use super::*;
+
+    ec_test!{ autocrypt, {
+        Autocrypt::new(Email::try_from("me@example.test")?, b"key".to_vec())
+            .with_prefer_encrypt(PreferEncrypt::Mutual)
+    } => ascii => [
+        Text "addr=me@example.test;",
+        MarkFWS,
+        Text " prefer-encrypt=mutual;",
+        MarkFWS,
+        Text " keydata=a2V5"
+    ]}
+
+    ec_test!{ gossip, {
+        AutocryptGossip::new(Email::try_from("you@example.test")?, b"key".to_vec())
+    } => ascii => [
+        Text "addr=you@example.test;",
+        MarkFWS,
+        Text " keydata=a2V5"
+    ]}
+
+    #[test]
+    fn long_keydata_is_folded_and_parses_again() {
+        let keydata = (0..1000u32).map(|idx| (idx % 251) as u8).collect::<Vec<_>>();
+        let autocrypt = Autocrypt::new(Email::try_from("me@example.test").unwrap(), keydata);
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            autocrypt.encode(&mut handle).unwrap();
+            handle.commit_partial_header();
+        }
+        let encoded = encoder.as_str().unwrap().to_owned();
+        assert!(encoded.lines().count() > 1);
+        assert!(encoded.lines().all(|line| line.len() <= 78));
+
+        let parsed = assert_ok!(Autocrypt::try_from(encoded.as_str()));
+        assert_eq!(parsed, autocrypt);
+    }
+
+    #[test]
+    fn parse() {
+        let autocrypt = assert_ok!(Autocrypt::try_from(
+            "addr=me@example.test; _ignored=yes; keydata=a2V5\r\n a2V5"));
+        assert_eq!(autocrypt.prefer_encrypt, PreferEncrypt::NoPreference);
+        assert_eq!(autocrypt.keydata, b"keykey".to_vec());
+
+        let autocrypt = assert_ok!(Autocrypt::try_from(
+            "addr=me@example.test; prefer-encrypt=mutual; keydata=a2V5"));
+        assert_eq!(autocrypt.prefer_encrypt, PreferEncrypt::Mutual);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_err!(Autocrypt::try_from("addr=me@example.test"));
+        assert_err!(Autocrypt::try_from("keydata=a2V5"));
+        assert_err!(Autocrypt::try_from("addr=me@example.test; critical=yes; keydata=a2V5"));
+        assert_err!(Autocrypt::try_from("addr=me@example.test; prefer-encrypt=always; keydata=a2V5"));
+        assert_err!(Autocrypt::try_from("addr=me@example.test; keydata=!!!"));
+        assert_err!(AutocryptGossip::try_from(
+            "addr=me@example.test; prefer-encrypt=mutual; keydata=a2V5"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use soft_ascii_string::SoftAsciiString;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+/// The body of the `Content-Duration` header (rfc3803).
+///
+/// It's the time it takes to play a time based media (e.g. a voice
+/// message) in seconds. Sub-second precision is not supported by the
+/// header, so durations are truncated to full seconds.
+///
+/// The header value is limited to 10 digits, so durations longer than
+/// `ContentDuration::MAX_SECS` can not be represented and are rejected
+/// by the `HeaderTryFrom` implementations.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub struct ContentDuration(u64);
+
+impl ContentDuration {
+
+    /// The longest duration (in seconds) which fits into the 10 digits of the header.
+    pub const MAX_SECS: u64 = 9_999_999_999;
+
+    pub fn as_secs(&self) -> u64 {
+        self.0
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_secs(self.0)
+    }
+}
+
+impl HeaderTryFrom<Duration> for ContentDuration {
+    fn try_from(duration: Duration) -> Result<Self, ComponentCreationError> {
+        ContentDuration::try_from(duration.as_secs())
+    }
+}
+
+impl HeaderTryFrom<u64> for ContentDuration {
+    fn try_from(secs: u64) -> Result<Self, ComponentCreationError> {
+        if secs > ContentDuration::MAX_SECS {
+            return Err(ComponentCreationError::new_with_str("ContentDuration", secs.to_string()));
+        }
+        Ok(ContentDuration(secs))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ContentDuration {
+    fn try_from(secs: &'a str) -> Result<Self, ComponentCreationError> {
+        let trimmed = secs.trim();
+        // rfc3803 limits the value to 10 digits
+        if trimmed.is_empty() || trimmed.len() > 10 || !trimmed.bytes().all(|bch| bch.is_ascii_digit()) {
+            return Err(ComponentCreationError::new_with_str("ContentDuration", secs));
+        }
+        //UNWRAP_SAFE: at most 10 digits always fit into u64
+        Ok(ContentDuration(trimmed.parse().unwrap()))
+    }
+}
+
+impl EncodableInHeader for  ContentDuration {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(&SoftAsciiString::from_unchecked(self.0.to_string()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ::HeaderTryFrom;
+    use super::*;
+
+    ec_test!{ encode_secs, {
+        ContentDuration::try_from(Duration::from_millis(33_900)).unwrap()
+    } => ascii => [
+        Text "33"
+    ]}
+
+    #[test]
+    fn parse() {
+        assert_eq!(assert_ok!(ContentDuration::try_from(" 33 ")).as_secs(), 33);
+        assert_err!(ContentDuration::try_from("33s"));
+        assert_err!(ContentDuration::try_from("-1"));
+        assert_err!(ContentDuration::try_from("12345678901"));
+    }
+
+    #[test]
+    fn limited_to_ten_digits() {
+        let max = assert_ok!(ContentDuration::try_from(ContentDuration::MAX_SECS));
+        assert_eq!(max.as_secs(), 9_999_999_999);
+        assert_err!(ContentDuration::try_from(ContentDuration::MAX_SECS + 1));
+        assert_err!(ContentDuration::try_from(Duration::from_secs(10_000_000_000)));
+    }
+}
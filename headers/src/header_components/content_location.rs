@@ -0,0 +1,100 @@
+use soft_ascii_string::SoftAsciiString;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::grammar::is_ascii_vchar;
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+/// The body of the `Content-Location` header (rfc2557).
+///
+/// It's a absolute or relative URI identifying the body it's
+/// used with. It's mainly used for html bodies so that relative
+/// URIs in them can be resolved, and to refer to embedded resources
+/// by URI instead of by content id.
+///
+/// The URI is validated to be us-ascii only and to not contain
+/// whitespace or `'<'`/`'>'`. Non-ascii characters have to be
+/// percent encoded.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ContentLocation(SoftAsciiString);
+
+impl ContentLocation {
+
+    /// Creates a new content location, validating it.
+    pub fn new(uri: &str) -> Result<Self, ComponentCreationError> {
+        let valid = !uri.is_empty()
+            && uri.chars().all(|ch| is_ascii_vchar(ch) && ch != '<' && ch != '>');
+
+        if !valid {
+            return Err(ComponentCreationError::new_with_str("ContentLocation", uri));
+        }
+        Ok(ContentLocation(SoftAsciiString::from_unchecked(uri)))
+    }
+
+    /// Returns true if the URI is absolute, i.e. starts with a scheme like `https:`.
+    pub fn is_absolute(&self) -> bool {
+        let uri = self.as_str();
+        match uri.find(':') {
+            Some(idx) if idx > 0 => uri[..idx].chars().enumerate().all(|(idx, ch)| {
+                ch.is_ascii_alphabetic()
+                    || (idx > 0 && (ch.is_ascii_digit() || ch == '+' || ch == '-' || ch == '.'))
+            }),
+            _ => false
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ContentLocation {
+    fn try_from(uri: &'a str) -> Result<Self, ComponentCreationError> {
+        ContentLocation::new(uri)
+    }
+}
+
+impl HeaderTryFrom<String> for ContentLocation {
+    fn try_from(uri: String) -> Result<Self, ComponentCreationError> {
+        ContentLocation::new(&uri)
+    }
+}
+
+impl EncodableInHeader for  ContentLocation {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(&self.0)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    ec_test!{ absolute, {
+        ContentLocation::try_from("https://example.test/news/index.html")?
+    } => ascii => [
+        Text "https://example.test/news/index.html"
+    ]}
+
+    #[test]
+    fn absolute_and_relative() {
+        assert!(ContentLocation::new("https://example.test/").unwrap().is_absolute());
+        assert!(!ContentLocation::new("images/logo.png").unwrap().is_absolute());
+        assert!(!ContentLocation::new("./a:b").unwrap().is_absolute());
+    }
+
+    #[test]
+    fn invalid() {
+        assert_err!(ContentLocation::new(""));
+        assert_err!(ContentLocation::new("images/my logo.png"));
+        assert_err!(ContentLocation::new("<https://example.test>"));
+        assert_err!(ContentLocation::new("https://exämple.test"));
+    }
+}
@@ -0,0 +1,96 @@
+use soft_ascii_string::SoftAsciiStr;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::bind::base64;
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+/// The body of the `Content-MD5` header (rfc1864).
+///
+/// It contains the MD5 digest of the body (before transfer encoding)
+/// and is encoded as base64. This crate does not compute the digest
+/// itself.
+///
+/// Note that it can only be used to detect accidental modifications,
+/// it does not provide any security against intentional modifications.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct ContentMd5(pub [u8; 16]);
+
+impl ContentMd5 {
+
+    /// Parses the base64 encoded digest as used in the header.
+    pub fn from_base64(encoded: &str) -> Result<Self, ComponentCreationError> {
+        let create_error = || ComponentCreationError::new_with_str("ContentMd5", encoded);
+        let decoded = base64::normal_decode(encoded.trim())
+            .map_err(|_| create_error())?;
+
+        if decoded.len() != 16 {
+            return Err(create_error());
+        }
+        let mut digest = [0u8; 16];
+        digest.copy_from_slice(&decoded);
+        Ok(ContentMd5(digest))
+    }
+
+    pub fn digest(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl From<[u8; 16]> for ContentMd5 {
+    fn from(digest: [u8; 16]) -> Self {
+        ContentMd5(digest)
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ContentMd5 {
+    fn try_from(encoded: &'a str) -> Result<Self, ComponentCreationError> {
+        ContentMd5::from_base64(encoded)
+    }
+}
+
+impl HeaderTryFrom<[u8; 16]> for ContentMd5 {
+    fn try_from(digest: [u8; 16]) -> Result<Self, ComponentCreationError> {
+        Ok(ContentMd5(digest))
+    }
+}
+
+impl EncodableInHeader for  ContentMd5 {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let encoded = base64::normal_encode(self.0);
+        handle.write_str(SoftAsciiStr::from_unchecked(encoded.as_str().trim_end()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    // md5("Check Integrity!")
+    const DIGEST: [u8; 16] = [
+        0x9f, 0x0a, 0xaa, 0xe9, 0xbe, 0xae, 0x6b, 0xfb,
+        0x53, 0x0e, 0x4e, 0xc1, 0xe4, 0xcd, 0x7c, 0xe3
+    ];
+
+    ec_test!{ encode_digest, {
+        ContentMd5::from(DIGEST)
+    } => ascii => [
+        Text "nwqq6b6ua/tTDk7B5M184w=="
+    ]}
+
+    #[test]
+    fn parse() {
+        let md5 = assert_ok!(ContentMd5::try_from("nwqq6b6ua/tTDk7B5M184w=="));
+        assert_eq!(md5.digest(), &DIGEST);
+
+        assert_err!(ContentMd5::try_from("X00+al7uzk+q"));
+        assert_err!(ContentMd5::try_from("not base64!"));
+    }
+}
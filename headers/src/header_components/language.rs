@@ -0,0 +1,303 @@
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+use vec1::Vec1;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+
+/// Irregular grandfathered tags which do not match the normal language tag syntax (rfc5646).
+const IRREGULAR_GRANDFATHERED: &[&str] = &[
+    "en-GB-oed", "i-ami", "i-bnn", "i-default", "i-enochian", "i-hak",
+    "i-klingon", "i-lux", "i-mingo", "i-navajo", "i-pwn", "i-tao",
+    "i-tay", "i-tsu", "sgn-BE-FR", "sgn-BE-NL", "sgn-CH-DE"
+];
+
+/// A BCP 47 language tag (rfc5646), e.g. `en`, `de-CH` or `zh-Hant-TW`.
+///
+/// The tag is validated to be well-formed, i.e. to follow the language
+/// tag syntax, and to not contain duplicate variants or extension
+/// singletons. It's not checked if the subtags are registered in the
+/// IANA language subtag registry.
+///
+/// Comparison is case sensitive, but `LanguageTag::matches` can be
+/// used to compare tags case insensitive.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct LanguageTag(SoftAsciiString);
+
+impl LanguageTag {
+
+    /// Creates a new language tag, validating it.
+    pub fn new(tag: &str) -> Result<Self, ComponentCreationError> {
+        if !is_well_formed(tag) {
+            return Err(ComponentCreationError::new_with_str("LanguageTag", tag));
+        }
+        Ok(LanguageTag(SoftAsciiString::from_unchecked(tag)))
+    }
+
+    /// Returns the primary language subtag, e.g. `"en"` for `"en-US"`.
+    pub fn primary_language(&self) -> &str {
+        //UNWRAP_SAFE: split always returns at last one element
+        self.as_str().split('-').next().unwrap()
+    }
+
+    /// Compares this tag with another tag ignoring the case.
+    pub fn matches(&self, other: &LanguageTag) -> bool {
+        self.as_str().eq_ignore_ascii_case(other.as_str())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+fn is_well_formed(tag: &str) -> bool {
+    if IRREGULAR_GRANDFATHERED.iter().any(|gf| gf.eq_ignore_ascii_case(tag)) {
+        return true;
+    }
+
+    let subtags = tag.split('-').collect::<Vec<_>>();
+    if subtags.iter().any(|subtag| {
+        subtag.is_empty() || subtag.len() > 8
+            || !subtag.bytes().all(|bch| bch.is_ascii_alphanumeric())
+    }) {
+        return false;
+    }
+
+    if subtags[0].eq_ignore_ascii_case("x") {
+        return subtags.len() > 1;
+    }
+
+    is_well_formed_langtag(&subtags)
+}
+
+/// Checks the `langtag` production of rfc5646, expects all subtags to be 1-8 alphanumerics.
+fn is_well_formed_langtag(subtags: &[&str]) -> bool {
+    let is_alpha = |subtag: &str| subtag.bytes().all(|bch| bch.is_ascii_alphabetic());
+    let is_digit = |subtag: &str| subtag.bytes().all(|bch| bch.is_ascii_digit());
+
+    let mut idx = 0;
+
+    // language
+    let language = subtags[idx];
+    if !is_alpha(language) || language.len() == 1 {
+        return false;
+    }
+    idx += 1;
+
+    // extlang, only for 2-3 letter languages
+    if language.len() <= 3 {
+        let mut count = 0;
+        while count < 3 && idx < subtags.len()
+            && subtags[idx].len() == 3 && is_alpha(subtags[idx])
+        {
+            idx += 1;
+            count += 1;
+        }
+    }
+
+    // script
+    if idx < subtags.len() && subtags[idx].len() == 4 && is_alpha(subtags[idx]) {
+        idx += 1;
+    }
+
+    // region
+    if idx < subtags.len() {
+        let subtag = subtags[idx];
+        if (subtag.len() == 2 && is_alpha(subtag)) || (subtag.len() == 3 && is_digit(subtag)) {
+            idx += 1;
+        }
+    }
+
+    // variants
+    let mut variants: Vec<&str> = Vec::new();
+    while idx < subtags.len() {
+        let subtag = subtags[idx];
+        let is_variant = subtag.len() >= 5
+            || (subtag.len() == 4 && subtag.as_bytes()[0].is_ascii_digit());
+        if !is_variant {
+            break;
+        }
+        if variants.iter().any(|variant| variant.eq_ignore_ascii_case(subtag)) {
+            return false;
+        }
+        variants.push(subtag);
+        idx += 1;
+    }
+
+    // extensions
+    let mut singletons: Vec<u8> = Vec::new();
+    while idx < subtags.len() && subtags[idx].len() == 1 {
+        let singleton = subtags[idx].as_bytes()[0].to_ascii_lowercase();
+        if singleton == b'x' {
+            break;
+        }
+        if singletons.contains(&singleton) {
+            return false;
+        }
+        singletons.push(singleton);
+        idx += 1;
+
+        let start = idx;
+        while idx < subtags.len() && subtags[idx].len() >= 2 {
+            idx += 1;
+        }
+        if start == idx {
+            return false;
+        }
+    }
+
+    // private use
+    if idx < subtags.len() && subtags[idx].eq_ignore_ascii_case("x") {
+        return idx + 1 < subtags.len();
+    }
+
+    idx == subtags.len()
+}
+
+impl Display for LanguageTag {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for LanguageTag {
+    fn try_from(tag: &'a str) -> Result<Self, ComponentCreationError> {
+        LanguageTag::new(tag)
+    }
+}
+
+impl HeaderTryFrom<String> for LanguageTag {
+    fn try_from(tag: String) -> Result<Self, ComponentCreationError> {
+        LanguageTag::new(&tag)
+    }
+}
+
+impl EncodableInHeader for  LanguageTag {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(&self.0)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// A non empty list of language tags as used in the `Content-Language` header (rfc3282).
+///
+/// Multiple tags are used if the content is intended for an audience
+/// speaking multiple languages, e.g. a mail containing a text
+/// in both english and german.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct LanguageList(pub Vec1<LanguageTag>);
+
+impl LanguageList {
+    pub fn from_single(tag: LanguageTag) -> Self {
+        LanguageList(Vec1::new(tag))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for LanguageList {
+
+    /// Parses a comma separated list of language tags, e.g. `"en, de"`.
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let tags = value.split(',')
+            .map(|tag| LanguageTag::new(tag.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        //UNWRAP_SAFE: split always returns at last one element
+        Ok(LanguageList(Vec1::try_from_vec(tags).unwrap()))
+    }
+}
+
+impl HeaderTryFrom<LanguageTag> for LanguageList {
+    fn try_from(tag: LanguageTag) -> Result<Self, ComponentCreationError> {
+        Ok(LanguageList::from_single(tag))
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for LanguageList
+    where T: HeaderTryInto<LanguageTag>
+{
+    fn try_from(vec: Vec<T>) -> Result<Self, ComponentCreationError> {
+        let mut out = Vec::new();
+        for tag in vec {
+            out.push(tag.try_into()?);
+        }
+        let out = Vec1::try_from_vec(out)
+            .map_err(|_| ComponentCreationError::new("LanguageList"))?;
+        Ok(LanguageList(out))
+    }
+}
+
+impl EncodableInHeader for  LanguageList {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        sep_for!{ tag in self.0.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            handle.write_str(SoftAsciiStr::from_unchecked(tag.as_str()))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+deref0!{ +mut LanguageList => Vec1<LanguageTag> }
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    #[test]
+    fn well_formed_tags() {
+        for tag in &[
+            "de", "en-US", "zh-Hant-TW", "sr-Latn-RS", "es-419", "zh-yue-HK",
+            "de-CH-1996", "sl-rozaj-biske", "en-a-bbb-x-a-ccc", "x-whatever",
+            "qaa-Qaaa-QM-x-southern", "i-klingon", "en-GB-oed", "hy-Latn-IT-arevela"
+        ] {
+            assert_ok!(LanguageTag::new(tag));
+        }
+    }
+
+    #[test]
+    fn malformed_tags() {
+        for tag in &[
+            "", "e", "en-", "-en", "en--US", "en_US", "de-419-DE", "a-DE",
+            "ar-a-aaa-b-bbb-a-ccc", "de-DE-1901-1901", "en-a", "en-x",
+            "toolongtag", "en US", "dé"
+        ] {
+            assert_err!(LanguageTag::new(tag));
+        }
+    }
+
+    #[test]
+    fn primary_language_and_matches() {
+        let tag = LanguageTag::new("en-US").unwrap();
+        assert_eq!(tag.primary_language(), "en");
+        assert!(tag.matches(&LanguageTag::new("EN-us").unwrap()));
+        assert!(!tag.matches(&LanguageTag::new("en-GB").unwrap()));
+    }
+
+    ec_test!{ language_list, {
+        LanguageList::try_from("en-US,de")?
+    } => ascii => [
+        Text "en-US,",
+        MarkFWS,
+        Text " de"
+    ]}
+
+    #[test]
+    fn language_list_rejects_invalid_tags() {
+        assert_err!(LanguageList::try_from("en, "));
+        assert_err!(LanguageList::try_from(Vec::<&str>::new()));
+    }
+}
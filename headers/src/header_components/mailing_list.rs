@@ -0,0 +1,374 @@
+//! Components for the mailing list headers (rfc2369, rfc2919, rfc8058).
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+use vec1::Vec1;
+
+use internals::MailType;
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use internals::grammar::{is_ascii_vchar, is_atext};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+
+use super::Phrase;
+
+/// A URI as used in the `List-*` headers (rfc2369).
+///
+/// The URI is validated to be us-ascii only, to start with
+/// a scheme (e.g. `https:` or `mailto:`) and to not contain
+/// whitespace or `'<'`/`'>'`. Non-ascii characters have to be
+/// percent encoded.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Uri(SoftAsciiString);
+
+impl Uri {
+
+    /// Creates a new URI, validating it.
+    pub fn new(uri: &str) -> Result<Self, ComponentCreationError> {
+        if !is_valid_uri(uri) {
+            return Err(ComponentCreationError::new_with_str("Uri", uri));
+        }
+        Ok(Uri(SoftAsciiString::from_unchecked(uri)))
+    }
+
+    /// Returns the scheme of the URI, e.g. `"mailto"`.
+    pub fn scheme(&self) -> &str {
+        let uri = self.as_str();
+        //UNWRAP_SAFE: validated in the constructor
+        &uri[..uri.find(':').unwrap()]
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+fn is_valid_uri(uri: &str) -> bool {
+    let colon_idx = match uri.find(':') {
+        Some(idx) => idx,
+        None => return false
+    };
+    let scheme = &uri[..colon_idx];
+    let valid_scheme = scheme.chars().enumerate().all(|(idx, ch)| {
+        ch.is_ascii_alphabetic()
+            || (idx > 0 && (ch.is_ascii_digit() || ch == '+' || ch == '-' || ch == '.'))
+    });
+    valid_scheme && !scheme.is_empty()
+        && uri.len() > colon_idx + 1
+        && uri.chars().all(|ch| is_ascii_vchar(ch) && ch != '<' && ch != '>')
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Uri {
+    fn try_from(uri: &'a str) -> Result<Self, ComponentCreationError> {
+        Uri::new(uri)
+    }
+}
+
+impl HeaderTryFrom<String> for Uri {
+    fn try_from(uri: String) -> Result<Self, ComponentCreationError> {
+        Uri::new(&uri)
+    }
+}
+
+impl EncodableInHeader for  Uri {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+        handle.write_str(&self.0)?;
+        handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// A non empty list of URIs as used in e.g. `List-Unsubscribe` (rfc2369).
+///
+/// The URIs are listed in order of preference.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct UriList(pub Vec1<Uri>);
+
+impl UriList {
+    pub fn from_single(uri: Uri) -> Self {
+        UriList(Vec1::new(uri))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for UriList {
+    fn try_from(uri: &'a str) -> Result<Self, ComponentCreationError> {
+        Ok(UriList::from_single(Uri::new(uri)?))
+    }
+}
+
+impl HeaderTryFrom<String> for UriList {
+    fn try_from(uri: String) -> Result<Self, ComponentCreationError> {
+        Ok(UriList::from_single(Uri::new(&uri)?))
+    }
+}
+
+impl HeaderTryFrom<Uri> for UriList {
+    fn try_from(uri: Uri) -> Result<Self, ComponentCreationError> {
+        Ok(UriList::from_single(uri))
+    }
+}
+
+impl<T> HeaderTryFrom<Vec<T>> for UriList
+    where T: HeaderTryInto<Uri>
+{
+    fn try_from(vec: Vec<T>) -> Result<Self, ComponentCreationError> {
+        let mut out = Vec::new();
+        for uri in vec {
+            out.push(uri.try_into()?);
+        }
+        let out = Vec1::try_from_vec(out)
+            .map_err(|_| ComponentCreationError::new("UriList"))?;
+        Ok(UriList(out))
+    }
+}
+
+macro_rules! impl_header_try_from_array {
+    ($($len:tt)*) => ($(
+        impl<T> HeaderTryFrom<[T; $len]> for UriList
+            where T: HeaderTryInto<Uri>
+        {
+            fn try_from( vec: [T; $len] ) -> Result<Self, ComponentCreationError> {
+                let heapified: Box<[T]> = Box::new(vec);
+                let vecified: Vec<_> = heapified.into();
+                UriList::try_from( vecified )
+            }
+        }
+    )*);
+}
+
+impl_header_try_from_array! { 1 2 3 4 5 6 7 8 }
+
+impl EncodableInHeader for  UriList {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        sep_for!{ uri in self.0.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            uri.encode(handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+deref0!{ +mut UriList => Vec1<Uri> }
+
+/// The body of the `List-Post` header (rfc2369).
+///
+/// Either the URIs through which to post to the list, or
+/// `NO` if posting to the list is not allowed.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum ListPost {
+    Uris(UriList),
+    No
+}
+
+impl<T> HeaderTryFrom<T> for ListPost
+    where T: HeaderTryInto<UriList>
+{
+    fn try_from(uris: T) -> Result<Self, ComponentCreationError> {
+        Ok(ListPost::Uris(uris.try_into()?))
+    }
+}
+
+impl EncodableInHeader for  ListPost {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        match *self {
+            ListPost::Uris(ref uris) => uris.encode(handle),
+            ListPost::No => handle.write_str(SoftAsciiStr::from_unchecked("NO"))
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The body of the `List-Id` header (rfc2919).
+///
+/// It consists of an optional description and the list id, which
+/// is a dot-atom like `list-label.example.com`. It's encoded as
+/// `Description <list-label.example.com>`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ListId {
+    pub description: Option<Phrase>,
+    id: SoftAsciiString
+}
+
+impl ListId {
+
+    /// Creates a new list id without a description.
+    ///
+    /// The id has to consist of at last two dot separated ascii atoms,
+    /// e.g. `list-label.example.com`, it should not include the
+    /// surrounding `'<'`, `'>'`.
+    pub fn new(id: &str) -> Result<Self, ComponentCreationError> {
+        let is_valid = id.contains('.')
+            && id.split('.').all(|atom| {
+                !atom.is_empty() && atom.chars().all(|ch| is_atext(ch, MailType::Ascii))
+            });
+
+        if !is_valid {
+            return Err(ComponentCreationError::new_with_str("ListId", id));
+        }
+        Ok(ListId { description: None, id: SoftAsciiString::from_unchecked(id) })
+    }
+
+    /// Sets the description of the list id.
+    pub fn with_description<P>(mut self, description: P) -> Result<Self, ComponentCreationError>
+        where P: HeaderTryInto<Phrase>
+    {
+        self.description = Some(description.try_into()?);
+        Ok(self)
+    }
+
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ListId {
+    fn try_from(id: &'a str) -> Result<Self, ComponentCreationError> {
+        ListId::new(id)
+    }
+}
+
+impl HeaderTryFrom<String> for ListId {
+    fn try_from(id: String) -> Result<Self, ComponentCreationError> {
+        ListId::new(&id)
+    }
+}
+
+impl<'a, P> HeaderTryFrom<(P, &'a str)> for ListId
+    where P: HeaderTryInto<Phrase>
+{
+    fn try_from(pair: (P, &'a str)) -> Result<Self, ComponentCreationError> {
+        ListId::new(pair.1)?.with_description(pair.0)
+    }
+}
+
+impl EncodableInHeader for  ListId {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if let Some(ref description) = self.description {
+            description.encode(handle)?;
+            handle.write_fws();
+        }
+        handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+        handle.write_str(&self.id)?;
+        handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// The body of the `List-Unsubscribe-Post` header (rfc8058).
+///
+/// The only allowed value is `List-Unsubscribe=One-Click`, which
+/// signals that the mail supports one-click unsubscription through
+/// a POST request to the https URI in the `List-Unsubscribe` header.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub struct OneClickUnsubscribe;
+
+const ONE_CLICK: &str = "List-Unsubscribe=One-Click";
+
+impl<'a> HeaderTryFrom<&'a str> for OneClickUnsubscribe {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        if value.trim() == ONE_CLICK {
+            Ok(OneClickUnsubscribe)
+        } else {
+            Err(ComponentCreationError::new_with_str("OneClickUnsubscribe", value))
+        }
+    }
+}
+
+impl EncodableInHeader for  OneClickUnsubscribe {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(ONE_CLICK))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    #[test]
+    fn uri_validation() {
+        assert_ok!(Uri::new("mailto:unsub@example.com?subject=unsubscribe"));
+        assert_ok!(Uri::new("https://example.com/unsub/a%C3%A4"));
+        assert_err!(Uri::new("example.com/unsub"));
+        assert_err!(Uri::new("1http://example.com"));
+        assert_err!(Uri::new("https://example.com/a b"));
+        assert_err!(Uri::new("https://example.com/<"));
+        assert_err!(Uri::new("https://example.com/ä"));
+
+        let uri = Uri::new("mailto:unsub@example.com").unwrap();
+        assert_eq!(uri.scheme(), "mailto");
+    }
+
+    ec_test!{ uri_list, {
+        UriList::try_from([
+            "mailto:unsub@example.com",
+            "https://example.com/unsub"
+        ])?
+    } => ascii => [
+        Text "<mailto:unsub@example.com>,",
+        MarkFWS,
+        Text " <https://example.com/unsub>"
+    ]}
+
+    ec_test!{ list_post_no, {
+        ListPost::No
+    } => ascii => [
+        Text "NO"
+    ]}
+
+    ec_test!{ list_id, {
+        ListId::try_from(("Our List", "our-list.example.com"))?
+    } => ascii => [
+        Text "Our",
+        MarkFWS,
+        Text " List",
+        MarkFWS,
+        Text " <our-list.example.com>"
+    ]}
+
+    #[test]
+    fn list_id_validation() {
+        assert_ok!(ListId::new("list.example"));
+        assert_err!(ListId::new("nodot"));
+        assert_err!(ListId::new("a..b"));
+        assert_err!(ListId::new("a b.c"));
+    }
+
+    ec_test!{ one_click, {
+        OneClickUnsubscribe::try_from("List-Unsubscribe=One-Click")?
+    } => ascii => [
+        Text "List-Unsubscribe=One-Click"
+    ]}
+
+    #[test]
+    fn one_click_rejects_other_values() {
+        assert_err!(OneClickUnsubscribe::try_from("List-Unsubscribe=Two-Click"));
+    }
+}
@@ -24,6 +24,12 @@ pub use self::mailbox::{Mailbox, NoDisplayName};
 mod mailbox_list;
 pub use self::mailbox_list::{MailboxList, OptMailboxList };
 
+mod address;
+pub use self::address::Address;
+
+mod address_list;
+pub use self::address_list::AddressList;
+
 mod transfer_encoding;
 pub use self::transfer_encoding::TransferEncoding;
 
@@ -50,6 +56,12 @@ pub use self::path::Path;
 mod received_token;
 pub use self::received_token::ReceivedToken;
 
+mod typed_address;
+pub use self::typed_address::TypedAddress;
+
+mod received;
+pub use self::received::{Received, ExtendedDomain, TcpInfo};
+
 pub mod word;
 pub use self::word::Word;
 
@@ -62,5 +74,36 @@ pub use self::phrase_list::PhraseList;
 mod disposition;
 pub use self::disposition::*;
 
+mod language;
+pub use self::language::{LanguageTag, LanguageList};
+
+mod content_location;
+pub use self::content_location::ContentLocation;
+
+mod content_md5;
+pub use self::content_md5::ContentMd5;
+
+mod content_duration;
+pub use self::content_duration::ContentDuration;
+
+mod priority;
+pub use self::priority::{Importance, Priority, XPriority, MailPriority};
+
+mod autocrypt;
+pub use self::autocrypt::{Autocrypt, AutocryptGossip, PreferEncrypt};
+
+mod authentication_results;
+pub use self::authentication_results::{
+    AuthenticationResults, ArcAuthenticationResults, MethodResult, ResultProperty
+};
+
+mod auto_submitted;
+pub use self::auto_submitted::{
+    AutoSubmitted, Precedence, AutoResponseKind, AutoResponseSuppress
+};
+
+mod mailing_list;
+pub use self::mailing_list::{Uri, UriList, ListPost, ListId, OneClickUnsubscribe};
+
 mod raw_unstructured;
 pub use self::raw_unstructured::*;
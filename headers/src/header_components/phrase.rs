@@ -1,6 +1,7 @@
 use vec1::{Vec1, Size0Error};
 
 use internals::grammar::encoded_word::EncodedWordContext;
+use internals::bind::encoded_word::decode_encoded_words;
 use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 
@@ -89,6 +90,18 @@ impl Phrase {
 
         Ok( Phrase( words ) )
     }
+
+    /// Creates a `Phrase` instance from the (encoded) phrase in a header of a received mail.
+    ///
+    /// Encoded words in it are decoded and quoted strings are unquoted, see
+    /// `internals::bind::encoded_word::decode_encoded_words` for details.
+    ///
+    /// # Error
+    ///
+    /// Fails in the same cases as `Phrase::new` fails for the decoded phrase.
+    pub fn from_encoded(phrase: &str) -> Result<Self, ComponentCreationError> {
+        Phrase::new(decode_encoded_words(phrase, EncodedWordContext::Phrase))
+    }
 }
 
 impl<'a> HeaderTryFrom<&'a str> for Phrase {
@@ -151,7 +164,10 @@ mod test {
         MarkFWS,
         Text " encoding"
     ]}
-}
-
-
 
+    #[test]
+    fn from_encoded() {
+        let phrase = Phrase::from_encoded("\"Doe, J.\" =?utf-8?Q?J=C3=B6rg?= =?utf-8?B?w7w=?=").unwrap();
+        assert_eq!(phrase, Phrase::new("Doe, J. Jörgü").unwrap());
+    }
+}
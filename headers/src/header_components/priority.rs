@@ -0,0 +1,288 @@
+use std::str::FromStr;
+
+use soft_ascii_string::{SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+/// The body of the `Importance` header (rfc2156).
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Importance {
+    Low,
+    Normal,
+    High
+}
+
+impl Importance {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Importance::Low => "low",
+            Importance::Normal => "normal",
+            Importance::High => "high"
+        }
+    }
+}
+
+impl FromStr for Importance {
+    type Err = ComponentCreationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        use self::Importance::*;
+        let trimmed = value.trim();
+        for &importance in &[Low, Normal, High] {
+            if trimmed.eq_ignore_ascii_case(importance.as_str()) {
+                return Ok(importance);
+            }
+        }
+        Err(ComponentCreationError::new_with_str("Importance", value))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Importance {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.parse()
+    }
+}
+
+impl EncodableInHeader for  Importance {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// The body of the `Priority` header (rfc2156).
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Priority {
+    NonUrgent,
+    Normal,
+    Urgent
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Priority::NonUrgent => "non-urgent",
+            Priority::Normal => "normal",
+            Priority::Urgent => "urgent"
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ComponentCreationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        use self::Priority::*;
+        let trimmed = value.trim();
+        for &priority in &[NonUrgent, Normal, Urgent] {
+            if trimmed.eq_ignore_ascii_case(priority.as_str()) {
+                return Ok(priority);
+            }
+        }
+        Err(ComponentCreationError::new_with_str("Priority", value))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Priority {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.parse()
+    }
+}
+
+impl EncodableInHeader for  Priority {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// The body of the (non-standard) `X-Priority` header.
+///
+/// It's a number from 1 (highest) to 5 (lowest) and is encoded
+/// with a comment describing the number, e.g. `1 (Highest)`.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub struct XPriority(u8);
+
+impl XPriority {
+
+    pub const HIGHEST: XPriority = XPriority(1);
+    pub const HIGH: XPriority = XPriority(2);
+    pub const NORMAL: XPriority = XPriority(3);
+    pub const LOW: XPriority = XPriority(4);
+    pub const LOWEST: XPriority = XPriority(5);
+
+    /// Creates a new instance, returns `None` if the value is not in `1..=5`.
+    pub fn new(value: u8) -> Option<Self> {
+        if (1..=5).contains(&value) {
+            Some(XPriority(value))
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// The description used as comment, e.g. `"Highest"` for 1.
+    pub fn description(&self) -> &'static str {
+        match self.0 {
+            1 => "Highest",
+            2 => "High",
+            3 => "Normal",
+            4 => "Low",
+            _ => "Lowest"
+        }
+    }
+}
+
+impl HeaderTryFrom<u8> for XPriority {
+    fn try_from(value: u8) -> Result<Self, ComponentCreationError> {
+        XPriority::new(value)
+            .ok_or_else(|| ComponentCreationError::new_with_str("XPriority", value.to_string()))
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for XPriority {
+
+    /// Parses the value ignoring any trailing comment, e.g. `"1 (Highest)"`.
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        value.split(|ch: char| ch.is_whitespace() || ch == '(')
+            .find(|part| !part.is_empty())
+            .and_then(|number| number.parse().ok())
+            .and_then(XPriority::new)
+            .ok_or_else(|| ComponentCreationError::new_with_str("XPriority", value))
+    }
+}
+
+impl EncodableInHeader for  XPriority {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let value = format!("{} ({})", self.0, self.description());
+        handle.write_str(&SoftAsciiString::from_unchecked(value))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(*self)
+    }
+}
+
+/// A priority abstracting over the `Importance`, `Priority` and `X-Priority` headers.
+///
+/// Mail clients disagree about which of the headers they use, so
+/// normally all three headers should be set consistently, which is
+/// what `Mail::set_priority` in `mail-core` does.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum MailPriority {
+    Low,
+    Normal,
+    High
+}
+
+impl MailPriority {
+
+    pub fn importance(&self) -> Importance {
+        match *self {
+            MailPriority::Low => Importance::Low,
+            MailPriority::Normal => Importance::Normal,
+            MailPriority::High => Importance::High
+        }
+    }
+
+    pub fn priority(&self) -> Priority {
+        match *self {
+            MailPriority::Low => Priority::NonUrgent,
+            MailPriority::Normal => Priority::Normal,
+            MailPriority::High => Priority::Urgent
+        }
+    }
+
+    pub fn x_priority(&self) -> XPriority {
+        match *self {
+            MailPriority::Low => XPriority::LOWEST,
+            MailPriority::Normal => XPriority::NORMAL,
+            MailPriority::High => XPriority::HIGHEST
+        }
+    }
+}
+
+impl From<Importance> for MailPriority {
+    fn from(importance: Importance) -> Self {
+        match importance {
+            Importance::Low => MailPriority::Low,
+            Importance::Normal => MailPriority::Normal,
+            Importance::High => MailPriority::High
+        }
+    }
+}
+
+impl From<Priority> for MailPriority {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::NonUrgent => MailPriority::Low,
+            Priority::Normal => MailPriority::Normal,
+            Priority::Urgent => MailPriority::High
+        }
+    }
+}
+
+impl From<XPriority> for MailPriority {
+
+    /// Converts 1-2 to high, 3 to normal and 4-5 to low priority.
+    fn from(x_priority: XPriority) -> Self {
+        match x_priority.value() {
+            1 | 2 => MailPriority::High,
+            3 => MailPriority::Normal,
+            _ => MailPriority::Low
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    ec_test!{ x_priority, {
+        XPriority::HIGHEST
+    } => ascii => [
+        Text "1 (Highest)"
+    ]}
+
+    ec_test!{ priority, {
+        Priority::try_from("Non-Urgent")?
+    } => ascii => [
+        Text "non-urgent"
+    ]}
+
+    #[test]
+    fn parse_x_priority() {
+        assert_eq!(assert_ok!(XPriority::try_from("2 (High)")), XPriority::HIGH);
+        assert_eq!(assert_ok!(XPriority::try_from("5")), XPriority::LOWEST);
+        assert_err!(XPriority::try_from("0"));
+        assert_err!(XPriority::try_from("6 (Very Low)"));
+        assert_err!(XPriority::try_from("high"));
+    }
+
+    #[test]
+    fn mail_priority_round_trip() {
+        for &priority in &[MailPriority::Low, MailPriority::Normal, MailPriority::High] {
+            assert_eq!(MailPriority::from(priority.importance()), priority);
+            assert_eq!(MailPriority::from(priority.priority()), priority);
+            assert_eq!(MailPriority::from(priority.x_priority()), priority);
+        }
+        assert_eq!(MailPriority::from(XPriority::HIGH), MailPriority::High);
+        assert_eq!(MailPriority::from(XPriority::LOW), MailPriority::Low);
+    }
+}
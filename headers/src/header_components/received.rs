@@ -0,0 +1,263 @@
+use std::net::IpAddr;
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+
+use super::word::{ Word, do_encode_word };
+use super::{ DateTime, Domain, Email };
+
+/// The `Received` trace header as specified in rfc5321 section 4.4.
+///
+/// It's encoded as
+/// `from <from> by <by> via <via> with <with> id <id> for <for_>; <date>`,
+/// where each clause is omitted if it is `None`.
+///
+/// Relays normally prepend a `Received` header for each mail they
+/// handle, which can be done using `HeaderMap::prepend`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// use mail_headers::HeaderTryFrom;
+/// use mail_headers::header_components::{Received, Domain, DateTime, Word};
+///
+/// # fn main() {
+/// let mut received = Received::new(DateTime::now());
+/// received.from = Some(Domain::try_from("client.example").unwrap().into());
+/// received.by = Some(Domain::try_from("relay.example").unwrap().into());
+/// received.with = Some(Word::try_from("ESMTPS").unwrap());
+/// # let _ = received;
+/// # }
+/// ```
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Received {
+    /// The host the mail was received from.
+    pub from: Option<ExtendedDomain>,
+    /// The host which received the mail.
+    pub by: Option<ExtendedDomain>,
+    /// The link type, e.g. `TCP`.
+    pub via: Option<Word>,
+    /// The protocol used, e.g. `ESMTP` or `ESMTPS`.
+    pub with: Option<Word>,
+    /// A id the receiving host uses to refer to the mail, e.g. a queue id.
+    pub id: Option<Word>,
+    /// The recipient the mail was received for.
+    pub for_: Option<Email>,
+    /// When the mail was received.
+    pub date: DateTime
+}
+
+impl Received {
+
+    /// Create a new `Received` header body with only a date.
+    pub fn new(date: DateTime) -> Self {
+        Received {
+            from: None,
+            by: None,
+            via: None,
+            with: None,
+            id: None,
+            for_: None,
+            date
+        }
+    }
+}
+
+/// A domain as used in the `from` and `by` clause of a `Received` header.
+///
+/// The tcp info is used to include the ip address (and the
+/// reverse DNS name) of the host, e.g.
+/// `client.example (mx.client.example [192.0.2.1])`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct ExtendedDomain {
+    pub domain: Domain,
+    pub tcp_info: Option<TcpInfo>
+}
+
+impl From<Domain> for ExtendedDomain {
+    fn from(domain: Domain) -> Self {
+        ExtendedDomain { domain, tcp_info: None }
+    }
+}
+
+/// The ip address and optionally the reverse DNS name of a host.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct TcpInfo {
+    pub host: Option<Domain>,
+    pub ip: IpAddr
+}
+
+impl From<IpAddr> for TcpInfo {
+    fn from(ip: IpAddr) -> Self {
+        TcpInfo { host: None, ip }
+    }
+}
+
+impl EncodableInHeader for  Received {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let mut first = true;
+        let mut separate = |handle: &mut EncodingWriter| {
+            if first {
+                first = false;
+            } else {
+                handle.write_fws();
+            }
+        };
+
+        if let Some(ref from) = self.from {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("from"))?;
+            handle.write_fws();
+            from.encode(handle)?;
+        }
+        if let Some(ref by) = self.by {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("by"))?;
+            handle.write_fws();
+            by.encode(handle)?;
+        }
+        if let Some(ref via) = self.via {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("via"))?;
+            handle.write_fws();
+            do_encode_word(via, handle, None)?;
+        }
+        if let Some(ref with) = self.with {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("with"))?;
+            handle.write_fws();
+            do_encode_word(with, handle, None)?;
+        }
+        if let Some(ref id) = self.id {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("id"))?;
+            handle.write_fws();
+            do_encode_word(id, handle, None)?;
+        }
+        if let Some(ref for_) = self.for_ {
+            separate(handle);
+            handle.write_str(SoftAsciiStr::from_unchecked("for"))?;
+            handle.write_fws();
+            handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+            for_.encode(handle)?;
+            handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        }
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        self.date.encode(handle)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl EncodableInHeader for  ExtendedDomain {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        self.domain.encode(handle)?;
+        if let Some(ref tcp_info) = self.tcp_info {
+            handle.write_fws();
+            handle.write_char(SoftAsciiChar::from_unchecked('('))?;
+            tcp_info.encode(handle)?;
+            handle.write_char(SoftAsciiChar::from_unchecked(')'))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+impl EncodableInHeader for  TcpInfo {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if let Some(ref host) = self.host {
+            host.encode(handle)?;
+            handle.write_fws();
+        }
+        let literal = match self.ip {
+            IpAddr::V4(ip) => format!("[{}]", ip),
+            IpAddr::V6(ip) => format!("[IPv6:{}]", ip)
+        };
+        handle.write_str(&SoftAsciiString::from_unchecked(literal))
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use ::HeaderTryFrom;
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use super::*;
+
+    /// Encodes the received header, unfolding the result.
+    fn encode(received: &Received) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            received.encode(&mut handle).unwrap();
+            handle.commit_partial_header();
+        }
+        encoder.as_str().unwrap().replace("\r\n", "")
+    }
+
+    #[test]
+    fn only_date() {
+        let date = DateTime::test_time(1);
+        let received = Received::new(date.clone());
+        assert_eq!(encode(&received), format!("; {}", date.to_rfc2822()));
+    }
+
+    #[test]
+    fn all_clauses() {
+        let date = DateTime::test_time(1);
+        let received = Received {
+            from: Some(ExtendedDomain {
+                domain: Domain::try_from("client.test").unwrap(),
+                tcp_info: Some(TcpInfo {
+                    host: Some(Domain::try_from("mx.client.test").unwrap()),
+                    ip: Ipv4Addr::new(192, 0, 2, 1).into()
+                })
+            }),
+            by: Some(ExtendedDomain {
+                domain: Domain::try_from("relay.test").unwrap(),
+                tcp_info: Some(TcpInfo::from(IpAddr::from(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))))
+            }),
+            via: Some(Word::try_from("TCP").unwrap()),
+            with: Some(Word::try_from("ESMTPS").unwrap()),
+            id: Some(Word::try_from("a1b2c3").unwrap()),
+            for_: Some(Email::try_from("rec@ipient.test").unwrap()),
+            date: date.clone()
+        };
+
+        assert_eq!(encode(&received), format!(
+            "from client.test (mx.client.test [192.0.2.1]) \
+             by relay.test ([IPv6:2001:db8::1]) \
+             via TCP with ESMTPS id a1b2c3 for <rec@ipient.test>; {}",
+            date.to_rfc2822()
+        ));
+    }
+
+    #[test]
+    fn no_encoded_word() {
+        let mut received = Received::new(DateTime::test_time(1));
+        received.id = Some(Word::try_from("↓id").unwrap());
+
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        let mut handle = encoder.writer();
+        assert_err!(received.encode(&mut handle));
+        handle.undo_header();
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::{self, Display};
+
+use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+
+use super::Email;
+
+/// The address type used for normal internet mail addresses.
+const RFC822: &str = "rfc822";
+
+/// A address prefixed with it's address type, e.g. `rfc822;user@example.com`.
+///
+/// This is used by the `Original-Recipient` header as well as by the
+/// `Original-Recipient`/`Final-Recipient` fields of delivery status
+/// notifications (rfc3464) and message disposition notifications (rfc8098).
+///
+/// The address type has to be an atom, the address can be any text which
+/// does not contain control characters (e.g. line breaks). If the address
+/// is not ascii it can only be encoded in internationalized mails.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct TypedAddress {
+    address_type: String,
+    address: String
+}
+
+impl TypedAddress {
+
+    /// Creates a new typed address.
+    ///
+    /// # Error
+    ///
+    /// Fails if the address type is not a (non-empty) atom, or if the
+    /// address is empty or contains control characters.
+    pub fn new<T, A>(address_type: T, address: A) -> Result<Self, ComponentCreationError>
+        where T: Into<String>, A: Into<String>
+    {
+        let address_type = address_type.into();
+        let address = address.into();
+
+        let valid_type = !address_type.is_empty()
+            && address_type.bytes().all(is_atext);
+
+        let trimmed = address.trim();
+        let valid_address = !trimmed.is_empty()
+            && !trimmed.chars().any(char::is_control);
+
+        if !valid_type || !valid_address {
+            return Err(ComponentCreationError::new_with_str(
+                "TypedAddress", format!("{};{}", address_type, address)));
+        }
+
+        let address = trimmed.to_owned();
+        Ok(TypedAddress { address_type, address })
+    }
+
+    /// Creates a `rfc822` typed address from given email.
+    pub fn rfc822(email: &Email) -> Self {
+        TypedAddress {
+            address_type: RFC822.to_owned(),
+            address: format!("{}@{}", email.local_part.as_str(), email.domain.as_str())
+        }
+    }
+
+    /// The address type, e.g. `rfc822`.
+    pub fn address_type(&self) -> &str {
+        &self.address_type
+    }
+
+    /// The address, e.g. `user@example.com`.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns true if the address type is `rfc822`.
+    pub fn is_rfc822(&self) -> bool {
+        self.address_type.eq_ignore_ascii_case(RFC822)
+    }
+}
+
+fn is_atext(bch: u8) -> bool {
+    bch.is_ascii_alphanumeric() || b"!#$%&'*+-/=?^_`{|}~".contains(&bch)
+}
+
+impl Display for TypedAddress {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{};{}", self.address_type, self.address)
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for TypedAddress {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        let mut parts = value.splitn(2, ';');
+        //UNWRAP_SAFE: splitn always returns at last one element
+        let address_type = parts.next().unwrap().trim();
+        let address = parts.next()
+            .ok_or_else(|| ComponentCreationError::new_with_str("TypedAddress", value))?;
+        TypedAddress::new(address_type, address)
+    }
+}
+
+impl HeaderTryFrom<Email> for TypedAddress {
+    fn try_from(email: Email) -> Result<Self, ComponentCreationError> {
+        Ok(TypedAddress::rfc822(&email))
+    }
+}
+
+impl EncodableInHeader for  TypedAddress {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.address_type))?;
+        handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
+        handle.write_fws();
+        if self.address.is_ascii() {
+            handle.write_str(SoftAsciiStr::from_unchecked(&self.address))
+        } else {
+            handle.write_utf8(&self.address)
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use super::*;
+
+    ec_test!{ rfc822, {
+        TypedAddress::rfc822(&Email::try_from("user@example.test")?)
+    } => ascii => [
+        Text "rfc822;",
+        MarkFWS,
+        Text " user@example.test"
+    ]}
+
+    ec_test!{ utf8_address, {
+        TypedAddress::new("utf-8", "jösé@example.test")?
+    } => utf8 => [
+        Text "utf-8;",
+        MarkFWS,
+        Text " jösé@example.test"
+    ]}
+
+    #[test]
+    fn utf8_address_in_ascii_mail() {
+        let addr = TypedAddress::new("utf-8", "jösé@example.test").unwrap();
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        let mut handle = encoder.writer();
+        assert_err!(addr.encode(&mut handle));
+        handle.undo_header();
+    }
+
+    #[test]
+    fn parse() {
+        let addr = assert_ok!(TypedAddress::try_from("rfc822; user@example.test"));
+        assert!(addr.is_rfc822());
+        assert_eq!(addr.address(), "user@example.test");
+        assert_eq!(addr.to_string(), "rfc822;user@example.test");
+
+        assert_err!(TypedAddress::try_from("user@example.test"));
+        assert_err!(TypedAddress::try_from("rfc 822;user@example.test"));
+        assert_err!(TypedAddress::try_from("rfc822;user@exa\r\nmple.test"));
+        assert_err!(TypedAddress::try_from("rfc822; "));
+    }
+}
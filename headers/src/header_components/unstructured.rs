@@ -7,7 +7,8 @@ use soft_ascii_string::SoftAsciiChar;
 use internals::grammar::is_vchar;
 use internals::error::{EncodingError, EncodingErrorKind};
 use internals::encoder::{EncodingWriter, EncodableInHeader};
-use internals::bind::encoded_word::{EncodedWordEncoding, WriterWrapper};
+use internals::bind::encoded_word::{EncodedWordEncoding, WriterWrapper, decode_encoded_words};
+use internals::grammar::encoded_word::EncodedWordContext;
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
 use ::data::Input;
@@ -20,6 +21,17 @@ pub struct Unstructured {
     text: Input,
 }
 
+impl Unstructured {
+
+    /// Creates a `Unstructured` instance from the (encoded) value of a header of a received mail.
+    ///
+    /// The value is unfolded and encoded words in it are decoded, see
+    /// `internals::bind::encoded_word::decode_encoded_words` for details.
+    pub fn from_encoded(value: &str) -> Result<Self, ComponentCreationError> {
+        Unstructured::try_from(decode_encoded_words(value, EncodedWordContext::Text))
+    }
+}
+
 impl Display for Unstructured {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
         fter.write_str(self.as_str())
@@ -118,6 +130,14 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn from_encoded() {
+        let subject = Unstructured::from_encoded(
+            "Re: =?utf-8?Q?Gr=C3=BC=C3=9Fe?=\r\n =?iso-8859-1?Q?_aus_K=F6ln?= =?x?"
+        ).unwrap();
+        assert_eq!(subject.as_str(), "Re: Grüße aus Köln =?x?");
+    }
+
     ec_test! { simple_encoding, {
         Unstructured::try_from( "this simple case" )?
     } => ascii => [
@@ -3,7 +3,8 @@
 use ::header_components;
 use self::validators::{
     from as validator_from,
-    resent_any as validator_resent_any
+    resent_any as validator_resent_any,
+    list_unsubscribe_post as validator_list_unsubscribe_post
 };
 
 
@@ -17,13 +18,13 @@ def_headers! {
     /// (rfc5322)
     Sender,       unchecked { "Sender"        },  Mailbox,        maxOne,   None,
     /// (rfc5322)
-    ReplyTo,      unchecked { "Reply-To"      },  MailboxList,    maxOne,   None,
+    ReplyTo,      unchecked { "Reply-To"      },  AddressList,    maxOne,   None,
     /// (rfc5322)
-    _To,          unchecked { "To"            },  MailboxList,    maxOne,   None,
+    _To,          unchecked { "To"            },  AddressList,    maxOne,   None,
     /// (rfc5322)
-    Cc,           unchecked { "Cc"            },  MailboxList,    maxOne,   None,
+    Cc,           unchecked { "Cc"            },  AddressList,    maxOne,   None,
     /// (rfc5322)
-    Bcc,          unchecked { "Bcc"           },  MailboxList,    maxOne,   None,
+    Bcc,          unchecked { "Bcc"           },  AddressList,    maxOne,   None,
     /// (rfc5322)
     MessageId,    unchecked { "Message-Id"    },  MessageId,      maxOne,   None,
     /// (rfc5322)
@@ -52,8 +53,8 @@ def_headers! {
     ResentMsgId,  unchecked { "Resent-Msg-Id" },  MessageId,      multi,    validator_resent_any,
     /// (rfc5322)
     ReturnPath,   unchecked { "Return-Path"   },  Path,           multi,    None,
-    /// (rfc5322)
-    Received,     unchecked { "Received"      },  ReceivedToken,  multi,    None,
+    /// (rfc5321)
+    Received,     unchecked { "Received"      },  Received,       multi,    None,
 
     /// (rfc2045)
     ContentType,  unchecked { "Content-Type"  }, MediaType,       maxOne,   None,
@@ -111,7 +112,71 @@ def_headers! {
     /// - `read-date`: when the resource this body is based on was read (to create the body)
     /// - `size`: the size this resource should have, note that `Content-Size` is NOT a mail
     ///           related header but specific to http.
-    ContentDisposition, unchecked { "Content-Disposition"       }, Disposition, maxOne, None
+    ContentDisposition, unchecked { "Content-Disposition"       }, Disposition, maxOne, None,
+
+    /// The language(s) of the intended audience of the body (rfc3282)
+    ContentLanguage, unchecked { "Content-Language" }, LanguageList,    maxOne, None,
+    /// A (absolute or relative) URI identifying the body, used to resolve relative
+    /// URIs in html bodies (rfc2557)
+    ContentLocation, unchecked { "Content-Location" }, ContentLocation, maxOne, None,
+    /// The MD5 digest of the body (rfc1864)
+    ContentMd5,      unchecked { "Content-MD5"      }, ContentMd5,      maxOne, None,
+    /// The time it takes to play a time based media body in seconds (rfc3803)
+    ContentDuration, unchecked { "Content-Duration" }, ContentDuration, maxOne, None,
+
+    /// The identifier of the mailing list the mail was send through (rfc2919)
+    ListId,              unchecked { "List-Id"               }, ListId,              maxOne, None,
+    /// URIs to unsubscribe from the mailing list (rfc2369)
+    ListUnsubscribe,     unchecked { "List-Unsubscribe"      }, UriList,             maxOne, None,
+    /// Signals support for one-click unsubscription (rfc8058)
+    ///
+    /// This requires a `List-Unsubscribe` header with a https URI, which
+    /// is checked by the contextual validators.
+    ListUnsubscribePost, unchecked { "List-Unsubscribe-Post" }, OneClickUnsubscribe, maxOne, validator_list_unsubscribe_post,
+    /// URIs to subscribe to the mailing list (rfc2369)
+    ListSubscribe,       unchecked { "List-Subscribe"        }, UriList,             maxOne, None,
+    /// URIs to post to the mailing list or `NO` if posting is not allowed (rfc2369)
+    ListPost,            unchecked { "List-Post"             }, ListPost,            maxOne, None,
+    /// URIs to get help about the mailing list (rfc2369)
+    ListHelp,            unchecked { "List-Help"             }, UriList,             maxOne, None,
+    /// URIs to access the archive of the mailing list (rfc2369)
+    ListArchive,         unchecked { "List-Archive"          }, UriList,             maxOne, None,
+    /// URIs to contact the owner of the mailing list (rfc2369)
+    ListOwner,           unchecked { "List-Owner"            }, UriList,             maxOne, None,
+
+    /// Marks mails which were generated automatically, e.g. to prevent vacation responders
+    /// from responding to them (rfc3834)
+    AutoSubmitted,         unchecked { "Auto-Submitted"           }, AutoSubmitted,        maxOne, None,
+    /// The (non-standard) precedence of the mail e.g. `bulk` (rfc2076)
+    Precedence,            unchecked { "Precedence"               }, Precedence,           maxOne, None,
+    /// The kinds of automatic responses which should be suppressed (Microsoft specific)
+    XAutoResponseSuppress, unchecked { "X-Auto-Response-Suppress" }, AutoResponseSuppress, maxOne, None,
+
+    /// The importance of the mail (rfc2156)
+    Importance, unchecked { "Importance" }, Importance, maxOne, None,
+    /// The priority of the mail, affecting delivery in X.400 gateways (rfc2156)
+    Priority,   unchecked { "Priority"   }, Priority,   maxOne, None,
+    /// The (non-standard) priority of the mail from 1 (highest) to 5 (lowest)
+    XPriority,  unchecked { "X-Priority" }, XPriority,  maxOne, None,
+
+    /// The public key of the sender for opportunistic encryption (Autocrypt Level 1)
+    Autocrypt,       unchecked { "Autocrypt"        }, Autocrypt,       maxOne, None,
+    /// The public keys of the other recipients, placed in the encrypted part
+    /// of a mail (Autocrypt Level 1)
+    AutocryptGossip, unchecked { "Autocrypt-Gossip" }, AutocryptGossip, multi,  None,
+
+    /// The results of message authentication checks (e.g. SPF, DKIM, DMARC) done by
+    /// a receiving server (rfc8601)
+    AuthenticationResults,    unchecked { "Authentication-Results"     }, AuthenticationResults,    multi, None,
+    /// The `Authentication-Results` of a ARC set (rfc8617)
+    ArcAuthenticationResults, unchecked { "ARC-Authentication-Results" }, ArcAuthenticationResults, multi, None,
+
+    /// Requests a message disposition notification (read receipt) to be send to
+    /// the given mailboxes (rfc8098)
+    DispositionNotificationTo, unchecked { "Disposition-Notification-To" }, MailboxList,  maxOne, None,
+    /// The original recipient of the mail as given by the sender, added by the
+    /// MTA (rfc8098, rfc3464)
+    OriginalRecipient,         unchecked { "Original-Recipient"          }, TypedAddress, maxOne, None
 }
 
 mod validators {
@@ -120,7 +185,10 @@ mod validators {
     use ::{ HeaderMap, HeaderKind, HeaderName, HeaderObj };
     use ::error::HeaderValidationError;
 
-    use super::{ _From, ResentFrom, Sender, ResentSender, ResentDate };
+    use super::{
+        _From, ResentFrom, Sender, ResentSender, ResentDate,
+        ListUnsubscribe
+    };
 
 
     pub fn from(map: &HeaderMap) -> Result<(), HeaderValidationError> {
@@ -180,6 +248,17 @@ mod validators {
         }
         validate_resent_block(&block)
     }
+
+    pub fn list_unsubscribe_post(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        let has_https_uri = map.get(ListUnsubscribe)
+            .filter_map(|res| res.ok())
+            .any(|uris| uris.iter().any(|uri| uri.scheme().eq_ignore_ascii_case("https")));
+
+        if !has_https_uri {
+            header_validation_bail!(kind: ListUnsubscribePostWithoutHttpsUri);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -188,7 +267,8 @@ mod test {
     use ::{HeaderMap, HeaderKind};
     use ::headers::{
         _From, ResentFrom, ResentTo, ResentDate,
-        Sender, ResentSender, Subject
+        Sender, ResentSender, Subject,
+        ListUnsubscribe, ListUnsubscribePost
     };
 
     test!(from_validation_normal {
@@ -273,4 +353,23 @@ mod test {
         assert_ok!(map.use_contextual_validators());
     });
 
+    test!(list_unsubscribe_post_with_https_uri {
+        let mut map = HeaderMap::new();
+        map.insert(ListUnsubscribe ::auto_body( [
+            "mailto:unsub@list.test",
+            "https://list.test/unsub/123"
+        ] )?);
+        map.insert(ListUnsubscribePost ::auto_body( "List-Unsubscribe=One-Click" )?);
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(list_unsubscribe_post_without_https_uri {
+        let mut map = HeaderMap::new();
+        map.insert(ListUnsubscribePost ::auto_body( "List-Unsubscribe=One-Click" )?);
+        assert_err!(map.use_contextual_validators());
+
+        map.insert(ListUnsubscribe ::auto_body( "mailto:unsub@list.test" )?);
+        assert_err!(map.use_contextual_validators());
+    });
+
 }
\ No newline at end of file
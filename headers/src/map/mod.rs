@@ -3,6 +3,7 @@
 //! It also contains some helper types like iterator types
 //! for the HeaderMap etc.
 use std::marker::PhantomData;
+use std::mem;
 use std::iter::ExactSizeIterator;
 use std::fmt::{self, Debug};
 use std::collections::HashSet;
@@ -386,6 +387,42 @@ impl HeaderMap {
         self._insert(name, H::MAX_ONE, obj)
     }
 
+    /// Inserts the given header into the map at the front of all other headers.
+    ///
+    /// This works like `insert` (including replacing existing headers if
+    /// `H::MAX_ONE` is `true`) except that the header is placed before all
+    /// other headers in the map. This is mainly meant to be used for trace
+    /// headers like `Received` which should be prepended by relays.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::header_components::{self, DateTime};
+    ///
+    /// let mut map = headers!{
+    ///     Subject: "..."
+    /// }.unwrap();
+    ///
+    /// let trace = header_components::Received::new(DateTime::now());
+    /// map.prepend(Received::body(trace));
+    ///
+    /// let (first, _) = map.iter().next().unwrap();
+    /// assert_eq!(first.as_str(), "Received");
+    /// # }
+    /// ```
+    pub fn prepend<H>(&mut self, header: Header<H>)
+        where H: HeaderKind
+    {
+        let name = header.name();
+        let obj: Box<HeaderObj> = Box::new(header);
+        self._insert_front(name, H::MAX_ONE, obj)
+    }
+
     /// Insert a HeaderObj into the header map.
     #[doc(hidden)]
     pub fn insert_untyped(&mut self, obj: Box<HeaderObj>) {
@@ -401,6 +438,24 @@ impl HeaderMap {
         }
     }
 
+    /// Inserts the header object in front of all other headers.
+    ///
+    /// The inner map can only append entries, so it's rebuilt with the
+    /// new header as first entry followed by all previous entries (which
+    /// is `O(n)` just like inserting at the front of a `Vec` would be).
+    /// If `max_one` is true previous headers with the same name are dropped.
+    fn _insert_front(&mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>) {
+        let capacity = self.inner_map.len() + 1;
+        let old_map = mem::replace(&mut self.inner_map, TotalOrderMultiMap::with_capacity(capacity));
+        self.inner_map.add(name, obj);
+        for (old_name, old_obj) in old_map {
+            if max_one && old_name == name {
+                continue;
+            }
+            self.inner_map.add(old_name, old_obj);
+        }
+    }
+
     /// Insert all given headers in order into this header map.
     ///
     /// The insertion order of the given headers into this map
@@ -889,6 +944,34 @@ mod test {
         assert!(res.next().is_none());
     }
 
+    #[test]
+    fn prepend_keeps_order_of_other_headers() {
+        let mut headers = headers! {
+            Subject: "abc",
+            Comments: "1st"
+        }.unwrap();
+
+        headers.prepend(Comments::auto_body("0th").unwrap());
+
+        let names = headers.iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Comments", "Subject", "Comments"]);
+
+        let comments = headers.get(Comments)
+            .map(|h| h.unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec!["0th", "1st"]);
+
+        headers.prepend(Subject::auto_body("new").unwrap());
+
+        let names = headers.iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "Comments", "Comments"]);
+        assert_eq!(headers.get_single(Subject).unwrap().unwrap().as_str(), "new");
+    }
+
     #[test]
     fn fmt_debug() {
         let headers = headers! {
@@ -68,46 +68,71 @@ impl HeaderName {
     /// by only allowing names in "snake case" no case
     /// insensitive comparison or case conversion is needed
     /// for header names
+    ///
+    /// As only exceptions words consisting of upper case letters
+    /// and digits are allowed if they contain at last one digit,
+    /// so that e.g. the canonical `Content-MD5` name can be used,
+    /// and names can start with a `ARC-` prefix, so that the canonical
+    /// names of the ARC headers (e.g. `ARC-Seal`, rfc8617) can be used.
     fn validate_name(name: &SoftAsciiStr) -> Result<(), InvalidHeaderName> {
-        let mut begin_of_word = true;
+        let invalid = || InvalidHeaderName { invalid_name: name.to_owned().into() };
         if name.len() < 1 {
-            return Err(InvalidHeaderName { invalid_name: name.to_owned().into() });
+            return Err(invalid());
         }
 
-        for ch in name.as_str().chars() {
+        let rest = match name.as_str() {
+            "ARC-" => return Err(invalid()),
+            name if name.starts_with("ARC-") => &name[4..],
+            name => name
+        };
+
+        let mut word = WordCase::None;
+        for ch in rest.chars() {
             if !is_ftext( ch ) {
-                return Err(InvalidHeaderName { invalid_name: name.to_owned().into() });
+                return Err(invalid());
             }
-            match ch {
-                'a'...'z' => {
-                    if begin_of_word {
-                        return Err(InvalidHeaderName { invalid_name: name.to_owned().into() });
-                    }
-                },
-                'A'...'Z' => {
-                    if begin_of_word {
-                        begin_of_word = false;
-                    } else {
-                        return Err(InvalidHeaderName { invalid_name: name.to_owned().into() });
+            word = match (word, ch) {
+                (WordCase::None, 'A'...'Z') => WordCase::Capital,
+                (WordCase::None, 'a'...'z') => return Err(invalid()),
+                (WordCase::Capital, 'a'...'z') |
+                (WordCase::Lower, 'a'...'z') => WordCase::Lower,
+                (WordCase::Capital, 'A'...'Z') |
+                (WordCase::Upper, 'A'...'Z') => WordCase::Upper,
+                (WordCase::UpperWithDigit, 'A'...'Z') => WordCase::UpperWithDigit,
+                (_, 'a'...'z') | (_, 'A'...'Z') => return Err(invalid()),
+                (WordCase::Upper, '0'...'9') => WordCase::UpperWithDigit,
+                (WordCase::None, '0'...'9') => WordCase::Capital,
+                (word, '0'...'9') => word,
+                (word, ch) => {
+                    if ch < '!' || ch > '~' || ch == ':' || word == WordCase::Upper {
+                        return Err(invalid());
                     }
-                },
-                '0'...'9' => {
-                    begin_of_word = false;
-                },
-                ch => {
-                    if ch < '!' || ch > '~' || ch == ':' {
-                        return Err(InvalidHeaderName { invalid_name: name.to_owned().into() });
-                    }
-                    begin_of_word = true;
+                    WordCase::None
                 }
-
-            }
-
+            };
+        }
+        if word == WordCase::Upper {
+            return Err(invalid());
         }
         Ok( () )
     }
 }
 
+/// Casing of the current word while validating a header name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum WordCase {
+    /// at the begin of a word
+    None,
+    /// only one upper case letter or digits so far
+    Capital,
+    /// a upper case letter followed by lower case letters
+    Lower,
+    /// only upper case letters
+    Upper,
+    /// only upper case letters and at last one digit
+    UpperWithDigit
+}
+
 #[derive(Clone, Debug, Fail)]
 #[fail(display = "given name is not a valid header name: {:?}", invalid_name)]
 pub struct InvalidHeaderName {
@@ -139,6 +164,10 @@ mod test {
             "Some-Header",
             "33",
             "Some34",
+            "Content-MD5",
+            "X-SHA256-Sum",
+            "ARC-Seal",
+            "ARC-Authentication-Results",
             // even trough they seem wrong the email standard only states
             // header field names have to be at last one char and can
             // only consist of printable US-ACII chars without :
@@ -164,6 +193,12 @@ mod test {
             "ans",
             "all-lower-calse",
             "ALL-UPPER-CASE",
+            "Content-MD5a",
+            "Content-Md5A",
+            "ARC",
+            "ARC-",
+            "ARC-seal",
+            "X-ARC-Seal",
             "",
             "a:b",
             ":",
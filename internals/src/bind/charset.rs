@@ -0,0 +1,374 @@
+//! Conversion between text in different charsets and rust strings.
+//!
+//! The conversion is done through the `CharsetConverter` trait, which
+//! makes it possible to plug in an implementation supporting additional
+//! charsets.
+//!
+//! `BuiltinCharsets` is a converter which supports the charsets most
+//! commonly used in mails which do not require large mapping tables:
+//! `utf-8`, `us-ascii`, `iso-8859-1`, `iso-8859-15`, `windows-1252` and
+//! `utf-16`/`utf-16be`/`utf-16le` (decoding only).
+//!
+//! If the (default) `encoding-rs` feature is enabled `EncodingRsCharsets`
+//! is provided, which supports all charsets of the WHATWG encoding standard
+//! (e.g. `shift_jis`, `euc-jp`, `iso-2022-jp`, `gb2312`/`gbk`, `gb18030`,
+//! `big5` or `euc-kr`) using the `encoding_rs` crate. In this case
+//! `BuiltinCharsets` uses it for all charsets it doesn't support itself.
+//!
+//! Charset names are matched case insensitive and common aliases
+//! (e.g. `latin1` or `cp1252`) are supported.
+use std::fmt::Debug;
+
+use ::error::{EncodingError, EncodingErrorKind};
+
+/// A converter between text in a given charset and rust strings.
+pub trait CharsetConverter: Debug + Send + Sync {
+
+    /// Decodes the bytes, which are text in the given charset.
+    ///
+    /// # Error
+    ///
+    /// Fails with `EncodingErrorKind::UnsupportedCharset` if the charset
+    /// is not supported by the converter. Implementations can also fail
+    /// with `EncodingErrorKind::Malformed` if the bytes are not valid
+    /// text in the charset, but should prefer a lossy decoding (replacing
+    /// invalid sequences with `U+FFFD`) like mail clients do.
+    fn decode(&self, charset: &str, bytes: &[u8]) -> Result<String, EncodingError>;
+
+    /// Encodes the text to the given charset.
+    ///
+    /// # Error
+    ///
+    /// Fails with `EncodingErrorKind::UnsupportedCharset` if the charset
+    /// is not supported by the converter and with
+    /// `EncodingErrorKind::NotEncodable` if the text contains characters
+    /// which can not be represented in the charset.
+    fn encode(&self, charset: &str, text: &str) -> Result<Vec<u8>, EncodingError>;
+}
+
+/// The charsets supported by `BuiltinCharsets`.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+enum Charset {
+    Utf8,
+    UsAscii,
+    Latin1,
+    Latin9,
+    Windows1252,
+    Utf16,
+    Utf16Be,
+    Utf16Le
+}
+
+impl Charset {
+
+    fn lookup(charset: &str) -> Option<Charset> {
+        use self::Charset::*;
+        let charset = charset.trim().to_ascii_lowercase();
+        let charset = match &*charset {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Utf8,
+            "us-ascii" | "ascii" | "ansi_x3.4-1968" | "iso646-us" | "us" => UsAscii,
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" => Latin1,
+            "iso-8859-15" | "iso8859-15" | "iso_8859-15" | "latin-9" | "latin9" => Latin9,
+            "windows-1252" | "cp1252" | "x-cp1252" => Windows1252,
+            "utf-16" => Utf16,
+            "utf-16be" => Utf16Be,
+            "utf-16le" => Utf16Le,
+            _ => return None
+        };
+        Some(charset)
+    }
+
+    fn name(&self) -> &'static str {
+        use self::Charset::*;
+        match *self {
+            Utf8 => "utf-8",
+            UsAscii => "us-ascii",
+            Latin1 => "iso-8859-1",
+            Latin9 => "iso-8859-15",
+            Windows1252 => "windows-1252",
+            Utf16 => "utf-16",
+            Utf16Be => "utf-16be",
+            Utf16Le => "utf-16le"
+        }
+    }
+}
+
+/// A `CharsetConverter` for the charsets which are supported without
+/// any additional dependencies.
+///
+/// Like mail clients (and the WHATWG encoding standard) text labeled as
+/// `us-ascii` or `iso-8859-1` is decoded as `windows-1252`, as mails
+/// labeled with them often contain e.g. "smart quotes" from `windows-1252`.
+/// Encoding is strict for all charsets.
+///
+/// If the `encoding-rs` feature is enabled all other charsets are
+/// converted using `EncodingRsCharsets`.
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct BuiltinCharsets;
+
+impl CharsetConverter for BuiltinCharsets {
+
+    fn decode(&self, charset: &str, bytes: &[u8]) -> Result<String, EncodingError> {
+        use self::Charset::*;
+        let builtin = match Charset::lookup(charset) {
+            Some(builtin) => builtin,
+            None => return fallback().decode(charset, bytes)
+        };
+        let text = match builtin {
+            Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            UsAscii | Latin1 | Windows1252 => bytes.iter().map(|&byte| decode_windows_1252(byte)).collect(),
+            Latin9 => bytes.iter().map(|&byte| decode_latin9(byte)).collect(),
+            Utf16 => {
+                if bytes.starts_with(&[0xFF, 0xFE]) {
+                    decode_utf16(&bytes[2..], u16::from_le_bytes)
+                } else if bytes.starts_with(&[0xFE, 0xFF]) {
+                    decode_utf16(&bytes[2..], u16::from_be_bytes)
+                } else {
+                    decode_utf16(bytes, u16::from_be_bytes)
+                }
+            },
+            Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Utf16Le => decode_utf16(bytes, u16::from_le_bytes)
+        };
+        Ok(text)
+    }
+
+    fn encode(&self, charset: &str, text: &str) -> Result<Vec<u8>, EncodingError> {
+        use self::Charset::*;
+        let charset = match Charset::lookup(charset) {
+            Some(builtin) => builtin,
+            None => return fallback().encode(charset, text)
+        };
+        let encode_char: fn(char) -> Option<u8> = match charset {
+            Utf8 => return Ok(text.as_bytes().to_owned()),
+            UsAscii => |ch| if ch.is_ascii() { Some(ch as u8) } else { None },
+            Latin1 => |ch| if (ch as u32) < 0x100 { Some(ch as u8) } else { None },
+            Latin9 => encode_latin9,
+            Windows1252 => encode_windows_1252,
+            Utf16 | Utf16Be | Utf16Le => return Err(unsupported_charset(charset.name()))
+        };
+
+        text.chars()
+            .map(|ch| encode_char(ch).ok_or_else(|| {
+                EncodingError::from(EncodingErrorKind::NotEncodable { encoding: charset.name() })
+                    .with_str_context(text)
+            }))
+            .collect()
+    }
+}
+
+/// The converter used by `BuiltinCharsets` for charsets it doesn't support.
+#[cfg(feature="encoding-rs")]
+fn fallback() -> impl CharsetConverter {
+    EncodingRsCharsets
+}
+
+/// The converter used by `BuiltinCharsets` for charsets it doesn't support.
+#[cfg(not(feature="encoding-rs"))]
+fn fallback() -> impl CharsetConverter {
+    NoCharsets
+}
+
+/// A converter not supporting any charset.
+#[cfg(not(feature="encoding-rs"))]
+#[derive(Debug)]
+struct NoCharsets;
+
+#[cfg(not(feature="encoding-rs"))]
+impl CharsetConverter for NoCharsets {
+    fn decode(&self, charset: &str, _bytes: &[u8]) -> Result<String, EncodingError> {
+        Err(unsupported_charset(charset))
+    }
+
+    fn encode(&self, charset: &str, _text: &str) -> Result<Vec<u8>, EncodingError> {
+        Err(unsupported_charset(charset))
+    }
+}
+
+fn unsupported_charset(charset: &str) -> EncodingError {
+    EncodingError::from(EncodingErrorKind::UnsupportedCharset)
+        .with_str_context(charset)
+}
+
+/// A `CharsetConverter` using the `encoding_rs` crate.
+///
+/// It supports all charsets (and charset labels) of the WHATWG encoding
+/// standard, which includes the legacy multi byte charsets commonly used
+/// in mails, like `shift_jis`, `iso-2022-jp`, `gb2312` or `big5`.
+///
+/// Decoding is lossy, i.e. invalid sequences are replaced with `U+FFFD`.
+/// Encoding is strict and fails if the text contains characters which
+/// can not be represented in the charset. Text can not be encoded to
+/// `utf-16` (and the `replacement` encoding), as `encoding_rs` doesn't
+/// support it.
+#[cfg(feature="encoding-rs")]
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct EncodingRsCharsets;
+
+#[cfg(feature="encoding-rs")]
+impl CharsetConverter for EncodingRsCharsets {
+
+    fn decode(&self, charset: &str, bytes: &[u8]) -> Result<String, EncodingError> {
+        let encoding = lookup_encoding(charset)?;
+        let (text, _had_errors) = encoding.decode_without_bom_handling(bytes);
+        Ok(text.into_owned())
+    }
+
+    fn encode(&self, charset: &str, text: &str) -> Result<Vec<u8>, EncodingError> {
+        let encoding = lookup_encoding(charset)?;
+        if encoding.output_encoding() != encoding {
+            return Err(unsupported_charset(charset));
+        }
+        let (bytes, _, had_errors) = encoding.encode(text);
+        if had_errors {
+            return Err(EncodingError::from(EncodingErrorKind::NotEncodable { encoding: encoding.name() })
+                .with_str_context(text));
+        }
+        Ok(bytes.into_owned())
+    }
+}
+
+#[cfg(feature="encoding-rs")]
+fn lookup_encoding(charset: &str) -> Result<&'static encoding_rs::Encoding, EncodingError> {
+    encoding_rs::Encoding::for_label_no_replacement(charset.trim().as_bytes())
+        .ok_or_else(|| unsupported_charset(charset))
+}
+
+/// Returns true if the charset is a alias for `utf-8`.
+pub fn is_utf8(charset: &str) -> bool {
+    Charset::lookup(charset) == Some(Charset::Utf8)
+}
+
+/// Decodes the bytes using the `BuiltinCharsets` converter.
+pub fn decode(charset: &str, bytes: &[u8]) -> Result<String, EncodingError> {
+    BuiltinCharsets.decode(charset, bytes)
+}
+
+/// Encodes the text using the `BuiltinCharsets` converter.
+pub fn encode(charset: &str, text: &str) -> Result<Vec<u8>, EncodingError> {
+    BuiltinCharsets.encode(charset, text)
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2)
+        .map(|chunk| match *chunk {
+            [first, second] => from_bytes([first, second]),
+            // a incomplete code unit
+            _ => 0xFFFD
+        })
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+/// The characters for the bytes `0x80..0xA0` in `windows-1252`.
+///
+/// The bytes not used by `windows-1252` are mapped to the
+/// corresponding C1 control characters.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}'
+];
+
+/// The bytes in which `iso-8859-15` differs from `iso-8859-1`.
+const LATIN9_DIFFERENCES: [(u8, char); 8] = [
+    (0xA4, '\u{20AC}'), (0xA6, '\u{0160}'), (0xA8, '\u{0161}'), (0xB4, '\u{017D}'),
+    (0xB8, '\u{017E}'), (0xBC, '\u{0152}'), (0xBD, '\u{0153}'), (0xBE, '\u{0178}')
+];
+
+fn decode_windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char
+    }
+}
+
+fn encode_windows_1252(ch: char) -> Option<u8> {
+    match ch as u32 {
+        0..=0x7F | 0xA0..=0xFF => Some(ch as u8),
+        _ => WINDOWS_1252_HIGH.iter()
+            .position(|&high| high == ch)
+            .map(|index| 0x80 + index as u8)
+    }
+}
+
+fn decode_latin9(byte: u8) -> char {
+    LATIN9_DIFFERENCES.iter()
+        .find(|&&(latin9, _)| latin9 == byte)
+        .map(|&(_, ch)| ch)
+        .unwrap_or(byte as char)
+}
+
+fn encode_latin9(ch: char) -> Option<u8> {
+    if let Some(&(byte, _)) = LATIN9_DIFFERENCES.iter().find(|&&(_, latin9)| latin9 == ch) {
+        return Some(byte);
+    }
+    let byte = ch as u32;
+    let replaced = LATIN9_DIFFERENCES.iter().any(|&(latin9, _)| latin9 as u32 == byte);
+    if byte < 0x100 && !replaced {
+        Some(byte as u8)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_single_byte_charsets() {
+        assert_eq!(assert_ok!(decode("ISO-8859-1", b"Caf\xE9 \x93quoted\x94")), "Café “quoted”");
+        assert_eq!(assert_ok!(decode("windows-1252", b"\x80 5")), "€ 5");
+        assert_eq!(assert_ok!(decode("latin-9", b"\xA4 5 \xE9")), "€ 5 é");
+        assert_eq!(assert_ok!(decode("utf-8", b"Caf\xC3\xA9 \xFF")), "Café \u{FFFD}");
+        assert_eq!(assert_ok!(decode("utf-16", b"\xFF\xFEh\x00i\x00")), "hi");
+        assert_eq!(assert_ok!(decode("utf-16be", b"\x00h\x00i")), "hi");
+    }
+
+    #[test]
+    fn encode_single_byte_charsets() {
+        assert_eq!(assert_ok!(encode("iso-8859-1", "Café")), b"Caf\xE9");
+        assert_eq!(assert_ok!(encode("cp1252", "“€”")), b"\x93\x80\x94");
+        assert_eq!(assert_ok!(encode("iso-8859-15", "€é")), b"\xA4\xE9");
+
+        let err = assert_err!(encode("iso-8859-1", "€"));
+        assert_eq!(err.kind(), EncodingErrorKind::NotEncodable { encoding: "iso-8859-1" });
+        let err = assert_err!(encode("iso-8859-15", "¤"));
+        assert_eq!(err.kind(), EncodingErrorKind::NotEncodable { encoding: "iso-8859-15" });
+        let err = assert_err!(encode("us-ascii", "é"));
+        assert_eq!(err.kind(), EncodingErrorKind::NotEncodable { encoding: "us-ascii" });
+    }
+
+    #[test]
+    fn unsupported_charsets_are_reported() {
+        let err = assert_err!(decode("x-no-such-charset", b"abc"));
+        assert_eq!(err.kind(), EncodingErrorKind::UnsupportedCharset);
+        assert_eq!(err.str_context(), Some("x-no-such-charset"));
+        assert!(is_utf8("UTF8"));
+        assert!(!is_utf8("latin1"));
+    }
+
+    #[cfg(feature="encoding-rs")]
+    #[test]
+    fn multi_byte_charsets() {
+        let japanese = "\u{65E5}\u{672C}\u{8A9E}";
+        let shift_jis = b"\x93\xFA\x96\x7B\x8C\xEA";
+        assert_eq!(assert_ok!(decode("Shift_JIS", shift_jis)), japanese);
+        assert_eq!(assert_ok!(encode("shift_jis", japanese)), shift_jis);
+
+        let chinese = "\u{4E2D}\u{6587}";
+        let gb2312 = b"\xD6\xD0\xCE\xC4";
+        assert_eq!(assert_ok!(decode("gb2312", gb2312)), chinese);
+        assert_eq!(assert_ok!(encode("GB2312", chinese)), gb2312);
+
+        assert_eq!(assert_ok!(decode("iso-2022-jp", b"\x1B$BF|K\\8l\x1B(B")), japanese);
+        assert_eq!(assert_ok!(EncodingRsCharsets.decode("shift_jis", b"\x93\xFA\x81")), "\u{65E5}\u{FFFD}");
+
+        let err = assert_err!(encode("shift_jis", "\u{1F600}"));
+        assert_eq!(err.kind(), EncodingErrorKind::NotEncodable { encoding: "Shift_JIS" });
+        let err = assert_err!(EncodingRsCharsets.encode("utf-16le", "hi"));
+        assert_eq!(err.kind(), EncodingErrorKind::UnsupportedCharset);
+    }
+}
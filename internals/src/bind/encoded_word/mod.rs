@@ -1,6 +1,10 @@
 use soft_ascii_string::{ SoftAsciiStr, SoftAsciiChar };
 
+use ::MailType;
+use ::error::{EncodingError, EncodingErrorKind};
+use ::grammar::encoded_word::{EncodedWordContext, try_parse_encoded_word_parts};
 use super::{base64, quoted_printable};
+use super::charset::{CharsetConverter, BuiltinCharsets};
 
 mod impls;
 pub use self::impls::*;
@@ -22,6 +26,41 @@ impl EncodedWordEncoding {
         }
     }
 
+    /// returns the encoding for the given acronym (case insensitive)
+    pub fn from_acronym(acronym: &str) -> Option<Self> {
+        use self::EncodedWordEncoding::*;
+        match acronym {
+            "B" | "b" => Some(Base64),
+            "Q" | "q" => Some(QuotedPrintable),
+            _ => None
+        }
+    }
+
+    /// decodes the payload of a encoded word
+    ///
+    /// The result are the bytes of the text in the charset of the encoded word.
+    /// For the `Q` encoding a `_` in the payload represents a space.
+    pub fn decode<R>(&self, payload: R) -> Result<Vec<u8>, EncodingError>
+        where R: AsRef<[u8]>
+    {
+        use self::EncodedWordEncoding::*;
+        let payload = payload.as_ref();
+        match *self {
+            Base64 => base64::encoded_word_decode(payload),
+            QuotedPrintable => {
+                let mut replaced = Vec::with_capacity(payload.len());
+                for &byte in payload {
+                    if byte == b'_' {
+                        replaced.extend_from_slice(b"=20");
+                    } else {
+                        replaced.push(byte);
+                    }
+                }
+                quoted_printable::encoded_word_decode(replaced)
+            }
+        }
+    }
+
     /// encodes a given utf8 string
     ///
     /// either `self::quoted_printable::encoded_word_encode`
@@ -47,6 +86,206 @@ impl EncodedWordEncoding {
     }
 }
 
+/// Decodes a encoded word (e.g. `=?iso-8859-1?Q?Caf=E9?=`).
+///
+/// The charset of the encoded word is converted using `BuiltinCharsets`,
+/// use `decode_encoded_word_with` to support additional charsets. A
+/// language specification in the charset (`=?utf-8*en?Q?Hy?=`, rfc2231)
+/// is ignored.
+///
+/// # Error
+///
+/// Fails with `EncodingErrorKind::Malformed` if the word is not a valid
+/// encoded word and with `EncodingErrorKind::UnsupportedCharset` if the
+/// charset is not supported.
+pub fn decode_encoded_word(word: &str) -> Result<String, EncodingError> {
+    decode_encoded_word_with(word, &BuiltinCharsets)
+}
+
+/// Decodes a encoded word using the given charset converter.
+///
+/// See `decode_encoded_word`.
+pub fn decode_encoded_word_with(word: &str, converter: &dyn CharsetConverter)
+    -> Result<String, EncodingError>
+{
+    let (charset, bytes) = decode_encoded_word_bytes(word, EncodedWordContext::Text)?;
+    converter.decode(charset, &bytes)
+}
+
+/// Parses the encoded word and decodes its payload, returning the charset and the bytes.
+fn decode_encoded_word_bytes(word: &str, ctx: EncodedWordContext)
+    -> Result<(&str, Vec<u8>), EncodingError>
+{
+    let (charset, encoding, payload) =
+        try_parse_encoded_word_parts(word, ctx, MailType::Ascii)
+            .map_err(|err| err.with_str_context(word))?;
+
+    let charset = charset.split('*').next().unwrap_or(charset);
+    let encoding = EncodedWordEncoding::from_acronym(encoding)
+        .ok_or_else(|| EncodingError::from(EncodingErrorKind::Malformed).with_str_context(word))?;
+    let bytes = encoding.decode(payload)
+        .map_err(|err| err.with_str_context(word))?;
+
+    Ok((charset, bytes))
+}
+
+/// Decodes all encoded words in a header value (rfc2047).
+///
+/// This is meant for the values of headers of received mails, e.g. the
+/// `Subject` (use `EncodedWordContext::Text`) or the display name of a
+/// mailbox (use `EncodedWordContext::Phrase`).
+///
+/// - The value is unfolded, i.e. line breaks followed by whitespace are removed.
+/// - Whitespace between two adjacent encoded words is dropped, the bytes of
+///   adjacent encoded words with the same charset are converted together
+///   (some mail programs split multi-byte characters across encoded words).
+/// - Each encoded word can have a different charset, the rfc2231 language
+///   suffix (`=?utf-8*en?Q?Hy?=`) is ignored.
+/// - Words which are not valid encoded words (in the given context) or
+///   use a charset not supported by `BuiltinCharsets` are kept literally.
+/// - In the `Phrase` context quoted strings are unquoted, encoded words
+///   in them are not decoded.
+pub fn decode_encoded_words(text: &str, ctx: EncodedWordContext) -> String {
+    decode_encoded_words_with(text, ctx, &BuiltinCharsets)
+}
+
+/// Decodes all encoded words in a header value using the given charset converter.
+///
+/// See `decode_encoded_words`.
+pub fn decode_encoded_words_with(
+    text: &str,
+    ctx: EncodedWordContext,
+    converter: &dyn CharsetConverter
+) -> String {
+    let text = unfold(text);
+    let mut out = String::with_capacity(text.len());
+    // the charset and bytes of the last encoded words, if there
+    // was no other word after them
+    let mut pending: Option<(&str, Vec<u8>)> = None;
+    let mut gap = "";
+    let mut rest = &*text;
+
+    while !rest.is_empty() {
+        let trimmed = rest.trim_start_matches(is_wsp);
+        if trimmed.len() < rest.len() {
+            gap = &rest[..rest.len() - trimmed.len()];
+            rest = trimmed;
+            continue;
+        }
+
+        let quoted = ctx == EncodedWordContext::Phrase && rest.starts_with('"');
+        let end =
+            if quoted {
+                quoted_string_end(rest)
+            } else {
+                rest.find(|ch| is_wsp(ch) || (ctx == EncodedWordContext::Phrase && ch == '"'))
+                    .unwrap_or(rest.len())
+            };
+        let (word, next) = rest.split_at(end);
+        rest = next;
+
+        let decoded =
+            if quoted {
+                None
+            } else {
+                decode_encoded_word_bytes(word, ctx).ok()
+                    .filter(|&(charset, ref bytes)| converter.decode(charset, bytes).is_ok())
+            };
+
+        match decoded {
+            Some((charset, bytes)) => {
+                match pending {
+                    Some((pending_charset, ref mut pending_bytes))
+                        if pending_charset.eq_ignore_ascii_case(charset) =>
+                    {
+                        pending_bytes.extend_from_slice(&bytes);
+                    },
+                    Some(_) => {
+                        flush_pending(&mut out, pending.take(), converter);
+                        pending = Some((charset, bytes));
+                    },
+                    None => {
+                        out.push_str(gap);
+                        pending = Some((charset, bytes));
+                    }
+                }
+            },
+            None => {
+                flush_pending(&mut out, pending.take(), converter);
+                out.push_str(gap);
+                if quoted {
+                    unquote(word, &mut out);
+                } else {
+                    out.push_str(word);
+                }
+            }
+        }
+        gap = "";
+    }
+    flush_pending(&mut out, pending, converter);
+    out.push_str(gap);
+    out
+}
+
+fn flush_pending(out: &mut String, pending: Option<(&str, Vec<u8>)>, converter: &dyn CharsetConverter) {
+    if let Some((charset, bytes)) = pending {
+        match converter.decode(charset, &bytes) {
+            Ok(text) => out.push_str(&text),
+            Err(_) => out.push_str(&String::from_utf8_lossy(&bytes))
+        }
+    }
+}
+
+fn is_wsp(ch: char) -> bool {
+    ch == ' ' || ch == '\t'
+}
+
+/// Removes all line breaks which are followed by whitespace and trailing line breaks.
+fn unfold(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut lines = text.trim_end_matches(['\r', '\n']).split('\n').peekable();
+    while let Some(line) = lines.next() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        out.push_str(line);
+        let is_folded = lines.peek()
+            .map(|next| next.starts_with(is_wsp))
+            .unwrap_or(true);
+        if !is_folded {
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+
+/// Returns the position after the closing quote of the quoted string at the start of `text`.
+fn quoted_string_end(text: &str) -> usize {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            return idx + 1;
+        }
+    }
+    text.len()
+}
+
+fn unquote(quoted: &str, out: &mut String) {
+    let content = &quoted[1..];
+    let content = content.strip_suffix('"').unwrap_or(content);
+    let mut escaped = false;
+    for ch in content.chars() {
+        if ch == '\\' && !escaped {
+            escaped = true;
+        } else {
+            out.push(ch);
+            escaped = false;
+        }
+    }
+}
+
 pub trait EncodedWordWriter {
     fn write_char( &mut self, ch: SoftAsciiChar );
     fn write_charset( &mut self );
@@ -89,4 +328,63 @@ pub trait EncodedWordWriter {
             self.write_char(ch)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_single_words() {
+        assert_eq!(assert_ok!(decode_encoded_word("=?utf-8?B?Q2Fmw6k=?=")), "Café");
+        assert_eq!(assert_ok!(decode_encoded_word("=?ISO-8859-1?q?Caf=E9_au_lait?=")), "Café au lait");
+        assert_eq!(assert_ok!(decode_encoded_word("=?us-ascii?Q?a_?=")), "a ");
+        assert_eq!(assert_ok!(decode_encoded_word("=?windows-1252*de?Q?=93Hallo=94?=")), "“Hallo”");
+
+        let err = assert_err!(decode_encoded_word("=?utf-8?X?abc?="));
+        assert_eq!(err.kind(), EncodingErrorKind::Malformed);
+        let err = assert_err!(decode_encoded_word("=?utf-8?Q?a b?="));
+        assert_eq!(err.kind(), EncodingErrorKind::Malformed);
+        let err = assert_err!(decode_encoded_word("=?x-unknown?B?gqA=?="));
+        assert_eq!(err.kind(), EncodingErrorKind::UnsupportedCharset);
+    }
+
+    #[cfg(feature="encoding-rs")]
+    #[test]
+    fn decode_multi_byte_charset_words() {
+        assert_eq!(assert_ok!(decode_encoded_word("=?shift_jis?B?gqA=?=")), "\u{3042}");
+        assert_eq!(assert_ok!(decode_encoded_word("=?GB2312?B?1tDOxA==?=")), "\u{4E2D}\u{6587}");
+    }
+
+    #[test]
+    fn decode_header_values() {
+        let text = decode_encoded_words(
+            "Re: =?utf-8?Q?Caf=C3=A9?=\r\n =?ISO-8859-1*fr?Q?_au_lait?= ist =?utf-8?B?4pyU?=",
+            EncodedWordContext::Text
+        );
+        assert_eq!(text, "Re: Café au lait ist ✔");
+
+        // a multi-byte character split across two encoded words
+        let text = decode_encoded_words("=?utf-8?Q?=E2=9C?= =?UTF-8?Q?=94?=", EncodedWordContext::Text);
+        assert_eq!(text, "✔");
+
+        let text = decode_encoded_words(
+            "a =?utf-8?X?b?= =?x-unknown?B?gqA=?= =?utf-8?Q?c?=d  =?utf-8?Q?e?= ",
+            EncodedWordContext::Text
+        );
+        assert_eq!(text, "a =?utf-8?X?b?= =?x-unknown?B?gqA=?= =?utf-8?Q?c?=d  e ");
+    }
+
+    #[test]
+    fn decode_phrases() {
+        let text = decode_encoded_words(
+            "\"Doe, \\\"J\\\"\" =?utf-8?Q?J=C3=B6rg?= \"=?utf-8?Q?x?=\"",
+            EncodedWordContext::Phrase
+        );
+        assert_eq!(text, "Doe, \"J\" Jörg =?utf-8?Q?x?=");
+
+        // `(` is valid in encoded words in text but not in phrases
+        let text = decode_encoded_words("=?utf-8?Q?(a)?=", EncodedWordContext::Phrase);
+        assert_eq!(text, "=?utf-8?Q?(a)?=");
+    }
+}
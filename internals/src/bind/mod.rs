@@ -1,6 +1,7 @@
 //! This module contains bindings to a number of external crates.
 
 pub mod encoded_word;
+pub mod charset;
 pub mod base64;
 pub mod quoted_string;
 pub mod quoted_printable;
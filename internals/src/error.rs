@@ -31,6 +31,9 @@ pub enum EncodingErrorKind {
     #[fail(display = "malformed data")]
     Malformed,
 
+    #[fail(display = "unsupported charset")]
+    UnsupportedCharset,
+
     #[fail(display = "the mail body data cannot be accessed")]
     AccessingMailBodyFailed,
 
@@ -13,6 +13,8 @@ extern crate quoted_string;
 extern crate media_type_impl_utils;
 extern crate percent_encoding;
 extern crate vec1;
+#[cfg(feature="encoding-rs")]
+extern crate encoding_rs;
 
 //NOTE: this would be worth it's own independent crate for utility macros
 #[macro_use]
@@ -125,13 +125,16 @@ pub fn vec_insert_bytes(target: &mut Vec<u8>, idx: usize, source: &[u8]) {
     let old_len = target.len();
     let insertion_len = source.len();
     let source_ptr = source.as_ptr();
+    let moved_data_len = old_len - idx;
+
+    // reserve before getting the insertion point, as reserving
+    // can reallocate the buffer
+    target.reserve(insertion_len);
+
     let insertion_point = unsafe {
         // SAFE: we panic if idx > target.len(), through idx == target.len() is fine
         target.as_mut_ptr().offset(idx as isize)
     };
-    let moved_data_len = old_len - idx;
-
-    target.reserve(insertion_len);
 
     unsafe {
         // SAFE 1: we reserved insertion_len and insertion_point is at most old_len
@@ -196,6 +199,17 @@ mod tests {
         assert!(base.capacity() >= 6);
     }
 
+    #[test]
+    fn inserting_slices_into_full_vec() {
+        let mut base = Vec::with_capacity(4);
+        base.extend_from_slice(&[0u8, 1, 2, 3]);
+        let new = &[10u8, 11];
+
+        vec_insert_bytes(&mut base, 2, new);
+
+        assert_eq!(&*base, &[0u8, 1, 10, 11, 2, 3]);
+    }
+
     #[test]
     fn inserting_slices_large_in_the_middle() {
         let mut base = vec![0u8, 1u8, 2u8, 3u8];
@@ -98,8 +98,11 @@ impl From<GeneralError> for MailSendError {
 #[derive(Debug, Fail)]
 pub enum OtherValidationError {
 
-    #[fail(display = "no To header was present")]
-    NoTo
+    #[fail(display = "no To, Cc or Bcc header was present")]
+    NoTo,
+
+    #[fail(display = "To, Cc and Bcc headers contain no mailbox")]
+    NoRecipients
 }
 
 impl From<OtherValidationError> for HeaderValidationError {
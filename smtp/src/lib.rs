@@ -17,6 +17,10 @@
 //! and those used fro smtp mail delivery are not necessary exactly
 //! the same (e.g. for bounce back mails and some no-reply setups).
 //!
+//! Delivery status notifications (bounces) can be created and parsed
+//! using the `dsn` module (re-exported from `mail-core`). Created ones
+//! should be send using `MailRequest::new_bounce`.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -60,6 +64,7 @@
 //!
 extern crate futures;
 extern crate new_tokio_smtp;
+extern crate vec1;
 extern crate mail_core as mail;
 extern crate mail_internals;
 #[cfg_attr(test, macro_use)]
@@ -74,6 +79,7 @@ mod request;
 mod send_mail;
 
 pub use self::request::MailRequest;
+pub use mail::dsn;
 #[cfg(feature="extended-api")]
 pub use self::request::derive_envelop_data_from_mail;
 
@@ -1,5 +1,7 @@
 use std::mem;
 
+use vec1::Vec1;
+
 use new_tokio_smtp::send_mail::{
     self as smtp,
     MailAddress,
@@ -12,8 +14,8 @@ use mail_internals::{
     error::EncodingError
 };
 use headers::{
-    headers::{Sender, _From, _To},
-    header_components::Mailbox,
+    headers::{Sender, _From, _To, Cc, Bcc},
+    header_components::{Mailbox, AddressList},
     error::{BuildInValidationError}
 };
 use mail::{
@@ -63,17 +65,34 @@ impl MailRequest {
         MailRequest { mail, envelop_data: Some(envelop) }
     }
 
+    /// create a new `MailRequest` for a bounce, e.g. a DSN created with `dsn::Dsn`
+    ///
+    /// The smtp envelop data is derived from the mail (see `derive_envelop_data_from_mail`)
+    /// except that a empty (`<>`) reverse path is used, as required for delivery status
+    /// notifications so that they can not cause further bounces.
+    pub fn new_bounce(mail: Mail) -> Result<Self, MailError> {
+        let mut envelop = derive_envelop_data_from_mail(&mail)?;
+        envelop.from = None;
+        Ok(MailRequest::new_with_envelop(mail, envelop))
+    }
+
     /// replace the smtp `EnvelopData`
     pub fn override_envelop(&mut self, envelop: EnvelopData) -> Option<EnvelopData> {
         mem::replace(&mut self.envelop_data, Some(envelop))
     }
 
     pub fn _into_mail_with_envelop(self) -> Result<(Mail, EnvelopData), MailError> {
+        let MailRequest { mut mail, envelop_data } = self;
         let envelop =
-            if let Some(envelop) = self.envelop_data { envelop }
-            else { derive_envelop_data_from_mail(&self.mail)? };
-
-        Ok((self.mail, envelop))
+            if let Some(envelop) = envelop_data { envelop }
+            else {
+                let envelop = derive_envelop_data_from_mail(&mail)?;
+                // the bcc recipients are only part of the envelop
+                mail.headers_mut().remove(Bcc);
+                envelop
+            };
+
+        Ok((mail, envelop))
     }
 
     #[cfg(not(feature="extended-api"))]
@@ -86,7 +105,8 @@ impl MailRequest {
     ///
     /// If envelop data was explicitly set it is returned.
     /// If no envelop data was explicitly given it is derived from the
-    /// Mail header fields using `derive_envelop_data_from_mail`.
+    /// Mail header fields using `derive_envelop_data_from_mail`
+    /// and the `Bcc` header is removed from the returned mail.
     #[cfg(feature="extended-api")]
     #[inline(always)]
     pub fn into_mail_with_envelop(self) -> Result<(Mail, EnvelopData), MailError> {
@@ -115,17 +135,22 @@ fn mailaddress_from_mailbox(mailbox: &Mailbox) -> Result<MailAddress, EncodingEr
 /// as smtp from else the single mailbox in from
 /// is used as smtp from.
 ///
-/// All `To`'s are used as smtp recipients.
+/// All mailboxes in `To`, `Cc` and `Bcc` are used as smtp
+/// recipients, mailboxes in groups (e.g. `Team: a@x, b@x;`)
+/// are expanded into their members. Addresses appearing
+/// multiple times are only used once.
 ///
-/// **`Cc`/`Bcc` is currently no supported/has no
-/// special handling**
+/// **Note that this doesn't remove the `Bcc` header from the mail,
+/// sending the mail through a `MailRequest` does.**
 ///
 /// # Error
 ///
 /// An error is returned if there is:
 ///
 /// - No From header
-/// - No To header
+/// - No `To`, `Cc` or `Bcc` header
+/// - No recipient at all, e.g. if `To` is `undisclosed-recipients:;`
+///   and there is no `Cc`/`Bcc` header
 /// - A From header with multiple addresses but no Sender header
 ///
 pub fn derive_envelop_data_from_mail(mail: &Mail)
@@ -148,15 +173,30 @@ pub fn derive_envelop_data_from_mail(mail: &Mail)
             mailaddress_from_mailbox(from.first())?
         };
 
-    let smtp_to =
-        if let Some(to) = headers.get_single(_To) {
-            let to = to?;
-            to.try_mapped_ref(mailaddress_from_mailbox)?
-        } else {
-            return Err(AnotherOtherValidationError::NoTo.into());
-        };
+    let mut lists: Vec<&AddressList> = Vec::new();
+    if let Some(to) = headers.get_single(_To) {
+        lists.push(to?);
+    }
+    if let Some(cc) = headers.get_single(Cc) {
+        lists.push(cc?);
+    }
+    if let Some(bcc) = headers.get_single(Bcc) {
+        lists.push(bcc?);
+    }
+    if lists.is_empty() {
+        return Err(AnotherOtherValidationError::NoTo.into());
+    }
+
+    let mut recipients: Vec<MailAddress> = Vec::new();
+    for mailbox in lists.into_iter().flat_map(|list| list.mailboxes()) {
+        let address = mailaddress_from_mailbox(mailbox)?;
+        if !recipients.iter().any(|known| known.as_str() == address.as_str()) {
+            recipients.push(address);
+        }
+    }
 
-    //TODO Cc, Bcc
+    let smtp_to = Vec1::try_from_vec(recipients)
+        .map_err(|_| AnotherOtherValidationError::NoRecipients)?;
 
     Ok(EnvelopData {
         from: Some(smtp_from),
@@ -175,7 +215,9 @@ mod test {
             test_utils::CTX
         };
         use headers::{
-            headers::{_From, _To, Sender},
+            HeaderTryFrom,
+            headers::{_From, _To, Cc, Bcc, Sender},
+            header_components::Address
         };
 
 
@@ -246,6 +288,172 @@ mod test {
                 "das@ding.test"
             );
         }
+
+        #[test]
+        fn expand_groups() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                _To: (
+                    "das@ding.test",
+                    Address::group("Team", vec!["a@team.test", "b@team.test"]).unwrap(),
+                    Address::undisclosed_recipients()
+                )
+            }.unwrap());
+
+            let envelop_data = derive_envelop_data_from_mail(&mail).unwrap();
+            let recipients = envelop_data.to.iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>();
+
+            assert_eq!(recipients, vec![
+                "das@ding.test", "a@team.test", "b@team.test"
+            ]);
+        }
+
+        #[test]
+        fn use_cc_and_bcc_without_duplicates() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                _To: ["das@ding.test"],
+                Cc: [Address::group("Team", vec!["a@team.test", "das@ding.test"]).unwrap()],
+                Bcc: [Address::try_from("c@d.test").unwrap(), Address::try_from("a@team.test").unwrap()]
+            }.unwrap());
+
+            let envelop_data = derive_envelop_data_from_mail(&mail).unwrap();
+            let recipients = envelop_data.to.iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>();
+
+            assert_eq!(recipients, vec![
+                "das@ding.test", "a@team.test", "c@d.test"
+            ]);
+        }
+
+        #[test]
+        fn fail_if_there_are_no_recipients() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                _To: [Address::undisclosed_recipients()]
+            }.unwrap());
+
+            let envelop_data = derive_envelop_data_from_mail(&mail);
+
+            //assert is_err
+            envelop_data.unwrap_err();
+        }
+
+        #[test]
+        fn fail_if_there_is_no_to_cc_or_bcc() {
+            let mut mail = Mail::new_singlepart_mail(mock_resource());
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"]
+            }.unwrap());
+
+            let envelop_data = derive_envelop_data_from_mail(&mail);
+
+            //assert is_err
+            envelop_data.unwrap_err();
+        }
+    }
+
+    mod into_mail_with_envelop {
+        use super::super::{MailRequest, derive_envelop_data_from_mail};
+        use mail::{
+            Mail,
+            Resource,
+            test_utils::CTX
+        };
+        use headers::{
+            HeaderTryFrom,
+            headers::{_From, _To, Bcc},
+            header_components::Address
+        };
+
+        #[test]
+        fn hidden_list_with_bcc() {
+            let mut mail = Mail::new_singlepart_mail(Resource::plain_text("hidden", CTX.unwrap()));
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                _To: [Address::undisclosed_recipients()],
+                Bcc: [
+                    Address::try_from("a@b.test").unwrap(),
+                    Address::group("Team", vec!["c@d.test", "e@f.test"]).unwrap()
+                ]
+            }.unwrap());
+
+            let (mail, envelop_data) = MailRequest::new(mail)
+                ._into_mail_with_envelop()
+                .unwrap();
+
+            let recipients = envelop_data.to.iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(recipients, vec!["a@b.test", "c@d.test", "e@f.test"]);
+
+            assert!(!mail.headers().contains(Bcc));
+            assert!(mail.headers().contains(_To));
+        }
+
+        #[test]
+        fn bcc_without_to() {
+            let mut mail = Mail::new_singlepart_mail(Resource::plain_text("hidden", CTX.unwrap()));
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                Bcc: [Address::try_from("a@b.test").unwrap()]
+            }.unwrap());
+
+            let (mail, envelop_data) = MailRequest::new(mail)
+                ._into_mail_with_envelop()
+                .unwrap();
+
+            assert_eq!(envelop_data.to.first().as_str(), "a@b.test");
+            assert!(!mail.headers().contains(Bcc));
+        }
+
+        #[test]
+        fn explicit_envelop_keeps_bcc() {
+            let mut mail = Mail::new_singlepart_mail(Resource::plain_text("hidden", CTX.unwrap()));
+            mail.insert_headers(headers! {
+                _From: ["ape@caffe.test"],
+                Bcc: [Address::try_from("a@b.test").unwrap()]
+            }.unwrap());
+            let envelop = derive_envelop_data_from_mail(&mail).unwrap();
+
+            let (mail, _) = MailRequest::new_with_envelop(mail, envelop)
+                ._into_mail_with_envelop()
+                .unwrap();
+
+            assert!(mail.headers().contains(Bcc));
+        }
+    }
+
+    mod new_bounce {
+        use super::super::MailRequest;
+        use mail::{
+            Mail,
+            Resource,
+            test_utils::CTX
+        };
+        use headers::headers::{_From, _To};
+
+        #[test]
+        fn uses_empty_reverse_path() {
+            let mut mail = Mail::new_singlepart_mail(Resource::plain_text("bounce", CTX.unwrap()));
+            mail.insert_headers(headers! {
+                _From: ["mailer-daemon@relay.test"],
+                _To: ["ape@caffe.test"]
+            }.unwrap());
+
+            let (_, envelop_data) = MailRequest::new_bounce(mail).unwrap()
+                ._into_mail_with_envelop()
+                .unwrap();
+
+            assert!(envelop_data.from.is_none());
+            assert_eq!(envelop_data.to.first().as_str(), "ape@caffe.test");
+        }
     }
 
     mod mailaddress_from_mailbox {
@@ -267,7 +475,7 @@ mod test {
             let mb = Mailbox::from(Email::new("tast@tost.test").unwrap());
             let address = mailaddress_from_mailbox(&mb).unwrap();
             assert_eq!(address.as_str(), "tast@tost.test");
-            assert_eq!(address.needs_smtputf8(), false);
+            assert!(!address.needs_smtputf8());
         }
 
         #[test]
@@ -275,7 +483,7 @@ mod test {
             let mb = Mailbox::from(Email::new("tüst@tost.test").unwrap());
             let address = mailaddress_from_mailbox(&mb).unwrap();
             assert_eq!(address.as_str(), "tüst@tost.test");
-            assert_eq!(address.needs_smtputf8(), true);
+            assert!(address.needs_smtputf8());
         }
 
         #[test]
@@ -283,7 +491,7 @@ mod test {
             let mb = Mailbox::from(Email::new("tast@tüst.test").unwrap());
             let address = mailaddress_from_mailbox(&mb).unwrap();
             assert_eq!(address.as_str(), "tast@xn--tst-hoa.test");
-            assert_eq!(address.needs_smtputf8(), false);
+            assert!(!address.needs_smtputf8());
         }
 
         #[test]
@@ -291,7 +499,7 @@ mod test {
             let mb = Mailbox::from(Email::new("töst@tüst.test").unwrap());
             let address = mailaddress_from_mailbox(&mb).unwrap();
             assert_eq!(address.as_str(), "töst@tüst.test");
-            assert_eq!(address.needs_smtputf8(), true);
+            assert!(address.needs_smtputf8());
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,479 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Range,
+    path::{Path, Component}
+};
+
+use failure::Error;
+use futures::Future;
+
+use mail_core::{
+    Resource, Source, Data, Metadata, IRI, Context,
+    compose::MailParts,
+    html::{tokenize, Tag, Token}
+};
+use mail_headers::header_components::MediaType;
+use mail_internals::bind::charset::{CharsetConverter, BuiltinCharsets};
+
+use super::PathRebaseable;
+
+/// Which local references are embedded by `embed_local_images_with`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum LocalReferences {
+    /// Only embed relative paths which stay inside of the base dir.
+    ///
+    /// References with absolute paths or `..` segments are not embedded
+    /// (and not rewritten). This is the default.
+    #[default]
+    InsideBaseDir,
+
+    /// Embed all local references, including absolute paths and paths
+    /// with `..` segments.
+    ///
+    /// Only use this if the html can be trusted, as it allows it to
+    /// embed any file readable through the context into the mail.
+    Any
+}
+
+/// Embeds local images referenced by the `text/html` bodies of the mail parts.
+///
+/// This is a post-render step which can be used with the result of
+/// `TemplateExt::render_to_mail_parts` (or any other `MailParts`). It
+/// scans all loaded (`Resource::Data`) `text/html` alternative bodies
+/// (decoded using their `charset`) for image references (the `src` of
+/// `<img>` and `<input type="image">` elements and `background`
+/// attributes) referring to local resources, i.e. either `path:` IRIs
+/// or relative paths without a scheme. Character
+/// references (e.g. `&amp;`) and percent-escapes (e.g. `%20`) in the
+/// references are decoded before resolving them.
+///
+/// Relative paths are resolved against `base_dir` (e.g. `Template::base_dir`),
+/// references with absolute paths or `..` segments are not embedded, use
+/// `embed_local_images_with` and `LocalReferences::Any` to embed them.
+/// All referenced resources are loaded using the context (which assigns
+/// them a new content id), the references are rewritten to `cid:` urls
+/// and the resources are added as inline embeddings of the body, so that
+/// they are placed in a `multipart/related` body with it.
+///
+/// Other references (e.g. `https:`, `cid:` or `data:` urls) are not changed.
+///
+/// # Error
+///
+/// The returned future fails if any of the referenced resources can not be loaded.
+pub fn embed_local_images(
+    parts: MailParts,
+    base_dir: impl AsRef<Path>,
+    ctx: &impl Context
+) -> impl Future<Item=MailParts, Error=Error> {
+    embed_local_images_with(parts, base_dir, LocalReferences::InsideBaseDir, ctx)
+}
+
+/// Like `embed_local_images` but `references` decides which local references are embedded.
+pub fn embed_local_images_with(
+    parts: MailParts,
+    base_dir: impl AsRef<Path>,
+    references: LocalReferences,
+    ctx: &impl Context
+) -> impl Future<Item=MailParts, Error=Error> {
+    let mut sources = HashMap::new();
+    for body in parts.alternative_bodies.iter() {
+        if let Some(html) = html_body(&body.resource) {
+            for (_, reference) in find_image_references(&html) {
+                if sources.contains_key(&*reference) {
+                    continue;
+                }
+                if let Some(iri) = local_iri(&reference, base_dir.as_ref(), references) {
+                    sources.insert(reference.into_owned(), Resource::Source(Source {
+                        iri,
+                        use_media_type: Default::default(),
+                        use_file_name: None
+                    }));
+                }
+            }
+        }
+    }
+
+    Resource::load_container(sources, ctx)
+        .map_err(Error::from)
+        .map(move |embeddings| rewrite_bodies(parts, &embeddings))
+}
+
+fn rewrite_bodies(mut parts: MailParts, embeddings: &HashMap<String, Resource>) -> MailParts {
+    for body in parts.alternative_bodies.iter_mut() {
+        let rewritten = html_body(&body.resource).and_then(|html| {
+            let mut used = Vec::new();
+            let html = rewrite_references(&html, |reference| {
+                let embedding = embeddings.get(reference)?;
+                let cid = embedding.content_id()?;
+                if !used.iter().any(|known: &&Resource| known.content_id() == Some(cid)) {
+                    used.push(embedding);
+                }
+                Some(format!("cid:{}", cid.as_str()))
+            });
+            if used.is_empty() {
+                None
+            } else {
+                let used = used.into_iter().cloned().collect::<Vec<_>>();
+                Some((html, used))
+            }
+        });
+
+        if let Some((html, used)) = rewritten {
+            if let Resource::Data(ref mut data) = body.resource {
+                *data = replace_html(data, html);
+            }
+            body.inline_embeddings.extend(used);
+        }
+    }
+    parts
+}
+
+/// Returns the decoded html of the resource if it is a loaded `text/html` body.
+///
+/// Bodies which can not be decoded (e.g. because of an unsupported charset)
+/// are skipped.
+fn html_body(resource: &Resource) -> Option<String> {
+    match *resource {
+        Resource::Data(ref data) if is_html(data.media_type()) => data.decode_text().ok(),
+        _ => None
+    }
+}
+
+/// Creates a copy of the html body data with the html replaced.
+///
+/// The html is encoded using the charset of the data, if that isn't
+/// possible (e.g. for `utf-16`) `utf-8` is used and the `charset`
+/// parameter is changed accordingly.
+fn replace_html(data: &Data, html: String) -> Data {
+    let charset = match data.media_type().get_param("charset") {
+        Some(charset) => charset.to_content().into_owned(),
+        None if ::std::str::from_utf8(data.buffer()).is_ok() => "utf-8".to_owned(),
+        None => "us-ascii".to_owned()
+    };
+    if let Ok(buffer) = BuiltinCharsets.encode(&charset, &html) {
+        return Data::new(buffer, data.metadata().clone());
+    }
+    let mut meta: Metadata = (**data.metadata()).clone();
+    meta.media_type.set_param("charset", "utf-8");
+    Data::new(html.into_bytes(), meta)
+}
+
+fn is_html(media_type: &MediaType) -> bool {
+    media_type.full_type() == "text/html"
+}
+
+/// Returns the IRI for the given reference, if it references a local resource
+/// which is allowed by `references`.
+///
+/// The reference is expected to have decoded character references. Any query
+/// or fragment is removed and percent-escapes in the path are decoded.
+fn local_iri(reference: &str, base_dir: &Path, references: LocalReferences) -> Option<IRI> {
+    let reference = reference.trim();
+    if reference.is_empty() || reference.starts_with('#') || reference.starts_with("//") {
+        return None;
+    }
+
+    let path =
+        if has_scheme(reference) {
+            if !reference.starts_with("path:") {
+                return None;
+            }
+            &reference["path:".len()..]
+        } else {
+            reference
+        };
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    let path = percent_decode(path)?;
+    if path.is_empty() {
+        return None;
+    }
+    let mut iri = IRI::from_parts("path", &path).ok()?;
+
+    if references == LocalReferences::InsideBaseDir && !stays_inside_base_dir(iri.tail()) {
+        return None;
+    }
+
+    iri.rebase_to_include_base_dir(base_dir).ok()?;
+    Some(iri)
+}
+
+/// True if the path is relative and has no `..` segments.
+fn stays_inside_base_dir(path: &str) -> bool {
+    Path::new(path).components().all(|component| match component {
+        Component::Normal(_) | Component::CurDir => true,
+        Component::ParentDir | Component::RootDir | Component::Prefix(_) => false
+    })
+}
+
+/// Decodes percent-escapes, returns `None` for malformed escapes or non utf-8 results.
+fn percent_decode(input: &str) -> Option<Cow<'_, str>> {
+    if !input.contains('%') {
+        return Some(Cow::Borrowed(input));
+    }
+    let mut out = Vec::with_capacity(input.len());
+    let mut bytes = input.bytes();
+    while let Some(bch) = bytes.next() {
+        if bch == b'%' {
+            let high = bytes.next().and_then(|bch| (bch as char).to_digit(16))?;
+            let low = bytes.next().and_then(|bch| (bch as char).to_digit(16))?;
+            out.push((high << 4 | low) as u8);
+        } else {
+            out.push(bch);
+        }
+    }
+    String::from_utf8(out).ok().map(Cow::Owned)
+}
+
+fn has_scheme(reference: &str) -> bool {
+    match reference.find(':') {
+        Some(end) => {
+            let scheme = &reference[..end];
+            scheme.starts_with(|ch: char| ch.is_ascii_alphabetic())
+                && scheme.chars().all(|ch| ch.is_ascii_alphanumeric() || "+-.".contains(ch))
+        },
+        None => false
+    }
+}
+
+/// Replaces all image references for which `replacement` returns `Some`.
+fn rewrite_references<F>(html: &str, mut replacement: F) -> String
+    where F: FnMut(&str) -> Option<String>
+{
+    let mut out = String::with_capacity(html.len());
+    let mut last_end = 0;
+    for (range, reference) in find_image_references(html) {
+        if let Some(new_reference) = replacement(&reference) {
+            out.push_str(&html[last_end..range.start]);
+            out.push_str(&new_reference);
+            last_end = range.end;
+        }
+    }
+    out.push_str(&html[last_end..]);
+    out
+}
+
+/// Finds all image references in the html.
+///
+/// This are the `src` attributes of `<img>` and `<input type="image">`
+/// elements and all `background` attributes. The range of the raw value
+/// is returned together with the value with decoded character references.
+///
+/// Values in comments, `<script>` and `<style>` elements are ignored.
+fn find_image_references(html: &str) -> Vec<(Range<usize>, Cow<'_, str>)> {
+    tokenize(html).into_iter()
+        .filter_map(|token| match token {
+            Token::Tag(tag) => Some(tag),
+            _ => None
+        })
+        .filter(|tag| !tag.closing)
+        .flat_map(|tag| {
+            let has_image_src = is_image_element(&tag);
+            tag.attributes.into_iter()
+                .filter(move |attr| attr.name == "background" || (has_image_src && attr.name == "src"))
+        })
+        .map(|attr| (attr.value_range.clone(), attr.decoded_value()))
+        .collect()
+}
+
+/// True for elements whose `src` attribute refers to a image.
+fn is_image_element(tag: &Tag) -> bool {
+    match &*tag.name {
+        "img" => true,
+        "input" => tag.attribute("type")
+            .map(|kind| kind.decoded_value().trim().eq_ignore_ascii_case("image"))
+            .unwrap_or(false),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::Future;
+    use soft_ascii_string::SoftAsciiString;
+    use vec1::Vec1;
+
+    use mail_core::{
+        compose::BodyPart,
+        context::CompositeContext,
+        default_impl::{simple_cpu_pool, HashedIdGen, InMemoryResourceLoader, Mux}
+    };
+    use mail_headers::header_components::Domain;
+
+    use super::*;
+
+    #[test]
+    fn finds_only_image_references() {
+        let html = concat!(
+            "<html><body background=\"bg.png\"><!-- <img src=\"commented.png\"> -->",
+            "<img alt='x' src='logo.png'><img src=other.png >",
+            "<script>var a = '<img src=\"script.png\">';</script>",
+            "<input type=\"IMAGE\" src=\"button.png\"><input type=text src=\"text.png\">",
+            "<audio src=\"sound.ogg\"></audio><iframe src=\"frame.html\"></iframe>",
+            "<a href=\"link.png\">link</a></body></html>"
+        );
+        let references = find_image_references(html).into_iter()
+            .map(|(range, reference)| {
+                assert_eq!(&html[range], reference);
+                reference.into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(references, vec!["bg.png", "logo.png", "other.png", "button.png"]);
+    }
+
+    #[test]
+    fn references_are_decoded() {
+        let html = "<img src=\"a&amp;b.png\"><td background='x&#x20;y.png'>";
+        let references = find_image_references(html).into_iter()
+            .map(|(range, reference)| (&html[range], reference.into_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(references, vec![
+            ("a&amp;b.png", "a&b.png".to_owned()),
+            ("x&#x20;y.png", "x y.png".to_owned())
+        ]);
+
+        let base_dir = Path::new("/templates");
+        let inside = LocalReferences::InsideBaseDir;
+        assert_eq!(local_iri("a%20b.png", base_dir, inside).unwrap().as_str(), "path:/templates/a b.png");
+        assert_eq!(local_iri("path:img/%C3%BC.png", base_dir, inside).unwrap().as_str(), "path:/templates/img/ü.png");
+        assert_eq!(local_iri("logo.png?v=2#top", base_dir, inside).unwrap().as_str(), "path:/templates/logo.png");
+        assert!(local_iri("a%2.png", base_dir, inside).is_none());
+        assert!(local_iri("%2E%2E/secret.png", base_dir, inside).is_none());
+        assert!(local_iri("?v=2", base_dir, inside).is_none());
+    }
+
+    #[test]
+    fn only_local_references_are_embedded() {
+        let base_dir = Path::new("/templates/welcome");
+        let inside = LocalReferences::InsideBaseDir;
+        assert_eq!(local_iri("logo.png", base_dir, inside).unwrap().as_str(), "path:/templates/welcome/logo.png");
+        assert_eq!(local_iri("./img/logo.png", base_dir, inside).unwrap().as_str(), "path:/templates/welcome/./img/logo.png");
+        assert_eq!(local_iri("path:img/logo.png", base_dir, inside).unwrap().as_str(), "path:/templates/welcome/img/logo.png");
+        assert!(local_iri("https://example.test/logo.png", base_dir, inside).is_none());
+        assert!(local_iri("//example.test/logo.png", base_dir, inside).is_none());
+        assert!(local_iri("cid:logo@example.test", base_dir, inside).is_none());
+        assert!(local_iri("data:image/png;base64,AAAA", base_dir, inside).is_none());
+        assert!(local_iri("", base_dir, inside).is_none());
+    }
+
+    #[test]
+    fn references_outside_of_base_dir_require_opt_in() {
+        let base_dir = Path::new("/templates/welcome");
+        let inside = LocalReferences::InsideBaseDir;
+        assert!(local_iri("/img/logo.png", base_dir, inside).is_none());
+        assert!(local_iri("path:/etc/passwd", base_dir, inside).is_none());
+        assert!(local_iri("../secret.png", base_dir, inside).is_none());
+        assert!(local_iri("img/../../secret.png", base_dir, inside).is_none());
+        assert!(local_iri("path:img/../../secret.png", base_dir, inside).is_none());
+
+        let any = LocalReferences::Any;
+        assert_eq!(local_iri("/img/logo.png", base_dir, any).unwrap().as_str(), "path:/img/logo.png");
+        assert_eq!(local_iri("../logo.png", base_dir, any).unwrap().as_str(), "path:/templates/welcome/../logo.png");
+    }
+
+    fn ctx_with(loader: InMemoryResourceLoader) -> impl Context {
+        CompositeContext::new(
+            Mux::new().with_loader("path", loader).unwrap(),
+            simple_cpu_pool(),
+            HashedIdGen::new(
+                Domain::from_unchecked("example.test".to_owned()),
+                SoftAsciiString::from_unchecked("tmpl")
+            ).unwrap()
+        )
+    }
+
+    fn html_parts(html: &[u8], media_type: &str, ctx: &impl Context) -> MailParts {
+        let body = BodyPart::new(Resource::Data(Data::new(html, Metadata {
+            file_meta: Default::default(),
+            media_type: MediaType::parse(media_type).unwrap(),
+            content_id: ctx.generate_content_id()
+        })));
+        MailParts {
+            alternative_bodies: Vec1::new(body),
+            inline_embeddings: Vec::new(),
+            attachments: Vec::new()
+        }
+    }
+
+    fn html_of(parts: &MailParts) -> (&[u8], String) {
+        match parts.alternative_bodies[0].resource {
+            Resource::Data(ref data) => {
+                let charset = data.media_type().get_param("charset").unwrap().to_content().into_owned();
+                (&**data.buffer(), charset)
+            },
+            _ => panic!("expected loaded html body")
+        }
+    }
+
+    #[test]
+    fn images_are_embedded_and_references_rewritten() {
+        let loader = InMemoryResourceLoader::new();
+        let png = MediaType::parse("image/png").unwrap();
+        loader.insert("/base/logo.png", &b"logo"[..], png.clone());
+        loader.insert("/base/img/bg.png", &b"bg"[..], png.clone());
+        loader.insert("/base/a b&c.png", &b"abc"[..], png);
+        let ctx = ctx_with(loader);
+
+        let html = concat!(
+            "<div background=\"img/bg.png\"><img src=\"logo.png\">",
+            "<img src=\"logo.png\"><img src=\"https://example.test/x.png\">",
+            "<img src=\"a%20b&amp;c.png\"></div>"
+        );
+        let parts = html_parts(html.as_bytes(), "text/html; charset=utf-8", &ctx);
+
+        let parts = embed_local_images(parts, "/base", &ctx).wait().unwrap();
+
+        let body = &parts.alternative_bodies[0];
+        assert_eq!(body.inline_embeddings.len(), 3);
+        let cid = |index: usize| body.inline_embeddings[index].content_id().unwrap().as_str().to_owned();
+        let (bg_cid, logo_cid, abc_cid) = (cid(0), cid(1), cid(2));
+        let expected = format!(
+            concat!(
+                "<div background=\"cid:{}\"><img src=\"cid:{}\">",
+                "<img src=\"cid:{}\"><img src=\"https://example.test/x.png\">",
+                "<img src=\"cid:{}\"></div>"
+            ),
+            bg_cid, logo_cid, logo_cid, abc_cid
+        );
+        match body.resource {
+            Resource::Data(ref data) => assert_eq!(&**data.buffer(), expected.as_bytes()),
+            _ => panic!("expected loaded html body")
+        }
+    }
+
+    #[test]
+    fn html_is_decoded_and_encoded_using_its_charset() {
+        let loader = InMemoryResourceLoader::new();
+        loader.insert("/base/logo.png", &b"logo"[..], MediaType::parse("image/png").unwrap());
+        let ctx = ctx_with(loader);
+
+        let html = b"<p>Gr\xfc\xdfe</p><img src=\"logo.png\">";
+        let parts = html_parts(html, "text/html; charset=iso-8859-1", &ctx);
+        let parts = embed_local_images(parts, "/base", &ctx).wait().unwrap();
+
+        let cid = parts.alternative_bodies[0].inline_embeddings[0].content_id().unwrap().as_str().to_owned();
+        let expected = format!("<p>Gr\u{fc}\u{df}e</p><img src=\"cid:{}\">", cid);
+        let (buffer, charset) = html_of(&parts);
+        assert_eq!(charset, "iso-8859-1");
+        assert_eq!(buffer, &*BuiltinCharsets.encode("iso-8859-1", &expected).unwrap());
+    }
+
+    #[test]
+    fn html_which_can_not_be_encoded_in_its_charset_uses_utf8() {
+        let loader = InMemoryResourceLoader::new();
+        loader.insert("/base/logo.png", &b"logo"[..], MediaType::parse("image/png").unwrap());
+        let ctx = ctx_with(loader);
+
+        let html = BuiltinCharsets.encode("utf-8", "<img src=\"logo.png\">").unwrap()
+            .iter()
+            .flat_map(|&bch| vec![bch, 0])
+            .collect::<Vec<u8>>();
+        let parts = html_parts(&html, "text/html; charset=utf-16le", &ctx);
+        let parts = embed_local_images(parts, "/base", &ctx).wait().unwrap();
+
+        let cid = parts.alternative_bodies[0].inline_embeddings[0].content_id().unwrap().as_str().to_owned();
+        let (buffer, charset) = html_of(&parts);
+        assert_eq!(charset, "utf-8");
+        assert_eq!(buffer, format!("<img src=\"cid:{}\">", cid).as_bytes());
+    }
+}
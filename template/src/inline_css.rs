@@ -0,0 +1,588 @@
+use mail_core::html::{tokenize, Tag, Token};
+
+/// Elements which never have content, i.e. are never closed.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "link", "meta", "param", "source", "track", "wbr"
+];
+
+/// Inlines the css rules of `<style>` elements into the `style` attributes of the matching elements.
+///
+/// Many mail clients ignore `<style>` elements, so css should be inlined
+/// for html mail bodies. This is done automatically for templates which
+/// have `inline_css = true` set in their (toml) spec.
+///
+/// Rules are applied respecting the specificity of their selectors and
+/// `!important`, already existing `style` attributes take precedence over
+/// rules from the style sheet (except if the rule is `!important`).
+///
+/// Only type, universal, id, class and attribute (`[name]`, `[name=value]`)
+/// selectors combined using descendant or child combinators are supported.
+/// Rules with other selectors (e.g. `a:hover`) as well as at-rules like media
+/// queries can not be inlined and are kept in a `<style>` element placed where
+/// the first style element was. `<style>` elements with a `media` attribute
+/// (other than `all` or `screen`) are kept unchanged.
+pub fn inline_css(html: &str) -> String {
+    let tokens = tokenize(html);
+
+    let mut style_sheet = StyleSheet::default();
+    let mut inlined_styles = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if let Token::Tag(ref tag) = *token {
+            if !tag.closing && tag.name == "style" && is_inlineable_style(tag) {
+                inlined_styles.push(index);
+                if let Some(Token::RawText(range)) = tokens.get(index + 1) {
+                    style_sheet.parse(&html[range.clone()]);
+                }
+            }
+        }
+    }
+
+    if style_sheet.rules.is_empty() && inlined_styles.is_empty() {
+        return html.to_owned();
+    }
+
+    let mut out = String::with_capacity(html.len());
+    let mut open_elements: Vec<Element> = Vec::new();
+    let mut tokens = tokens.into_iter().enumerate();
+    while let Some((index, token)) = tokens.next() {
+        match token {
+            Token::Text(range) | Token::Other(range) | Token::RawText(range) => {
+                out.push_str(&html[range]);
+            },
+            Token::Tag(ref tag) if inlined_styles.contains(&index) => {
+                if Some(&index) == inlined_styles.first() && !style_sheet.kept.is_empty() {
+                    out.push_str("<style type=\"text/css\">\n");
+                    out.push_str(&style_sheet.kept);
+                    out.push_str("</style>");
+                }
+                // skip the content and end tag of the style element
+                let content_and_end = tokens.by_ref()
+                    .take_while(|(_, token)| match *token {
+                        Token::Tag(ref end) => !(end.closing && end.name == tag.name),
+                        _ => true
+                    });
+                for _ in content_and_end {}
+            },
+            Token::Tag(tag) => {
+                if tag.closing {
+                    if let Some(open) = open_elements.iter().rposition(|element| element.name == tag.name) {
+                        open_elements.truncate(open);
+                    }
+                    out.push_str(&html[tag.range.clone()]);
+                    continue;
+                }
+
+                open_elements.push(Element::new(&tag));
+                let in_head = open_elements.iter().any(|element| element.name == "head");
+                if in_head {
+                    out.push_str(&html[tag.range.clone()]);
+                } else {
+                    write_tag_with_style(html, &tag, &style_sheet, &open_elements, &mut out);
+                }
+                if tag.self_closing || VOID_ELEMENTS.contains(&&*tag.name) {
+                    open_elements.pop();
+                }
+            }
+        }
+    }
+    out
+}
+
+fn is_inlineable_style(tag: &Tag) -> bool {
+    match tag.attribute("media") {
+        Some(media) => {
+            let media = media.value.trim().to_ascii_lowercase();
+            media.is_empty() || media == "all" || media == "screen"
+        },
+        None => true
+    }
+}
+
+fn write_tag_with_style(
+    html: &str,
+    tag: &Tag,
+    style_sheet: &StyleSheet,
+    open_elements: &[Element],
+    out: &mut String
+) {
+    let matching_rules = style_sheet.rules.iter()
+        .filter(|rule| rule.selector.matches(open_elements))
+        .collect::<Vec<_>>();
+    if matching_rules.is_empty() {
+        out.push_str(&html[tag.range.clone()]);
+        return;
+    }
+
+    let existing_style = tag.attribute("style");
+    let inline_declarations = existing_style
+        .map(|style| parse_declarations(style.value))
+        .unwrap_or_default();
+
+    let candidates = matching_rules.iter()
+        .flat_map(|rule| rule.declarations.iter().enumerate().map(move |(index, declaration)| {
+            (Priority::new(declaration, false, rule.selector.specificity(), rule.order, index), declaration)
+        }))
+        .chain(inline_declarations.iter().enumerate().map(|(index, declaration)| {
+            (Priority::new(declaration, true, (0, 0, 0), 0, index), declaration)
+        }));
+
+    let mut declarations: Vec<(Priority, &Declaration)> = Vec::new();
+    for (priority, declaration) in candidates {
+        match declarations.iter().position(|&(_, known)| known.property == declaration.property) {
+            Some(index) => {
+                if priority >= declarations[index].0 {
+                    declarations[index] = (priority, declaration);
+                }
+            },
+            None => declarations.push((priority, declaration))
+        }
+    }
+    declarations.sort_by_key(|&(priority, _)| priority);
+
+    let style = declarations.iter()
+        .map(|&(_, declaration)| {
+            let important = if declaration.important { " !important" } else { "" };
+            format!("{}: {}{}", declaration.property, declaration.value, important)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+        .replace('"', "&quot;");
+    let style_attribute = format!("style=\"{}\"", style);
+
+    match existing_style {
+        Some(existing) => {
+            out.push_str(&html[tag.range.start..existing.range.start]);
+            out.push_str(&style_attribute);
+            out.push_str(&html[existing.range.end..tag.range.end]);
+        },
+        None => {
+            let tag_text = &html[tag.range.clone()];
+            let insert_at =
+                if tag.self_closing {
+                    tag_text.len() - 2
+                } else if tag_text.ends_with('>') {
+                    tag_text.len() - 1
+                } else {
+                    tag_text.len()
+                };
+            let (head, tail) = tag_text.split_at(insert_at);
+            out.push_str(head.trim_end());
+            out.push(' ');
+            out.push_str(&style_attribute);
+            out.push_str(tail);
+        }
+    }
+}
+
+/// A element on the stack of open elements.
+#[derive(Debug)]
+struct Element {
+    name: String,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, String)>
+}
+
+impl Element {
+    fn new(tag: &Tag) -> Self {
+        Element {
+            name: tag.name.clone(),
+            id: tag.attribute("id").map(|id| id.value.trim().to_owned()),
+            classes: tag.attribute("class")
+                .map(|class| class.value.split_whitespace().map(ToOwned::to_owned).collect())
+                .unwrap_or_default(),
+            attributes: tag.attributes.iter()
+                .map(|attr| (attr.name.clone(), attr.value.to_owned()))
+                .collect()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StyleSheet {
+    rules: Vec<Rule>,
+    /// Css which can not be inlined, e.g. media queries.
+    kept: String
+}
+
+impl StyleSheet {
+
+    fn parse(&mut self, css: &str) {
+        let css = strip_comments(css);
+        let mut rest = css.trim_start();
+        while !rest.is_empty() {
+            if rest.starts_with('@') {
+                let end = match (rest.find(';'), rest.find('{')) {
+                    (Some(semicolon), Some(brace)) if semicolon < brace => semicolon + 1,
+                    (Some(semicolon), None) => semicolon + 1,
+                    (_, Some(brace)) => block_end(rest, brace),
+                    (None, None) => rest.len()
+                };
+                self.kept.push_str(rest[..end].trim());
+                self.kept.push('\n');
+                rest = rest[end..].trim_start();
+                continue;
+            }
+
+            let brace = match rest.find('{') {
+                Some(brace) => brace,
+                None => break
+            };
+            let end = block_end(rest, brace);
+            let prelude = rest[..brace].trim();
+            let block = rest[brace + 1..end].trim_end_matches('}');
+            let declarations = parse_declarations(block);
+
+            let mut not_inlineable = Vec::new();
+            for selector in prelude.split(',').map(str::trim) {
+                match Selector::parse(selector) {
+                    Some(parsed) => {
+                        let order = self.rules.len();
+                        self.rules.push(Rule { selector: parsed, declarations: declarations.clone(), order });
+                    },
+                    None => not_inlineable.push(selector)
+                }
+            }
+            if !not_inlineable.is_empty() {
+                self.kept.push_str(&format!("{} {{ {} }}\n", not_inlineable.join(", "), block.trim()));
+            }
+            rest = rest[end..].trim_start();
+        }
+    }
+}
+
+/// Returns the end of the block starting at `open_brace` (after the closing `}`).
+fn block_end(css: &str, open_brace: usize) -> usize {
+    let mut depth = 0;
+    for (index, ch) in css[open_brace..].char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return open_brace + index + 1;
+                }
+            },
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 2..].find("*/").map(|end| &rest[start + 2 + end + 2..]).unwrap_or("");
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug, Clone)]
+struct Declaration {
+    property: String,
+    value: String,
+    important: bool
+}
+
+/// Parses a list of declarations, e.g. the content of a `style` attribute.
+fn parse_declarations(block: &str) -> Vec<Declaration> {
+    split_outside_of_quotes_and_parens(block, ';').into_iter()
+        .filter_map(|declaration| {
+            let colon = declaration.find(':')?;
+            let property = declaration[..colon].trim().to_ascii_lowercase();
+            let mut value = declaration[colon + 1..].trim();
+            if property.is_empty() || value.is_empty() {
+                return None;
+            }
+
+            let mut important = false;
+            if let Some(bang) = value.rfind('!') {
+                if value[bang + 1..].trim().eq_ignore_ascii_case("important") {
+                    important = true;
+                    value = value[..bang].trim_end();
+                }
+            }
+            Some(Declaration { property, value: value.to_owned(), important })
+        })
+        .collect()
+}
+
+fn split_outside_of_quotes_and_parens(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (index, ch) in text.char_indices() {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (Some(_), _) => {},
+            (None, '"') | (None, '\'') => quote = Some(ch),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, _) if ch == separator && depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + ch.len_utf8();
+            },
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+#[derive(Debug)]
+struct Rule {
+    selector: Selector,
+    declarations: Vec<Declaration>,
+    /// The position of the rule in all style sheets.
+    order: usize
+}
+
+/// The priority of a declaration, declarations with a higher priority win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Priority {
+    important: bool,
+    /// Declarations from `style` attributes beat the ones from style sheets.
+    inline: bool,
+    specificity: (u32, u32, u32),
+    rule_order: usize,
+    declaration_order: usize
+}
+
+impl Priority {
+    fn new(
+        declaration: &Declaration,
+        inline: bool,
+        specificity: (u32, u32, u32),
+        rule_order: usize,
+        declaration_order: usize
+    ) -> Self {
+        Priority {
+            important: declaration.important,
+            inline, specificity, rule_order, declaration_order
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child
+}
+
+/// A compound selector, e.g. `p.note#first`.
+#[derive(Debug, Default)]
+struct Compound {
+    element: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, Option<String>)>
+}
+
+impl Compound {
+    fn matches(&self, element: &Element) -> bool {
+        self.element.as_ref().map(|name| *name == element.name).unwrap_or(true)
+            && self.id.as_ref().map(|id| Some(id) == element.id.as_ref()).unwrap_or(true)
+            && self.classes.iter().all(|class| element.classes.contains(class))
+            && self.attributes.iter().all(|(name, value)| {
+                element.attributes.iter().any(|(attr_name, attr_value)| {
+                    attr_name == name && value.as_ref().map(|value| value == attr_value).unwrap_or(true)
+                })
+            })
+    }
+}
+
+/// A selector, consisting of compound selectors and the combinators between them.
+#[derive(Debug)]
+struct Selector {
+    /// The compound selectors, each with the combinator relating it to the previous one.
+    parts: Vec<(Combinator, Compound)>
+}
+
+impl Selector {
+
+    /// Parses a selector, returns `None` if it is not supported.
+    fn parse(selector: &str) -> Option<Selector> {
+        let chars = selector.chars().collect::<Vec<_>>();
+        let mut parts = Vec::new();
+        let mut combinator = Combinator::Descendant;
+        let mut pos = 0;
+
+        loop {
+            let mut had_whitespace = false;
+            while pos < chars.len() && chars[pos].is_whitespace() {
+                had_whitespace = true;
+                pos += 1;
+            }
+            if pos >= chars.len() {
+                break;
+            }
+            if chars[pos] == '>' {
+                if parts.is_empty() {
+                    return None;
+                }
+                combinator = Combinator::Child;
+                pos += 1;
+                continue;
+            }
+            if !parts.is_empty() && !had_whitespace && combinator != Combinator::Child {
+                return None;
+            }
+
+            let mut compound = Compound::default();
+            let mut is_empty = true;
+            while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != '>' {
+                let ch = chars[pos];
+                match ch {
+                    '*' if is_empty => pos += 1,
+                    '#' | '.' => {
+                        let name = read_identifier(&chars, &mut pos, 1)?;
+                        if ch == '#' {
+                            compound.id = Some(name);
+                        } else {
+                            compound.classes.push(name);
+                        }
+                    },
+                    '[' => {
+                        let end = chars[pos..].iter().position(|&ch| ch == ']')? + pos;
+                        let attribute = chars[pos + 1..end].iter().collect::<String>();
+                        let mut split = attribute.splitn(2, '=');
+                        let name = split.next()?.trim().to_ascii_lowercase();
+                        if name.is_empty() || !name.chars().all(is_identifier_char) {
+                            return None;
+                        }
+                        let value = split.next()
+                            .map(|value| value.trim().trim_matches(|ch| ch == '"' || ch == '\'').to_owned());
+                        compound.attributes.push((name, value));
+                        pos = end + 1;
+                    },
+                    _ if is_empty && is_identifier_char(ch) => {
+                        let name = read_identifier(&chars, &mut pos, 0)?;
+                        compound.element = Some(name.to_ascii_lowercase());
+                    },
+                    // pseudo classes/elements, sibling combinators etc.
+                    _ => return None
+                }
+                is_empty = false;
+            }
+            parts.push((combinator, compound));
+            combinator = Combinator::Descendant;
+        }
+
+        if parts.is_empty() || combinator == Combinator::Child {
+            None
+        } else {
+            Some(Selector { parts })
+        }
+    }
+
+    /// Returns the specificity as (ids, classes and attributes, elements).
+    fn specificity(&self) -> (u32, u32, u32) {
+        self.parts.iter().fold((0, 0, 0), |(ids, classes, elements), (_, compound)| {
+            (
+                ids + compound.id.is_some() as u32,
+                classes + (compound.classes.len() + compound.attributes.len()) as u32,
+                elements + compound.element.is_some() as u32
+            )
+        })
+    }
+
+    /// Checks if the selector matches the last element of the open elements.
+    fn matches(&self, open_elements: &[Element]) -> bool {
+        matches_parts(&self.parts, open_elements)
+    }
+}
+
+fn matches_parts(parts: &[(Combinator, Compound)], elements: &[Element]) -> bool {
+    let ((combinator, compound), parts) = match parts.split_last() {
+        Some(last) => last,
+        None => return true
+    };
+    let (element, ancestors) = match elements.split_last() {
+        Some(last) => last,
+        None => return false
+    };
+    if !compound.matches(element) {
+        return false;
+    }
+    if parts.is_empty() {
+        return true;
+    }
+    match *combinator {
+        Combinator::Child => matches_parts(parts, ancestors),
+        Combinator::Descendant => (0..ancestors.len())
+            .rev()
+            .any(|end| matches_parts(parts, &ancestors[..=end]))
+    }
+}
+
+fn is_identifier_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '-' || ch == '_'
+}
+
+/// Reads a identifier starting at `pos + skip`.
+fn read_identifier(chars: &[char], pos: &mut usize, skip: usize) -> Option<String> {
+    let start = *pos + skip;
+    let end = chars[start..].iter().position(|&ch| !is_identifier_char(ch))
+        .map(|end| start + end)
+        .unwrap_or(chars.len());
+    if start == end {
+        return None;
+    }
+    *pos = end;
+    Some(chars[start..end].iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::inline_css;
+
+    #[test]
+    fn inlines_rules_by_specificity() {
+        let html = concat!(
+            "<html><head><title>x</title><style type=\"text/css\">\n",
+            "/* base */ p { color: black; margin: 0 }\n",
+            "p.note, #first { color: blue }\n",
+            ".box > p { font-weight: bold; color: green !important }\n",
+            "td[align=right] { padding: 2px }\n",
+            "</style></head><body>",
+            "<p id=\"first\" class=\"note\" style=\"margin: 4px; color: red\">a</p>",
+            "<div class=\"box\"><p>b</p><span><p>c</p></span></div>",
+            "<table><tr><td align=right>d</td><td>e</td></tr></table><br/>",
+            "</body></html>"
+        );
+        assert_eq!(inline_css(html), concat!(
+            "<html><head><title>x</title></head><body>",
+            "<p id=\"first\" class=\"note\" style=\"margin: 4px; color: red\">a</p>",
+            "<div class=\"box\"><p style=\"margin: 0; font-weight: bold; color: green !important\">b</p>",
+            "<span><p style=\"color: black; margin: 0\">c</p></span></div>",
+            "<table><tr><td align=right style=\"padding: 2px\">d</td><td>e</td></tr></table><br/>",
+            "</body></html>"
+        ));
+    }
+
+    #[test]
+    fn keeps_media_queries_and_pseudo_classes() {
+        let html = concat!(
+            "<style>a { color: #00f; font-family: \"Open Sans\", sans-serif }\n",
+            "a:hover { color: red }\n",
+            "@media (max-width: 600px) { a { display: block !important } }</style>",
+            "<style media=\"print\">a { color: black }</style>",
+            "<p><a href=\"https://example.test\">link</a><img src=\"x.png\" /></p>"
+        );
+        assert_eq!(inline_css(html), concat!(
+            "<style type=\"text/css\">\n",
+            "a:hover { color: red }\n",
+            "@media (max-width: 600px) { a { display: block !important } }\n",
+            "</style>",
+            "<style media=\"print\">a { color: black }</style>",
+            "<p><a href=\"https://example.test\" ",
+            "style=\"color: #00f; font-family: &quot;Open Sans&quot;, sans-serif\">link</a>",
+            "<img src=\"x.png\" /></p>"
+        ));
+    }
+}
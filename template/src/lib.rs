@@ -3,9 +3,12 @@ extern crate serde;
 extern crate futures;
 extern crate mail_core;
 extern crate mail_headers;
+extern crate mail_internals;
 extern crate vec1;
 extern crate toml;
 extern crate maybe_owned;
+#[cfg(test)]
+extern crate soft_ascii_string;
 #[cfg(feature="handlebars")]
 extern crate handlebars as hbs;
 
@@ -49,6 +52,8 @@ use mail_headers::{
 mod base_dir;
 mod path_rebase;
 mod additional_cid;
+mod embed_images;
+mod inline_css;
 pub mod serde_impl;
 pub mod error;
 
@@ -58,6 +63,8 @@ pub mod handlebars;
 pub use self::base_dir::*;
 pub use self::path_rebase::*;
 pub use self::additional_cid::*;
+pub use self::embed_images::*;
+pub use self::inline_css::*;
 
 /// Trait used to bind/implement template engines.
 pub trait TemplateEngine: Sized {
@@ -145,12 +152,17 @@ pub struct Template<TE: TemplateEngine> {
     //TODO: make sure
     embeddings: HashMap<String, Resource>,
     attachments: Vec<Resource>,
+    inline_css: bool,
     engine: TE,
 }
 
 impl<TE> Template<TE>
     where TE: TemplateEngine
 {
+    pub fn base_dir(&self) -> &CwdBaseDir {
+        &self.base_dir
+    }
+
     pub fn inline_embeddings(&self) -> &HashMap<String, Resource> {
         &self.embeddings
     }
@@ -159,6 +171,11 @@ impl<TE> Template<TE>
         &self.attachments
     }
 
+    /// Returns true if css is inlined into `text/html` bodies when rendering (see `inline_css`).
+    pub fn inline_css(&self) -> bool {
+        self.inline_css
+    }
+
     pub fn engine(&self) -> &TE {
         &self.engine
     }
@@ -266,6 +283,13 @@ impl<TE, D> TemplateExt<TE, D> for Template<TE>
                 ])
             )?;
 
+            let raw =
+                if self.inline_css() && body.media_type().full_type() == "text/html" {
+                    inline_css(&raw)
+                } else {
+                    raw
+                };
+
             let data = Data::new(
                 raw.into_bytes(),
                 Metadata {
@@ -280,11 +304,10 @@ impl<TE, D> TemplateExt<TE, D> for Template<TE>
                 .cloned()
                 .collect();
 
-            bodies.push(BodyPart {
-                resource: Resource::Data(data),
-                inline_embeddings,
-                attachments: Vec::new()
-            });
+            bodies.push(
+                BodyPart::new(Resource::Data(data))
+                    .with_inline_embeddings(inline_embeddings)
+            );
         }
 
         attachments.extend(self.attachments().iter().cloned());
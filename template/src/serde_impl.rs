@@ -57,6 +57,9 @@ pub struct TemplateBase<TE: TemplateEngine> {
     #[serde(default)]
     #[serde(deserialize_with="deserialize_attachments")]
     attachments: Vec<Resource>,
+    /// If css from `<style>` elements should be inlined into `text/html` bodies.
+    #[serde(default)]
+    inline_css: bool
 }
 
 impl<TE> TemplateBase<TE>
@@ -72,7 +75,8 @@ impl<TE> TemplateBase<TE>
             subject,
             bodies,
             mut embeddings,
-            mut attachments
+            mut attachments,
+            inline_css
         } = self;
 
         let base_dir = base_dir.unwrap_or(default_base_dir);
@@ -118,7 +122,7 @@ impl<TE> TemplateBase<TE>
         let fut = loading_embeddings
             .join3(loading_attachments, loading_body_embeddings)
             .map_err(Error::from)
-            .map(|(embeddings, attachments, body_embeddings)| {
+            .map(move |(embeddings, attachments, body_embeddings)| {
                 for (body, loaded_embeddings) in bodies.iter_mut().zip(body_embeddings) {
                     mem::replace(&mut body.inline_embeddings, loaded_embeddings);
                 }
@@ -129,6 +133,7 @@ impl<TE> TemplateBase<TE>
                     bodies,
                     embeddings,
                     attachments,
+                    inline_css,
                     engine
                 }
             });